@@ -0,0 +1,43 @@
+// Copyright (c) 2022 solarliner
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+#![feature(portable_simd)]
+#![feature(test)]
+
+extern crate test;
+
+use std::simd::f32x2;
+
+use nih_reverb::biquad::{Biquad, BiquadParams, CoefficientCache};
+use test::{black_box, Bencher};
+
+/// Cutoff held steady, as when the damping is not being automated.
+const CUTOFF: f32 = 3000. / 44100.;
+
+#[bench]
+fn damping_recompute(b: &mut Bencher) {
+    let mut biquad = Biquad::<2>::default();
+    b.iter(|| {
+        for _ in 0..1024 {
+            biquad.params = BiquadParams::lowpass_1p(f32x2::splat(black_box(CUTOFF)));
+            black_box(biquad.next_sample(f32x2::splat(1.)));
+        }
+    });
+}
+
+#[bench]
+fn damping_cached(b: &mut Bencher) {
+    let mut biquad = Biquad::<2>::default();
+    let mut cache = CoefficientCache::default();
+    b.iter(|| {
+        for _ in 0..1024 {
+            let params = cache.get(black_box(CUTOFF), |fc| {
+                BiquadParams::lowpass_1p(f32x2::splat(fc))
+            });
+            biquad.set_params_smoothed(params, 0.01);
+            black_box(biquad.next_sample(f32x2::splat(1.)));
+        }
+    });
+}
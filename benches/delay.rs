@@ -0,0 +1,38 @@
+// Copyright (c) 2022 solarliner
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+#![feature(portable_simd)]
+#![feature(test)]
+
+extern crate test;
+
+use std::simd::f32x2;
+
+use nih_reverb::delay::{Delay, RingDelay};
+use test::{black_box, Bencher};
+
+const MAX_DELAY: usize = 1 << 16;
+
+#[bench]
+fn delay_vecdeque(b: &mut Bencher) {
+    let mut delay = Delay::<f32x2>::new(MAX_DELAY);
+    b.iter(|| {
+        for i in 0..1024 {
+            let out = delay.tap(black_box(i as f32 * 37.3));
+            delay.push_next(out + f32x2::splat(1.));
+        }
+    });
+}
+
+#[bench]
+fn delay_ring(b: &mut Bencher) {
+    let mut delay = RingDelay::<f32x2>::new(MAX_DELAY);
+    b.iter(|| {
+        for i in 0..1024 {
+            let out = delay.tap(black_box(i as f32 * 37.3));
+            delay.push_next(out + f32x2::splat(1.));
+        }
+    });
+}
@@ -0,0 +1,45 @@
+// Copyright (c) 2022 solarliner
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+#![feature(portable_simd)]
+#![feature(test)]
+
+extern crate test;
+
+use std::simd::f32x4;
+
+use nih_reverb::householder::transform;
+use test::{black_box, Bencher};
+
+fn inputs() -> Vec<f32x4> {
+    (0..1024)
+        .map(|i| f32x4::from_array([i as f32, -(i as f32), 0.5, i as f32 * 0.25]))
+        .collect()
+}
+
+#[bench]
+fn householder_scalar_sum(b: &mut Bencher) {
+    let inputs = inputs();
+    b.iter(|| {
+        for &x in &inputs {
+            let mut v = black_box(x);
+            let s = v.to_array().into_iter().sum::<f32>() * -0.5;
+            for i in 0..4 {
+                v[i] += s;
+            }
+            black_box(v);
+        }
+    });
+}
+
+#[bench]
+fn householder_simd(b: &mut Bencher) {
+    let inputs = inputs();
+    b.iter(|| {
+        for &x in &inputs {
+            black_box(transform(black_box(x)));
+        }
+    });
+}
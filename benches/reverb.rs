@@ -0,0 +1,45 @@
+// Copyright (c) 2022 solarliner
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+#![feature(portable_simd)]
+#![feature(test)]
+
+extern crate test;
+
+use std::simd::f32x2;
+
+use nih_reverb::Reverb;
+use test::{black_box, Bencher};
+
+/// Buffer size of a typical host callback.
+const BUFFER_SIZE: usize = 512;
+
+fn buffer() -> Vec<f32x2> {
+    (0..BUFFER_SIZE)
+        .map(|i| f32x2::splat(f32::sin(i as f32 * 0.05)))
+        .collect()
+}
+
+#[bench]
+fn per_sample(b: &mut Bencher) {
+    let mut reverb = Reverb::default();
+    let mut frames = buffer();
+    b.iter(|| {
+        for frame in frames.iter_mut() {
+            reverb.process_in_place(std::slice::from_mut(frame));
+        }
+        black_box(&frames);
+    });
+}
+
+#[bench]
+fn block(b: &mut Bencher) {
+    let mut reverb = Reverb::default();
+    let mut frames = buffer();
+    b.iter(|| {
+        reverb.process_in_place(&mut frames);
+        black_box(&frames);
+    });
+}
@@ -0,0 +1,40 @@
+// Copyright (c) 2022 solarliner
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+#![feature(portable_simd)]
+#![feature(test)]
+
+extern crate test;
+
+use std::simd::f32x4;
+
+use nih_reverb::simdmath::{simd_f32func, simd_f32tanh};
+use test::{black_box, Bencher};
+
+fn inputs() -> Vec<f32x4> {
+    (0..1024)
+        .map(|i| f32x4::splat(i as f32 / 64. - 8.))
+        .collect()
+}
+
+#[bench]
+fn tanh_scalar(b: &mut Bencher) {
+    let inputs = inputs();
+    b.iter(|| {
+        for &x in &inputs {
+            black_box(simd_f32func(f32::tanh, x));
+        }
+    });
+}
+
+#[bench]
+fn tanh_simd(b: &mut Bencher) {
+    let inputs = inputs();
+    b.iter(|| {
+        for &x in &inputs {
+            black_box(simd_f32tanh(x));
+        }
+    });
+}
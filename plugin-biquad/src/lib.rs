@@ -2,27 +2,65 @@
 //
 // This software is released under the MIT License.
 // https://opensource.org/licenses/MIT
-#![feature(portable_simd)]
+#![cfg_attr(feature = "simd", feature(portable_simd))]
+#![cfg_attr(feature = "simd", feature(array_from_fn))]
 
-use std::{
-    simd::{LaneCount, Simd, SupportedLaneCount},
-    sync::{atomic::AtomicBool, Arc},
-};
+use std::sync::{atomic::AtomicBool, Arc};
 
 use nih_plug::prelude::*;
 use nih_reverb::biquad::{Biquad, BiquadParams};
+use nih_reverb::simd::{LaneCount, Simd, SupportedLaneCount};
+
+/// Per-sample interpolation amount applied to the filter coefficients to avoid zipper noise.
+const COEFFS_SMOOTHING: f32 = 5e-3;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
 enum BiquadMode {
     #[id = "lp"]
-    #[name = "LP (24)"]
+    #[name = "LP (6)"]
     Lowpass,
     #[id = "hp"]
-    #[name = "HP (24)"]
+    #[name = "HP (6)"]
     Highpass,
     #[id = "bp"]
-    #[name = "BP (24)"]
+    #[name = "BP (12)"]
     Bandpass,
+    #[id = "ap"]
+    #[name = "AP (12)"]
+    Allpass,
+    #[id = "peak"]
+    #[name = "Peak"]
+    Peaking,
+    #[id = "ls"]
+    #[name = "Low Shelf"]
+    LowShelf,
+    #[id = "hs"]
+    #[name = "High Shelf"]
+    HighShelf,
+}
+
+impl BiquadMode {
+    /// Filter coefficients for this mode, with `fc` normalized to the sample rate. The gain is
+    /// only used by the peaking and shelving modes.
+    fn biquad_params<const N: usize>(
+        self,
+        fc: Simd<f32, N>,
+        q: Simd<f32, N>,
+        gain_db: Simd<f32, N>,
+    ) -> BiquadParams<N>
+    where
+        LaneCount<N>: SupportedLaneCount,
+    {
+        match self {
+            Self::Lowpass => BiquadParams::lowpass_1p(fc),
+            Self::Highpass => BiquadParams::highpass_1p(fc),
+            Self::Bandpass => BiquadParams::bandpass(fc, q),
+            Self::Allpass => BiquadParams::allpass(fc, q),
+            Self::Peaking => BiquadParams::peaking(fc, q, gain_db),
+            Self::LowShelf => BiquadParams::low_shelf(fc, q, gain_db),
+            Self::HighShelf => BiquadParams::high_shelf(fc, q, gain_db),
+        }
+    }
 }
 
 #[derive(Params)]
@@ -33,6 +71,12 @@ struct PluginParams {
     frequency: FloatParam,
     #[id = "q"]
     q: FloatParam,
+    /// Boost or cut of the peaking and shelving modes, in decibels.
+    #[id = "gain"]
+    gain: FloatParam,
+    /// Offset between the left and right cutoffs, in semitones.
+    #[id = "stoff"]
+    stereo_offset: FloatParam,
 }
 
 #[derive(Debug, Default, Clone)]
@@ -77,6 +121,24 @@ impl PluginParams {
                     factor: FloatRange::skew_factor(-2.5),
                 },
             ),
+            gain: FloatParam::new(
+                "Gain",
+                0.0,
+                FloatRange::Linear {
+                    min: -24.0,
+                    max: 24.0,
+                },
+            )
+            .with_unit(" dB"),
+            stereo_offset: FloatParam::new(
+                "Stereo Offset",
+                0.0,
+                FloatRange::Linear {
+                    min: -24.0,
+                    max: 24.0,
+                },
+            )
+            .with_unit(" st"),
         }
     }
 
@@ -84,17 +146,33 @@ impl PluginParams {
     where
         LaneCount<N>: SupportedLaneCount,
     {
-        let fc = Simd::splat(self.frequency.smoothed.next() / sr / 2.0);
+        let fc = lane_cutoffs(
+            self.frequency.smoothed.next(),
+            self.stereo_offset.smoothed.next(),
+        ) / Simd::splat(sr * 2.0);
         let q = Simd::splat(self.q.smoothed.next());
+        let gain_db = Simd::splat(self.gain.smoothed.next());
 
-        match self.mode.value() {
-            BiquadMode::Lowpass => BiquadParams::lowpass_1p(fc, q),
-            BiquadMode::Bandpass => BiquadParams::bandpass(fc, q),
-            BiquadMode::Highpass => BiquadParams::highpass_1p(fc, q),
-        }
+        self.mode.value().biquad_params(fc, q, gain_db)
     }
 }
 
+/// Cutoff of each lane, spreading `offset` semitones evenly across the lanes around `frequency`.
+/// With two lanes, the left cutoff is `offset / 2` semitones below and the right one above.
+fn lane_cutoffs<const N: usize>(frequency: f32, offset: f32) -> Simd<f32, N>
+where
+    LaneCount<N>: SupportedLaneCount,
+{
+    Simd::from_array(std::array::from_fn(|i| {
+        let t = if N > 1 {
+            i as f32 / (N - 1) as f32 - 0.5
+        } else {
+            0.
+        };
+        frequency * f32::powf(2., offset * t / 12.)
+    }))
+}
+
 #[derive(Clone)]
 struct BiquadPlugin<const N: usize>
 where
@@ -130,7 +208,8 @@ where
             self.biquad.reset();
         }
 
-        self.biquad.params = self.params.next_biquad_params(sr);
+        self.biquad
+            .set_params_smoothed(self.params.next_biquad_params(sr), COEFFS_SMOOTHING);
 
         self.biquad.next_sample(input)
     }
@@ -190,7 +269,14 @@ impl Plugin for BiquadPlugin<2> {
     ) -> ProcessStatus {
         let samplerate = context.transport().sample_rate;
         for mut channels in buffer.iter_samples() {
-            channels.from_simd(self.next_sample(samplerate, channels.to_simd()));
+            let mut input = [0.; 2];
+            for (sample, channel) in input.iter_mut().zip(channels.iter_mut()) {
+                *sample = *channel;
+            }
+            let output = self.next_sample(samplerate, Simd::from_array(input));
+            for (channel, sample) in channels.iter_mut().zip(output.to_array()) {
+                *channel = sample;
+            }
         }
         ProcessStatus::Normal
     }
@@ -203,3 +289,78 @@ impl Vst3Plugin for BiquadPlugin<2> {
 }
 
 nih_export_vst3!(BiquadPlugin::<2>);
+
+impl ClapPlugin for BiquadPlugin<2> {
+    const CLAP_ID: &'static str = "com.solarliner.nih-reverb.biquad";
+
+    const CLAP_DESCRIPTION: Option<&'static str> = Some("Stereo biquad filter");
+
+    const CLAP_MANUAL_URL: Option<&'static str> = None;
+
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::AudioEffect,
+        ClapFeature::Filter,
+        ClapFeature::Stereo,
+    ];
+}
+
+nih_export_clap!(BiquadPlugin::<2>);
+
+#[cfg(test)]
+mod tests {
+    use nih_reverb::biquad::{Biquad, BiquadParams};
+    use nih_reverb::simd::f32x2;
+
+    use super::{lane_cutoffs, BiquadMode};
+
+    #[test]
+    fn every_mode_runs() {
+        for mode in [
+            BiquadMode::Lowpass,
+            BiquadMode::Highpass,
+            BiquadMode::Bandpass,
+            BiquadMode::Allpass,
+            BiquadMode::Peaking,
+            BiquadMode::LowShelf,
+            BiquadMode::HighShelf,
+        ] {
+            let params = mode.biquad_params(
+                f32x2::splat(1000. / 48e3),
+                f32x2::splat(std::f32::consts::FRAC_1_SQRT_2),
+                f32x2::splat(6.),
+            );
+            let mut biquad = Biquad::new(params);
+            let mut buffer: Vec<f32x2> = (0..512)
+                .map(|i| f32x2::splat(if i == 0 { 1. } else { 0. }))
+                .collect();
+            biquad.process_block(&mut buffer);
+            assert!(
+                buffer
+                    .iter()
+                    .all(|x| x.to_array().iter().all(|x| x.is_finite())),
+                "{mode:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn offset_spreads_cutoffs() {
+        let fc: f32x2 = lane_cutoffs(1000., 24.);
+        assert!((fc[0] - 500.).abs() < 1e-2, "{fc:?}");
+        assert!((fc[1] - 2000.).abs() < 1e-2, "{fc:?}");
+        assert_eq!(f32x2::splat(1000.), lane_cutoffs(1000., 0.));
+    }
+
+    #[test]
+    fn lanes_have_different_responses() {
+        let sr = 48e3;
+        let fc = lane_cutoffs::<2>(1000., 24.) / f32x2::splat(sr);
+        let params = BiquadParams::lowpass(fc, f32x2::splat(std::f32::consts::FRAC_1_SQRT_2));
+        let (left, _) = params.response_lane(1000. / sr, 0);
+        let (right, _) = params.response_lane(1000. / sr, 1);
+        assert!(left < 0.5, "{left}");
+        assert!(right > 0.9, "{right}");
+    }
+}
@@ -0,0 +1,202 @@
+// Copyright (c) 2022 solarliner
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+use nih_plug::prelude::*;
+
+use crate::DelayParams;
+
+/// Key of the active slot in the persisted fields of the state.
+const SLOT_FIELD: &str = "abslot";
+/// Prefix of the keys holding the values of the inactive slot, followed by the parameter ID.
+const STORED_PREFIX: &str = "abstored.";
+
+/// A/B comparison of two sets of parameter values. The parameters hold the settings of the active
+/// slot, while the other one is kept here as normalized values. Both are persisted with the state,
+/// and states saved without them start out on A, with B unset.
+#[derive(Default)]
+pub(crate) struct AbComparison {
+    slot_b: AtomicBool,
+    /// Normalized values of the inactive slot by parameter ID, empty until the first switch.
+    stored: RwLock<BTreeMap<String, f32>>,
+}
+
+impl AbComparison {
+    /// Whether B is the active slot.
+    pub fn slot_b(&self) -> bool {
+        self.slot_b.load(Ordering::Relaxed)
+    }
+}
+
+unsafe impl Params for AbComparison {
+    fn param_map(&self) -> Vec<(String, ParamPtr, String)> {
+        Vec::new()
+    }
+
+    fn serialize_fields(&self) -> BTreeMap<String, String> {
+        let slot = if self.slot_b() { "b" } else { "a" };
+        let stored = self.stored.read().unwrap();
+        std::iter::once((SLOT_FIELD.to_string(), slot.to_string()))
+            .chain(
+                stored
+                    .iter()
+                    .map(|(id, value)| (format!("{STORED_PREFIX}{id}"), value.to_string())),
+            )
+            .collect()
+    }
+
+    fn deserialize_fields(&self, serialized: &BTreeMap<String, String>) {
+        let slot_b = serialized.get(SLOT_FIELD).map(String::as_str) == Some("b");
+        self.slot_b.store(slot_b, Ordering::Relaxed);
+        *self.stored.write().unwrap() = serialized
+            .iter()
+            .filter_map(|(key, value)| {
+                let id = key.strip_prefix(STORED_PREFIX)?;
+                Some((id.to_string(), value.parse().ok()?))
+            })
+            .collect();
+    }
+}
+
+/// Normalized value of every parameter of `params`, by parameter ID.
+fn normalized_values(params: &DelayParams) -> BTreeMap<String, f32> {
+    params
+        .param_map()
+        .into_iter()
+        // SAFETY: the parameters are alive for the duration of this function
+        .map(|(id, param, _)| (id, unsafe { param.normalized_value() }))
+        .collect()
+}
+
+/// Parameters of `params` paired with their normalized value in `values`. Parameters missing from
+/// it, added after the values were stored, are left out and keep their current value.
+fn to_set(params: &DelayParams, values: &BTreeMap<String, f32>) -> Vec<(ParamPtr, f32)> {
+    params
+        .param_map()
+        .into_iter()
+        .filter_map(|(id, param, _)| Some((param, *values.get(&id)?)))
+        .collect()
+}
+
+/// Stores the current settings in the active slot and switches to the other one, returning the
+/// normalized value to set each parameter to. The first switch starts B off as a copy of A.
+pub(crate) fn switch(params: &DelayParams) -> Vec<(ParamPtr, f32)> {
+    let current = normalized_values(params);
+    let mut stored = params.ab.stored.write().unwrap();
+    let other = if stored.is_empty() {
+        current.clone()
+    } else {
+        std::mem::take(&mut *stored)
+    };
+    *stored = current;
+    params.ab.slot_b.fetch_xor(true, Ordering::Relaxed);
+    to_set(params, &other)
+}
+
+/// Copies the settings of A over B. When B is the active slot, this returns the normalized value
+/// to set each parameter to; otherwise only the stored B changes, and this returns nothing.
+pub(crate) fn copy_a_to_b(params: &DelayParams) -> Vec<(ParamPtr, f32)> {
+    let mut stored = params.ab.stored.write().unwrap();
+    if params.ab.slot_b() {
+        to_set(params, &stored)
+    } else {
+        *stored = normalized_values(params);
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use nih_plug::prelude::*;
+
+    use super::{copy_a_to_b, switch};
+    use crate::DelayParams;
+
+    fn mix(params: &DelayParams) -> ParamPtr {
+        params
+            .param_map()
+            .into_iter()
+            .find(|(id, _, _)| id == "mix")
+            .unwrap()
+            .1
+    }
+
+    fn set_mix(params: &DelayParams, value: f32) {
+        unsafe { mix(params).set_normalized_value(value) };
+    }
+
+    fn get_mix(params: &DelayParams) -> f32 {
+        unsafe { mix(params).normalized_value() }
+    }
+
+    /// Sets the parameters as the editor would.
+    fn apply(values: Vec<(ParamPtr, f32)>) {
+        for (param, value) in values {
+            unsafe { param.set_normalized_value(value) };
+        }
+    }
+
+    #[test]
+    fn switch_swaps_settings() {
+        let params = DelayParams::default();
+        assert!(!params.ab.slot_b());
+
+        // B starts as a copy of A
+        set_mix(&params, 0.3);
+        apply(switch(&params));
+        assert!(params.ab.slot_b());
+        assert_eq!(0.3, get_mix(&params));
+
+        // Edits to B are kept aside while A is active
+        set_mix(&params, 0.8);
+        apply(switch(&params));
+        assert!(!params.ab.slot_b());
+        assert_eq!(0.3, get_mix(&params));
+        apply(switch(&params));
+        assert!(params.ab.slot_b());
+        assert_eq!(0.8, get_mix(&params));
+    }
+
+    #[test]
+    fn copy_overwrites_b() {
+        let params = DelayParams::default();
+        apply(switch(&params));
+        apply(switch(&params));
+        set_mix(&params, 0.5);
+        assert!(copy_a_to_b(&params).is_empty());
+        apply(switch(&params));
+        assert_eq!(0.5, get_mix(&params));
+
+        // Copying while on B loads A in its place
+        set_mix(&params, 0.9);
+        apply(copy_a_to_b(&params));
+        assert!(params.ab.slot_b());
+        assert_eq!(0.5, get_mix(&params));
+    }
+
+    #[test]
+    fn slots_are_persisted() {
+        let params = DelayParams::default();
+        set_mix(&params, 0.3);
+        apply(switch(&params));
+        set_mix(&params, 0.8);
+
+        let loaded = DelayParams::default();
+        loaded.ab.deserialize_fields(&params.ab.serialize_fields());
+        assert!(loaded.ab.slot_b());
+        apply(switch(&loaded));
+        assert_eq!(0.3, get_mix(&loaded));
+
+        // States saved without the comparison start on A, with B unset
+        loaded.ab.deserialize_fields(&BTreeMap::new());
+        assert!(!loaded.ab.slot_b());
+        assert!(loaded.ab.stored.read().unwrap().is_empty());
+    }
+}
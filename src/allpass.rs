@@ -0,0 +1,306 @@
+// Copyright (c) 2022 solarliner
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use crate::delay::Delay;
+use crate::simd::{f32x2, LaneCount, Simd, SupportedLaneCount};
+
+/// Delay times of the allpass stages at the largest size, in seconds. Taken from the input
+/// diffusers of Dattorro's plate, and extended with mutually prime times in the same range.
+const TIMES: [f32; 8] = [
+    4.771e-3, 3.595e-3, 12.73e-3, 9.307e-3, 7.129e-3, 5.531e-3, 10.57e-3, 8.123e-3,
+];
+/// Scale applied to the delay times at the smallest size.
+const MIN_SCALE: f32 = 0.25;
+/// Spread of the delay times between lanes, so that the lanes decorrelate.
+const LANE_SPREAD: f32 = 0.07;
+/// Delay times of the decorrelation stages for the left and right channels, in seconds. Short
+/// enough not to be heard as echoes, and mutually prime between the channels.
+const DECORRELATION_TIMES: [[f32; 4]; 2] = [
+    [1.153e-3, 2.269e-3, 3.541e-3, 4.177e-3],
+    [1.381e-3, 1.987e-3, 3.109e-3, 4.733e-3],
+];
+/// Gain of the decorrelation stages.
+const DECORRELATION_GAIN: f32 = 0.5;
+/// Largest gain of the diffusion stages. An allpass is only stable below unity gain, and close to
+/// it the diffusion rings at the stage delays.
+pub const MAX_GAIN: f32 = 0.8;
+
+/// Schroeder allpass filter: a feedforward/feedback comb pair with a flat magnitude response.
+#[derive(Debug, Clone)]
+pub struct Allpass<const L: usize>
+where
+    LaneCount<L>: SupportedLaneCount,
+{
+    delay: Delay<Simd<f32, L>>,
+    /// State of the Thiran interpolator.
+    thiran: Simd<f32, L>,
+    pub gain: f32,
+}
+
+impl<const L: usize> Allpass<L>
+where
+    LaneCount<L>: SupportedLaneCount,
+{
+    pub fn new(max_delay: usize, gain: f32) -> Self {
+        Self {
+            delay: Delay::new(max_delay),
+            thiran: Simd::splat(0.),
+            gain,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.delay.reset();
+        self.thiran = Simd::splat(0.);
+    }
+
+    /// Processes the next sample, with the delay of each lane in samples. Fractional delays are
+    /// read with cubic interpolation, which colors the response slightly.
+    pub fn next_sample(&mut self, pos: Simd<f32, L>, x: Simd<f32, L>) -> Simd<f32, L> {
+        let delayed = self.delay.get(pos);
+        self.feed(delayed, x)
+    }
+
+    /// Processes the next sample like [`Self::next_sample`], reading fractional delays with a
+    /// Thiran allpass interpolator so that the magnitude response stays flat.
+    pub fn next_sample_thiran(&mut self, pos: Simd<f32, L>, x: Simd<f32, L>) -> Simd<f32, L> {
+        let delayed = self.delay.get_thiran(pos, &mut self.thiran);
+        self.feed(delayed, x)
+    }
+
+    fn feed(&mut self, delayed: Simd<f32, L>, x: Simd<f32, L>) -> Simd<f32, L> {
+        let gain = Simd::splat(self.gain);
+        let w = x + gain * delayed;
+        self.delay.push_next(w);
+        delayed - gain * w
+    }
+}
+
+/// Series of `N` Schroeder allpass filters diffusing `L` lanes.
+#[derive(Debug, Clone)]
+pub struct AllpassLine<const N: usize, const L: usize>
+where
+    LaneCount<L>: SupportedLaneCount,
+{
+    stages: [Allpass<L>; N],
+    /// Delay of each stage and lane at the largest size, in samples.
+    lengths: [Simd<f32, L>; N],
+}
+
+impl<const N: usize, const L: usize> AllpassLine<N, L>
+where
+    LaneCount<L>: SupportedLaneCount,
+{
+    pub fn new(samplerate: f32, gain: f32) -> Self {
+        assert!(N <= TIMES.len(), "at most {} allpass stages", TIMES.len());
+        Self::with_times(samplerate, gain, std::array::from_fn(|i| TIMES[i]))
+    }
+
+    /// Creates the stages with the given delay times at the largest size, in seconds.
+    pub fn with_times(samplerate: f32, gain: f32, times: [f32; N]) -> Self {
+        let lengths = std::array::from_fn(|i| {
+            Simd::from_array(std::array::from_fn(|lane| {
+                (times[i] * samplerate * (1. + LANE_SPREAD * lane as f32)).round()
+            }))
+        });
+        let max_len = lengths
+            .iter()
+            .map(|len: &Simd<f32, L>| len.reduce_max())
+            .fold(0., f32::max);
+        Self {
+            stages: std::array::from_fn(|_| Allpass::new(max_len as usize + 4, gain)),
+            lengths,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for stage in &mut self.stages {
+            stage.reset();
+        }
+    }
+
+    /// Sets the gain of all stages, clamped to [`MAX_GAIN`] to keep them stable. Higher gains
+    /// give longer, more resonant diffusion.
+    pub fn set_gain(&mut self, gain: f32) {
+        let gain = gain.clamp(0., MAX_GAIN);
+        for stage in &mut self.stages {
+            stage.gain = gain;
+        }
+    }
+
+    /// Total delay through the stages at the given size, in samples.
+    pub fn mean_delay(&self, size: f32) -> f32 {
+        let scale = MIN_SCALE + (1. - MIN_SCALE) * size;
+        let sum: f32 = self.lengths.iter().map(|len| len.reduce_sum()).sum();
+        sum / L as f32 * scale
+    }
+
+    pub fn next_sample(&mut self, size: f32, x: Simd<f32, L>) -> Simd<f32, L> {
+        let scale = Simd::splat(MIN_SCALE + (1. - MIN_SCALE) * size);
+        self.stages
+            .iter_mut()
+            .zip(&self.lengths)
+            .fold(x, |x, (stage, &len)| {
+                stage.next_sample_thiran(len * scale, x)
+            })
+    }
+}
+
+/// Decorrelates the channels of a stereo signal by running each one through its own allpass
+/// chain. Only the side signal is changed, so that the mono sum is left as it is.
+#[derive(Debug, Clone)]
+pub struct Decorrelator {
+    channels: [AllpassLine<4, 1>; 2],
+}
+
+impl Decorrelator {
+    pub fn new(samplerate: f32) -> Self {
+        Self {
+            channels: DECORRELATION_TIMES
+                .map(|times| AllpassLine::with_times(samplerate, DECORRELATION_GAIN, times)),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for channel in &mut self.channels {
+            channel.reset();
+        }
+    }
+
+    /// Processes the next frame, moving the side signal towards the decorrelated one by `amount`.
+    pub fn next_sample(&mut self, amount: f32, x: f32x2) -> f32x2 {
+        let [l, r] = x.to_array();
+        let dl = self.channels[0].next_sample(1., Simd::from_array([l]))[0];
+        let dr = self.channels[1].next_sample(1., Simd::from_array([r]))[0];
+        let side = (l - r) / 2.;
+        let delta = ((dl - dr) / 2. - side) * amount;
+        f32x2::from_array([l + delta, r - delta])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::TAU;
+
+    use rand::prelude::*;
+
+    use crate::simd::f32x2;
+
+    use super::{Allpass, AllpassLine, Decorrelator, MAX_GAIN};
+
+    /// Steady-state gain of each lane for a sine at `freq` Hz.
+    fn sine_gain(samplerate: f32, freq: f32, mut process: impl FnMut(f32x2) -> f32x2) -> [f32; 2] {
+        let len = samplerate as usize;
+        let output: Vec<f32x2> = (0..len)
+            .map(|i| process(f32x2::splat(f32::sin(TAU * freq * i as f32 / samplerate))))
+            .collect();
+        // Skip the transient while the stages fill up
+        let steady = &output[len / 2..];
+        let power = steady.iter().map(|x| x * x).sum::<f32x2>() / f32x2::splat(steady.len() as f32);
+        power.to_array().map(|p| (2. * p).sqrt())
+    }
+
+    #[test]
+    fn magnitude_is_flat() {
+        let samplerate = 48e3;
+        let mut freq = 50.;
+        while freq < 20e3 {
+            // A fractional size puts the taps between samples
+            let mut line = AllpassLine::<4, 2>::new(samplerate, 0.6);
+            for gain in sine_gain(samplerate, freq, |x| line.next_sample(0.7, x)) {
+                assert!((gain - 1.).abs() < 1e-2, "gain {gain} at {freq} Hz");
+            }
+            freq *= 1.5;
+        }
+    }
+
+    #[test]
+    fn thiran_is_flatter_than_cubic() {
+        let samplerate = 48e3;
+        let pos = f32x2::splat(100.5);
+        let freq = 15e3;
+        let mut cubic = Allpass::<2>::new(128, 0.6);
+        let mut thiran = Allpass::<2>::new(128, 0.6);
+        let cubic = sine_gain(samplerate, freq, |x| cubic.next_sample(pos, x))[0];
+        let thiran = sine_gain(samplerate, freq, |x| thiran.next_sample_thiran(pos, x))[0];
+        assert!((thiran - 1.).abs() < 1e-2, "thiran gain {thiran}");
+        assert!(
+            (cubic - 1.).abs() > (thiran - 1.).abs(),
+            "cubic gain {cubic}"
+        );
+    }
+
+    #[test]
+    fn stable_up_to_max_gain() {
+        let samplerate = 48e3;
+        for gain in [0., 0.3, 0.5, MAX_GAIN, 0.99, 1., 1.5] {
+            let mut line = AllpassLine::<4, 2>::new(samplerate, 0.6);
+            line.set_gain(gain);
+            let mut rng = StdRng::seed_from_u64(0);
+            let mut peak = 0f32;
+            for _ in 0..samplerate as usize {
+                let x = rng.gen::<f32>() - 0.5;
+                let y = line.next_sample(0.5, f32x2::splat(x));
+                peak = peak.max(y.abs().reduce_max());
+            }
+            assert!(peak.is_finite() && peak < 4., "peak {peak} at gain {gain}");
+            // Rings out once the input stops
+            let tail = (0..samplerate as usize)
+                .map(|_| line.next_sample(0.5, f32x2::splat(0.)))
+                .last()
+                .unwrap();
+            assert!(
+                tail.abs().reduce_max() < 1e-3,
+                "tail {tail:?} at gain {gain}"
+            );
+        }
+    }
+
+    #[test]
+    fn reset_silences() {
+        let mut line = AllpassLine::<4, 2>::new(48e3, 0.6);
+        line.next_sample(1., f32x2::splat(1.));
+        line.reset();
+        for _ in 0..2000 {
+            assert_eq!(f32x2::splat(0.), line.next_sample(1., f32x2::splat(0.)));
+        }
+    }
+
+    /// Correlation between the channels and level of the mono sum, for a mono noise input.
+    fn decorrelated(amount: f32) -> (f32, f32) {
+        let mut decorrelator = Decorrelator::new(48e3);
+        let mut seed = 1u32;
+        let out: Vec<f32x2> = (0..48000)
+            .map(|_| {
+                // Xorshift, to keep the input the same across amounts
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                let x = seed as f32 / u32::MAX as f32 - 0.5;
+                decorrelator.next_sample(amount, f32x2::splat(x))
+            })
+            .skip(1000)
+            .collect();
+        let [ll, rr] = out.iter().map(|x| x * x).sum::<f32x2>().to_array();
+        let lr: f32 = out.iter().map(|x| x[0] * x[1]).sum();
+        let mono: f32 = out.iter().map(|x| (x[0] + x[1]).powi(2)).sum();
+        (lr / (ll * rr).sqrt(), (mono / out.len() as f32).sqrt())
+    }
+
+    #[test]
+    fn decorrelation_lowers_correlation() {
+        let amounts = [0., 0.5, 1.];
+        let results = amounts.map(decorrelated);
+        let (correlated, mono) = results[0];
+        assert!((correlated - 1.).abs() < 1e-6, "{correlated}");
+        for pair in results.windows(2) {
+            assert!(pair[1].0 < pair[0].0, "{results:?}");
+        }
+        assert!(results[2].0 < 0.5, "{results:?}");
+        for (_, level) in results {
+            assert!((level - mono).abs() < 1e-3 * mono, "{results:?}");
+        }
+    }
+}
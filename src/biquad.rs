@@ -3,12 +3,17 @@
 // This software is released under the MIT License.
 // https://opensource.org/licenses/MIT
 
-use std::f32::consts::TAU;
-use std::simd::{LaneCount, Simd, SupportedLaneCount};
+use std::f32::consts::{PI, TAU};
 
+use crate::simd::{LaneCount, Simd, SupportedLaneCount};
 use crate::simdmath::*;
 
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "SerializedParams", try_from = "SerializedParams")
+)]
 pub struct BiquadParams<const LANES: usize>
 where
     LaneCount<LANES>: SupportedLaneCount,
@@ -17,6 +22,50 @@ where
     b: [Simd<f32, LANES>; 3],
 }
 
+/// Coefficients of [`BiquadParams`] as they are serialized, with the lanes of each coefficient in
+/// a list. Serde doesn't handle arrays of a generic length.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SerializedParams {
+    a: [Vec<f32>; 2],
+    b: [Vec<f32>; 3],
+}
+
+#[cfg(feature = "serde")]
+impl<const LANES: usize> From<BiquadParams<LANES>> for SerializedParams
+where
+    LaneCount<LANES>: SupportedLaneCount,
+{
+    fn from(params: BiquadParams<LANES>) -> Self {
+        let (a, b) = params.coefficients();
+        Self {
+            a: a.map(Vec::from),
+            b: b.map(Vec::from),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<const LANES: usize> TryFrom<SerializedParams> for BiquadParams<LANES>
+where
+    LaneCount<LANES>: SupportedLaneCount,
+{
+    type Error = String;
+
+    fn try_from(params: SerializedParams) -> Result<Self, Self::Error> {
+        let lanes = |coeffs: &Vec<f32>| {
+            <[f32; LANES]>::try_from(coeffs.as_slice())
+                .map_err(|_| format!("expected {} lanes, got {}", LANES, coeffs.len()))
+        };
+        let [a1, a2] = &params.a;
+        let [b0, b1, b2] = &params.b;
+        Ok(Self::from_coefficients(
+            [lanes(a1)?, lanes(a2)?],
+            [lanes(b0)?, lanes(b1)?, lanes(b2)?],
+        ))
+    }
+}
+
 impl<const LANES: usize> Default for BiquadParams<LANES>
 where
     LaneCount<LANES>: SupportedLaneCount,
@@ -51,6 +100,42 @@ where
         }
     }
 
+    pub fn lowpass(fc: Simd<f32, LANES>, q: Simd<f32, LANES>) -> Self {
+        let w0 = Simd::splat(TAU) * fc;
+        let cw0 = simd_f32cos(w0);
+        let a = simd_f32sin(w0) / (Simd::splat(2.) * q);
+
+        let b1 = Simd::splat(1.) - cw0;
+        let b0 = b1 / Simd::splat(2.);
+        let b2 = b0;
+        let a0 = Simd::splat(1.) + a;
+        let a1 = Simd::splat(-2.) * cw0;
+        let a2 = Simd::splat(1.) - a;
+
+        Self {
+            a: [a1 / a0, a2 / a0],
+            b: [b0 / a0, b1 / a0, b2 / a0],
+        }
+    }
+
+    pub fn highpass(fc: Simd<f32, LANES>, q: Simd<f32, LANES>) -> Self {
+        let w0 = Simd::splat(TAU) * fc;
+        let cw0 = simd_f32cos(w0);
+        let a = simd_f32sin(w0) / (Simd::splat(2.) * q);
+
+        let b1 = -(Simd::splat(1.) + cw0);
+        let b0 = -b1 / Simd::splat(2.);
+        let b2 = b0;
+        let a0 = Simd::splat(1.) + a;
+        let a1 = Simd::splat(-2.) * cw0;
+        let a2 = Simd::splat(1.) - a;
+
+        Self {
+            a: [a1 / a0, a2 / a0],
+            b: [b0 / a0, b1 / a0, b2 / a0],
+        }
+    }
+
     pub fn allpass(fc: Simd<f32, LANES>, q: Simd<f32, LANES>) -> Self {
         let w0 = Simd::splat(TAU) * fc;
         let cw0 = simd_f32cos(w0);
@@ -68,11 +153,82 @@ where
         }
     }
 
-    pub fn lowpass_1p(fc: Simd<f32, LANES>, q: Simd<f32, LANES>) -> Self {
-        let k = simd_f32tan(fc / Simd::splat(2.));
+    /// RBJ peaking EQ, with `gain_db` the per-lane boost (or cut when negative) in decibels.
+    pub fn peaking(fc: Simd<f32, LANES>, q: Simd<f32, LANES>, gain_db: Simd<f32, LANES>) -> Self {
+        let w0 = Simd::splat(TAU) * fc;
+        let cw0 = simd_f32cos(w0);
+        let a = simd_f32sin(w0) / (Simd::splat(2.) * q);
+        let amp = db_to_amplitude_sqrt(gain_db);
+
+        let b0 = Simd::splat(1.) + a * amp;
+        let b1 = Simd::splat(-2.) * cw0;
+        let b2 = Simd::splat(1.) - a * amp;
+        let a0 = Simd::splat(1.) + a / amp;
+        let a1 = Simd::splat(-2.) * cw0;
+        let a2 = Simd::splat(1.) - a / amp;
+
+        Self {
+            a: [a1 / a0, a2 / a0],
+            b: [b0 / a0, b1 / a0, b2 / a0],
+        }
+    }
+
+    /// RBJ low shelf, boosting (or cutting when negative) frequencies below `fc` by `gain_db`.
+    pub fn low_shelf(fc: Simd<f32, LANES>, q: Simd<f32, LANES>, gain_db: Simd<f32, LANES>) -> Self {
+        let w0 = Simd::splat(TAU) * fc;
+        let cw0 = simd_f32cos(w0);
+        let a = simd_f32sin(w0) / (Simd::splat(2.) * q);
+        let amp = db_to_amplitude_sqrt(gain_db);
+        let one = Simd::splat(1.);
+        let two = Simd::splat(2.);
+        let sa = two * simd_f32func(f32::sqrt, amp) * a;
+
+        let b0 = amp * ((amp + one) - (amp - one) * cw0 + sa);
+        let b1 = two * amp * ((amp - one) - (amp + one) * cw0);
+        let b2 = amp * ((amp + one) - (amp - one) * cw0 - sa);
+        let a0 = (amp + one) + (amp - one) * cw0 + sa;
+        let a1 = -two * ((amp - one) + (amp + one) * cw0);
+        let a2 = (amp + one) + (amp - one) * cw0 - sa;
+
+        Self {
+            a: [a1 / a0, a2 / a0],
+            b: [b0 / a0, b1 / a0, b2 / a0],
+        }
+    }
+
+    /// RBJ high shelf, boosting (or cutting when negative) frequencies above `fc` by `gain_db`.
+    pub fn high_shelf(
+        fc: Simd<f32, LANES>,
+        q: Simd<f32, LANES>,
+        gain_db: Simd<f32, LANES>,
+    ) -> Self {
+        let w0 = Simd::splat(TAU) * fc;
+        let cw0 = simd_f32cos(w0);
+        let a = simd_f32sin(w0) / (Simd::splat(2.) * q);
+        let amp = db_to_amplitude_sqrt(gain_db);
+        let one = Simd::splat(1.);
+        let two = Simd::splat(2.);
+        let sa = two * simd_f32func(f32::sqrt, amp) * a;
+
+        let b0 = amp * ((amp + one) + (amp - one) * cw0 + sa);
+        let b1 = -two * amp * ((amp - one) + (amp + one) * cw0);
+        let b2 = amp * ((amp + one) + (amp - one) * cw0 - sa);
+        let a0 = (amp + one) - (amp - one) * cw0 + sa;
+        let a1 = two * ((amp - one) - (amp + one) * cw0);
+        let a2 = (amp + one) - (amp - one) * cw0 - sa;
+
+        Self {
+            a: [a1 / a0, a2 / a0],
+            b: [b0 / a0, b1 / a0, b2 / a0],
+        }
+    }
+
+    /// First-order lowpass, bilinear-transformed so that the -3 dB point lands on `fc`.
+    pub fn lowpass_1p(fc: Simd<f32, LANES>) -> Self {
+        let k = simd_f32tan(Simd::splat(PI) * fc);
         let a = Simd::splat(1.) + k;
 
-        let a1 = -(Simd::splat(1.) - fc) / a;
+        let a1 = (k - Simd::splat(1.)) / a;
         let b0 = k / a;
         let b1 = k / a;
 
@@ -82,11 +238,12 @@ where
         }
     }
 
-    pub fn highpass_1p(fc: Simd<f32, LANES>, q: Simd<f32, LANES>) -> Self {
-        let k = simd_f32tan(fc / Simd::splat(2.));
+    /// First-order highpass, bilinear-transformed so that the -3 dB point lands on `fc`.
+    pub fn highpass_1p(fc: Simd<f32, LANES>) -> Self {
+        let k = simd_f32tan(Simd::splat(PI) * fc);
         let a = Simd::splat(1.) + k;
 
-        let a1 = -(Simd::splat(1.) - fc) / a;
+        let a1 = (k - Simd::splat(1.)) / a;
         let b0 = Simd::splat(1.) / a;
         let b1 = Simd::splat(-1.) / a;
 
@@ -97,6 +254,97 @@ where
     }
 }
 
+impl<const LANES: usize> BiquadParams<LANES>
+where
+    LaneCount<LANES>: SupportedLaneCount,
+{
+    /// Creates the filter from raw coefficients, laid out like [`Self::coefficients`].
+    pub fn from_coefficients(a: [[f32; LANES]; 2], b: [[f32; LANES]; 3]) -> Self {
+        Self {
+            a: a.map(Simd::from_array),
+            b: b.map(Simd::from_array),
+        }
+    }
+
+    /// Raw coefficients of each lane, as `([a1, a2], [b0, b1, b2])`. The coefficients are
+    /// normalized so that `a0` is 1, and the feedback ones are subtracted:
+    /// `y[n] = b0 x[n] + b1 x[n-1] + b2 x[n-2] - a1 y[n-1] - a2 y[n-2]`.
+    pub fn coefficients(&self) -> ([[f32; LANES]; 2], [[f32; LANES]; 3]) {
+        (self.a.map(Simd::to_array), self.b.map(Simd::to_array))
+    }
+
+    /// Linearly interpolates each coefficient towards `other` by the amount `t`.
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        let t = Simd::splat(t);
+        Self {
+            a: std::array::from_fn(|i| self.a[i] + (other.a[i] - self.a[i]) * t),
+            b: std::array::from_fn(|i| self.b[i] + (other.b[i] - self.b[i]) * t),
+        }
+    }
+
+    /// Group delay of each lane at DC, in samples. This is the delay a slowly varying signal goes
+    /// through, and is not finite for filters that block DC, such as highpasses.
+    pub fn group_delay_dc(&self) -> Simd<f32, LANES> {
+        // At DC, the group delay of `B(z)/A(z)` is the centroid of the taps of `B` minus that of
+        // the taps of `A`, with `a0 = 1`
+        let [b0, b1, b2] = self.b;
+        let [a1, a2] = self.a;
+        let two = Simd::splat(2.);
+        let num = (b1 + two * b2) / (b0 + b1 + b2);
+        let den = (a1 + two * a2) / (Simd::splat(1.) + a1 + a2);
+        num - den
+    }
+
+    /// Evaluates the transfer function of the first lane at the given normalized frequency,
+    /// returning its linear magnitude and phase in radians.
+    pub fn response(&self, normalized_freq: f32) -> (f32, f32) {
+        self.response_lane(normalized_freq, 0)
+    }
+
+    /// Evaluates the transfer function of the given lane at the given normalized frequency,
+    /// returning its linear magnitude and phase in radians.
+    pub fn response_lane(&self, normalized_freq: f32, lane: usize) -> (f32, f32) {
+        let w = TAU * normalized_freq;
+        let (s1, c1) = w.sin_cos();
+        let (s2, c2) = (2. * w).sin_cos();
+        let [b0, b1, b2] = self.b.map(|b| b[lane]);
+        let [a1, a2] = self.a.map(|a| a[lane]);
+
+        let num_re = b0 + b1 * c1 + b2 * c2;
+        let num_im = -(b1 * s1 + b2 * s2);
+        let den_re = 1. + a1 * c1 + a2 * c2;
+        let den_im = -(a1 * s1 + a2 * s2);
+
+        let magnitude = f32::hypot(num_re, num_im) / f32::hypot(den_re, den_im);
+        let phase = f32::atan2(num_im, num_re) - f32::atan2(den_im, den_re);
+        (magnitude, phase)
+    }
+}
+
+/// Converts decibels into the square root of the linear amplitude, as used by the RBJ cookbook
+/// for peaking and shelving filters (`A = 10^(dB/40)`).
+#[inline(always)]
+fn db_to_amplitude_sqrt<const LANES: usize>(gain_db: Simd<f32, LANES>) -> Simd<f32, LANES>
+where
+    LaneCount<LANES>: SupportedLaneCount,
+{
+    simd_f32func(|g| f32::powf(10., g / 40.), gain_db)
+}
+
+/// Whether to flush near-denormal filter state to zero. Only x86 pays a significant performance
+/// penalty for denormal arithmetic, so other platforms skip the extra work.
+const FLUSH_DENORMALS: bool = cfg!(any(target_arch = "x86", target_arch = "x86_64"));
+
+#[inline(always)]
+fn flush_denormals<const LANES: usize>(x: Simd<f32, LANES>) -> Simd<f32, LANES>
+where
+    LaneCount<LANES>: SupportedLaneCount,
+{
+    x.abs()
+        .lanes_lt(Simd::splat(1e-20))
+        .select(Simd::splat(0.), x)
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct Biquad<const LANES: usize>
 where
@@ -133,47 +381,322 @@ where
         let out = self.state[0] + self.params.b[0] * input;
         self.state[0] = self.state[1] + self.params.b[1] * input - self.params.a[0] * out;
         self.state[1] = self.params.b[2] * input - self.params.a[1] * out;
+        if FLUSH_DENORMALS {
+            self.state = self.state.map(flush_denormals);
+        }
         out
     }
 
+    /// Moves the current coefficients towards `target` by the fraction `t`. Calling this once
+    /// per sample with a `t` around 0.001–0.01 gives click-free sweeps, at the cost of the
+    /// filter lagging slightly behind the target.
+    pub fn set_params_smoothed(&mut self, target: BiquadParams<LANES>, t: f32) {
+        self.params = self.params.lerp(&target, t);
+    }
+
+    pub fn process_block(&mut self, buffer: &mut [Simd<f32, LANES>]) {
+        for sample in buffer {
+            *sample = self.next_sample(*sample);
+        }
+    }
+
     pub fn reset(&mut self) {
         self.state = [Simd::splat(0.); 2];
     }
 }
 
+/// Relative change of the cutoff below which [`CoefficientCache`] keeps its coefficients, about
+/// 1.7 cents.
+const CUTOFF_TOLERANCE: f32 = 1e-3;
+
+/// Coefficients of a filter designed from a single cutoff, recomputed only when the cutoff moves
+/// by more than [`CUTOFF_TOLERANCE`]. Meant to be paired with [`Biquad::set_params_smoothed`],
+/// which glides over the small steps this leaves.
+#[derive(Debug, Copy, Clone)]
+pub struct CoefficientCache<const LANES: usize>
+where
+    LaneCount<LANES>: SupportedLaneCount,
+{
+    cutoff: Option<f32>,
+    params: BiquadParams<LANES>,
+}
+
+impl<const LANES: usize> Default for CoefficientCache<LANES>
+where
+    LaneCount<LANES>: SupportedLaneCount,
+{
+    fn default() -> Self {
+        Self {
+            cutoff: None,
+            params: BiquadParams::default(),
+        }
+    }
+}
+
+impl<const LANES: usize> CoefficientCache<LANES>
+where
+    LaneCount<LANES>: SupportedLaneCount,
+{
+    /// Returns the coefficients for `cutoff`, calling `design` only if the cutoff moved past the
+    /// tolerance since the last time they were computed.
+    pub fn get(
+        &mut self,
+        cutoff: f32,
+        design: impl FnOnce(f32) -> BiquadParams<LANES>,
+    ) -> BiquadParams<LANES> {
+        let stale = self.cutoff.map_or(true, |cached| {
+            (cutoff - cached).abs() > CUTOFF_TOLERANCE * cached
+        });
+        if stale {
+            self.cutoff = Some(cutoff);
+            self.params = design(cutoff);
+        }
+        self.params
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use std::{iter::repeat, simd::Simd};
+    use std::{
+        cell::Cell,
+        f32::consts::{FRAC_1_SQRT_2, FRAC_PI_4, PI, TAU},
+        iter::repeat,
+    };
 
     use approx::assert_abs_diff_eq;
+    use rand::prelude::*;
 
-    use super::{Biquad, BiquadParams};
+    use super::{Biquad, BiquadParams, CoefficientCache};
+    use crate::simd::Simd;
 
-    fn test_unit(params: BiquadParams<1>, steady: f32) {
+    fn steady_state(params: BiquadParams<1>) -> f32 {
         let mut biquad = Biquad::new(params);
-        let steady_actual = repeat(0.)
+        repeat(0.)
             .take(10)
             .chain(repeat(1.))
             .map(|v| biquad.next_sample(Simd::from_array([v]))[0])
             .take(5000)
             .last()
-            .unwrap();
-        assert_abs_diff_eq!(steady, steady_actual);
+            .unwrap()
+    }
+
+    fn sine_gain(params: BiquadParams<1>, freq: f32) -> f32 {
+        let mut biquad = Biquad::new(params);
+        let sum_sq = (0..10000)
+            .map(|i| f32::sin(TAU * freq * i as f32))
+            .map(|v| biquad.next_sample(Simd::from_array([v]))[0].powi(2))
+            .skip(8000)
+            .sum::<f32>();
+        f32::sqrt(2. * sum_sq / 2000.)
+    }
+
+    fn test_unit(params: BiquadParams<1>, steady: f32) {
+        assert_abs_diff_eq!(steady, steady_state(params));
     }
 
     #[test]
     fn step_lowpass_1p() {
-        test_unit(
-            BiquadParams::lowpass_1p(Simd::splat(0.3), Simd::splat(1.)),
-            1.,
-        );
+        test_unit(BiquadParams::lowpass_1p(Simd::splat(0.3)), 1.);
     }
 
     #[test]
     fn step_highpass_1p() {
+        test_unit(BiquadParams::highpass_1p(Simd::splat(0.3)), 0.);
+    }
+
+    #[test]
+    fn cutoff_1p() {
+        for fc in [0.01, 0.05, 0.1, 0.2, 0.3] {
+            let lp = sine_gain(BiquadParams::lowpass_1p(Simd::splat(fc)), fc);
+            let hp = sine_gain(BiquadParams::highpass_1p(Simd::splat(fc)), fc);
+            assert_abs_diff_eq!(FRAC_1_SQRT_2, lp, epsilon = 1e-2);
+            assert_abs_diff_eq!(FRAC_1_SQRT_2, hp, epsilon = 1e-2);
+        }
+    }
+
+    #[test]
+    fn response_1p() {
+        let (mag, phase) = BiquadParams::<1>::lowpass_1p(Simd::splat(0.1)).response(0.1);
+        assert_abs_diff_eq!(FRAC_1_SQRT_2, mag, epsilon = 1e-5);
+        assert_abs_diff_eq!(-FRAC_PI_4, phase, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn group_delay_dc() {
+        // The bilinear one-pole lowpass delays DC by `1 / (2 tan(pi fc))`
+        let fc = 0.05;
+        let params = BiquadParams::<1>::lowpass_1p(Simd::splat(fc));
+        let expected = 0.5 / f32::tan(PI * fc);
+        assert_abs_diff_eq!(expected, params.group_delay_dc()[0], epsilon = 1e-3);
+
+        // Matches the slope of the phase response near DC
+        let df = 1e-4;
+        let (_, phase) = params.response(df);
+        assert_abs_diff_eq!(
+            -phase / (TAU * df),
+            params.group_delay_dc()[0],
+            epsilon = 1e-2
+        );
+
+        assert_eq!(
+            Simd::splat(0.),
+            BiquadParams::<2>::default().group_delay_dc()
+        );
+    }
+
+    #[test]
+    fn coefficients_round_trip() {
+        let params = BiquadParams::lowpass(Simd::from_array([0.1, 0.2]), Simd::splat(0.7));
+        let (a, b) = params.coefficients();
+        assert_eq!((a, b), BiquadParams::from_coefficients(a, b).coefficients());
+
+        // Running the difference equation on the raw coefficients matches the filter in each lane
+        let mut biquad = Biquad::new(params);
+        let (mut x1, mut x2, mut y1, mut y2) = ([0f32; 2], [0f32; 2], [0f32; 2], [0f32; 2]);
+        for n in 0..32 {
+            let x = if n == 0 { 1. } else { 0. };
+            let out = biquad.next_sample(Simd::splat(x));
+            for lane in 0..2 {
+                let y = b[0][lane] * x + b[1][lane] * x1[lane] + b[2][lane] * x2[lane]
+                    - a[0][lane] * y1[lane]
+                    - a[1][lane] * y2[lane];
+                assert_abs_diff_eq!(y, out[lane], epsilon = 1e-6);
+                x2[lane] = x1[lane];
+                x1[lane] = x;
+                y2[lane] = y1[lane];
+                y1[lane] = y;
+            }
+        }
+
+        let (a, b) = BiquadParams::<2>::default().coefficients();
+        assert_eq!([[0.; 2]; 2], a);
+        assert_eq!([[1.; 2], [0.; 2], [0.; 2]], b);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let params = BiquadParams::peaking(
+            Simd::from_array([0.05, 0.1]),
+            Simd::splat(1.),
+            Simd::splat(6.),
+        );
+        let json = serde_json::to_string(&params).unwrap();
+        let decoded: BiquadParams<2> = serde_json::from_str(&json).unwrap();
+        assert_eq!(params.coefficients(), decoded.coefficients());
+        // The lane count has to match
+        assert!(serde_json::from_str::<BiquadParams<4>>(&json).is_err());
+    }
+
+    #[test]
+    fn block_matches_per_sample() {
+        let params = BiquadParams::lowpass(Simd::splat(0.1), Simd::splat(2.));
+        let mut rng = StdRng::seed_from_u64(0);
+        let input: Vec<Simd<f32, 2>> = (0..512)
+            .map(|_| Simd::from_array([rng.gen(), rng.gen()]))
+            .collect();
+
+        let mut biquad = Biquad::new(params);
+        let expected: Vec<_> = input.iter().map(|&v| biquad.next_sample(v)).collect();
+
+        let mut biquad = Biquad::new(params);
+        let mut actual = input;
+        for chunk in actual.chunks_mut(100) {
+            biquad.process_block(chunk);
+        }
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn smoothed_params_no_discontinuity() {
+        fn max_jump(mut update: impl FnMut(&mut Biquad<1>, BiquadParams<1>)) -> f32 {
+            let start = BiquadParams::lowpass(Simd::splat(0.02), Simd::splat(FRAC_1_SQRT_2));
+            let target = BiquadParams::lowpass(Simd::splat(0.3), Simd::splat(FRAC_1_SQRT_2));
+            let mut biquad = Biquad::new(start);
+            let mut last = 0.;
+            let mut jump = 0.;
+            for i in 0..4000 {
+                if i >= 2000 {
+                    update(&mut biquad, target);
+                }
+                let out = biquad.next_sample(Simd::splat(f32::sin(TAU * 0.01 * i as f32)))[0];
+                if i >= 1000 {
+                    jump = f32::max(jump, (out - last).abs());
+                }
+                last = out;
+            }
+            jump
+        }
+
+        assert!(max_jump(|bq, target| bq.params = target) > 1.);
+        assert!(max_jump(|bq, target| bq.set_params_smoothed(target, 5e-3)) < 0.2);
+    }
+
+    #[test]
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    fn state_flushes_to_zero() {
+        let mut biquad = Biquad::<1>::new(BiquadParams::lowpass_1p(Simd::splat(1e-3)));
+        biquad.next_sample(Simd::splat(1.));
+        for _ in 0..10000 {
+            biquad.next_sample(Simd::splat(0.));
+        }
+        assert_eq!([Simd::splat(0.); 2], biquad.state);
+    }
+
+    #[test]
+    fn step_peaking_flat() {
         test_unit(
-            BiquadParams::highpass_1p(Simd::splat(0.3), Simd::splat(1.)),
-            0.,
+            BiquadParams::peaking(Simd::splat(0.1), Simd::splat(1.), Simd::splat(0.)),
+            1.,
         );
     }
+
+    #[test]
+    fn step_low_shelf() {
+        for gain_db in [-12., -3., 6.] {
+            let params =
+                BiquadParams::low_shelf(Simd::splat(0.05), Simd::splat(1.), Simd::splat(gain_db));
+            assert_abs_diff_eq!(
+                f32::powf(10., gain_db / 20.),
+                steady_state(params),
+                epsilon = 1e-4
+            );
+        }
+    }
+
+    #[test]
+    fn resonant_lowpass() {
+        let params = BiquadParams::lowpass(Simd::splat(0.1), Simd::splat(8.));
+        assert_abs_diff_eq!(1., steady_state(params), epsilon = 1e-5);
+        assert!(sine_gain(params, 0.1) > 1.);
+        assert!(sine_gain(params, 0.5 - 1e-3) < 1e-2);
+    }
+
+    #[test]
+    fn resonant_highpass() {
+        let params = BiquadParams::highpass(Simd::splat(0.1), Simd::splat(8.));
+        assert_abs_diff_eq!(0., steady_state(params), epsilon = 1e-5);
+        assert!(sine_gain(params, 0.1) > 1.);
+        assert!(sine_gain(params, 0.5 - 1e-3) > 0.99);
+    }
+
+    #[test]
+    fn cache_skips_small_changes() {
+        let mut cache = CoefficientCache::<1>::default();
+        let designs = Cell::new(0);
+        let design = |fc| {
+            designs.set(designs.get() + 1);
+            BiquadParams::lowpass_1p(Simd::splat(fc))
+        };
+        for _ in 0..100 {
+            cache.get(0.1, design);
+        }
+        cache.get(0.10001, design);
+        assert_eq!(1, designs.get());
+
+        let params = cache.get(0.2, design);
+        assert_eq!(2, designs.get());
+        let (mag, _) = params.response(0.2);
+        assert_abs_diff_eq!(FRAC_1_SQRT_2, mag, epsilon = 1e-5);
+    }
 }
@@ -0,0 +1,83 @@
+// Copyright (c) 2022 solarliner
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use crate::biquad::{Biquad, BiquadParams};
+use crate::simd::{f32x2, Simd};
+
+/// Splits a stereo signal into low, mid and high bands. Each band is taken out of what is left
+/// by the one below it, so the bands always add back up to the input exactly.
+#[derive(Debug, Clone)]
+pub struct Crossover {
+    low: Biquad<2>,
+    mid: Biquad<2>,
+}
+
+impl Crossover {
+    /// Creates the crossover with its two split frequencies, normalized to the sample rate.
+    pub fn new(low_fc: f32, high_fc: f32) -> Self {
+        Self {
+            low: Biquad::new(BiquadParams::lowpass_1p(Simd::splat(low_fc))),
+            mid: Biquad::new(BiquadParams::lowpass_1p(Simd::splat(high_fc))),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.low.reset();
+        self.mid.reset();
+    }
+
+    /// Splits the next sample into its low, mid and high bands.
+    pub fn split(&mut self, x: f32x2) -> [f32x2; 3] {
+        let low = self.low.next_sample(x);
+        let rest = x - low;
+        let mid = self.mid.next_sample(rest);
+        [low, mid, rest - mid]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::TAU;
+
+    use crate::simd::f32x2;
+
+    use super::Crossover;
+
+    #[test]
+    fn bands_sum_to_input() {
+        let mut crossover = Crossover::new(0.01, 0.1);
+        for i in 0..1000 {
+            let x = f32x2::from_array([f32::sin(TAU * 0.003 * i as f32), (i % 7) as f32 - 3.]);
+            let [low, mid, high] = crossover.split(x);
+            let sum = low + mid + high;
+            for (a, b) in sum.to_array().into_iter().zip(x.to_array()) {
+                assert!((a - b).abs() < 1e-5, "{a} != {b}");
+            }
+        }
+    }
+
+    #[test]
+    fn bands_follow_frequency() {
+        let energy = |freq: f32| {
+            let mut crossover = Crossover::new(0.01, 0.1);
+            let mut energy = [0.; 3];
+            for i in 0..4000 {
+                let x = f32x2::splat(f32::sin(TAU * freq * i as f32));
+                for (e, band) in energy.iter_mut().zip(crossover.split(x)) {
+                    *e += band[0] * band[0];
+                }
+            }
+            energy
+        };
+        let argmax = |e: [f32; 3]| {
+            (0..3)
+                .max_by(|&a, &b| e[a].partial_cmp(&e[b]).unwrap())
+                .unwrap()
+        };
+        assert_eq!(0, argmax(energy(0.001)));
+        assert_eq!(1, argmax(energy(0.03)));
+        assert_eq!(2, argmax(energy(0.3)));
+    }
+}
@@ -3,32 +3,70 @@
 // This software is released under the MIT License.
 // https://opensource.org/licenses/MIT
 
-use std::{
-    collections::VecDeque,
-    simd::{LaneCount, Simd, SupportedLaneCount},
-};
+use std::collections::VecDeque;
+
+use crate::simd::{LaneCount, Simd, SupportedLaneCount};
+
+/// Interpolation used when reading a delay line at a fractional position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    Nearest,
+    Linear,
+    Cubic,
+    /// First-order Thiran allpass interpolation. This keeps state between reads, so it should be
+    /// used with a single read per sample on a given delay line.
+    Allpass,
+}
 
 #[derive(Debug, Clone)]
 pub struct Delay<T> {
     buffer: VecDeque<T>,
+    allpass_state: T,
 }
 
 impl<T> Delay<T> {
+    /// Pushes the next sample, dropping the oldest one. Does nothing on an empty delay line.
     pub fn push_next(&mut self, next: T) {
-        self.buffer.pop_back();
-        self.buffer.push_front(next);
+        if self.buffer.pop_back().is_some() {
+            self.buffer.push_front(next);
+        }
     }
 
     pub fn len(&self) -> usize {
         self.buffer.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// Capacity of the delay line, in samples. The buffer is always full, so this is also the
+    /// position past which reads wrap around to the newest samples; the longest delay that can be
+    /// read back is one sample less.
+    pub fn max_delay(&self) -> usize {
+        self.buffer.len()
+    }
 }
 
 impl<T: Default> Delay<T> {
     pub fn new(max_delay: usize) -> Self {
         Self {
             buffer: VecDeque::from_iter(std::iter::repeat_with(T::default).take(max_delay)),
+            allpass_state: T::default(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for sample in &mut self.buffer {
+            *sample = T::default();
         }
+        self.allpass_state = T::default();
+    }
+
+    /// Changes the length of the delay line, keeping the most recent samples. Growing it pads the
+    /// end of the history with silence.
+    pub fn resize(&mut self, new_len: usize) {
+        self.buffer.resize_with(new_len, T::default);
     }
 }
 
@@ -36,7 +74,7 @@ impl<const L: usize> Delay<Simd<f32, L>>
 where
     LaneCount<L>: SupportedLaneCount,
 {
-    pub fn get(&mut self, pos: Simd<f32, L>) -> Simd<f32, L> {
+    pub fn get(&self, pos: Simd<f32, L>) -> Simd<f32, L> {
         let mut res = Simd::splat(0.);
         for i in 0..L {
             res[i] = self.tap(pos[i])[i];
@@ -45,25 +83,109 @@ where
     }
 
     // Cubic interpolation
-    pub fn tap(&mut self, pos: f32) -> Simd<f32, L> {
-        let pos = (pos + self.buffer.len() as f32) % self.buffer.len() as f32;
-        let ix = pos.floor() as usize;
-        let f = pos.fract();
+    pub fn tap(&self, pos: f32) -> Simd<f32, L> {
+        let [out] = self.tap_many([pos]);
+        out
+    }
 
-        let a0 = self.sample(ix.saturating_sub(2));
-        let a1 = self.sample(ix.saturating_sub(1));
-        let b0 = self.sample(ix);
-        let b1 = self.sample(ix.saturating_add(1));
+    /// Reads each lane `seconds` back at the given sample rate, with the cubic interpolation of
+    /// [`Self::tap`]. The positions are clamped to the readable range, see
+    /// [`Self::clamp_position`].
+    pub fn get_seconds(&self, seconds: Simd<f32, L>, samplerate: f32) -> Simd<f32, L> {
+        let max = self.max_position();
+        self.get((seconds * Simd::splat(samplerate)).clamp(Simd::splat(1.), Simd::splat(max)))
+    }
 
-        cubic(f, [a0, a1, b0, b1])
+    /// Reads the delay line `seconds` back at the given sample rate, with the position clamped
+    /// like [`Self::get_seconds`].
+    pub fn tap_seconds(&self, seconds: f32, samplerate: f32) -> Simd<f32, L> {
+        self.tap(self.clamp_position(seconds * samplerate))
+    }
+
+    /// Clamps a position in samples to the range that can be read back without wrapping around:
+    /// at least one sample back, and far enough from the end of the line to keep the samples
+    /// after the read position used by the cubic interpolation.
+    pub fn clamp_position(&self, pos: f32) -> f32 {
+        pos.clamp(1., self.max_position())
+    }
+
+    /// Position `seconds` back at the given sample rate, in samples, wrapped into the delay line
+    /// instead of clamped. Reads wrap positions past either end of the line around to the other,
+    /// so this is where a tap with a negative delay actually reads from.
+    pub fn wrap_seconds(&self, seconds: f32, samplerate: f32) -> f32 {
+        if self.buffer.is_empty() {
+            return 0.;
+        }
+        (seconds * samplerate).rem_euclid(self.buffer.len() as f32)
+    }
+
+    fn max_position(&self) -> f32 {
+        (self.buffer.len() as f32 - 2.).max(1.)
     }
 
-    // Nearest-neighbor interpolation
-    #[cfg(never)]
-    pub fn tap(&mut self, pos: f32) -> Simd<f32, L> {
-        let ix = pos.round() as _;
-        let s = self.sample(ix);
-        return s;
+    /// Reads the delay line at several positions at once, with the same cubic interpolation as
+    /// [`Self::tap`].
+    pub fn tap_many<const M: usize>(&self, positions: [f32; M]) -> [Simd<f32, L>; M] {
+        if self.buffer.is_empty() {
+            return [Simd::splat(0.); M];
+        }
+        positions.map(|pos| {
+            let (ix, f) = self.split_pos(pos);
+            cubic(
+                f,
+                [
+                    self.sample(ix.saturating_sub(2)),
+                    self.sample(ix.saturating_sub(1)),
+                    self.sample(ix),
+                    self.sample(ix.saturating_add(1)),
+                ],
+            )
+        })
+    }
+
+    pub fn tap_with(&mut self, pos: f32, mode: Interpolation) -> Simd<f32, L> {
+        if self.buffer.is_empty() {
+            return Simd::splat(0.);
+        }
+        let (ix, f) = self.split_pos(pos);
+        let a = self.sample(ix.saturating_sub(1));
+        let b = self.sample(ix);
+
+        match mode {
+            Interpolation::Nearest if f < 0.5 => a,
+            Interpolation::Nearest => b,
+            Interpolation::Linear => a + (b - a) * Simd::splat(f),
+            Interpolation::Cubic => self.tap(pos),
+            Interpolation::Allpass => {
+                let eta = Simd::splat((1. - f) / (1. + f));
+                let out = b + eta * (a - self.allpass_state);
+                self.allpass_state = out;
+                out
+            }
+        }
+    }
+
+    /// Reads each lane at its own position with first-order Thiran allpass interpolation, whose
+    /// magnitude response is flat. `state` holds the previous output of the interpolator, and
+    /// must be kept between calls with a single read per sample.
+    pub fn get_thiran(&self, pos: Simd<f32, L>, state: &mut Simd<f32, L>) -> Simd<f32, L> {
+        let mut res = Simd::splat(0.);
+        for i in 0..L {
+            // Keeping the fractional delay within [0.5, 1.5) keeps the pole away from Nyquist
+            let base = (pos[i] - 0.5).floor().max(1.);
+            let frac = pos[i] - base;
+            let eta = (1. - frac) / (1. + frac);
+            let a = self.sample(base as usize - 1)[i];
+            let b = self.sample(base as usize)[i];
+            res[i] = b + eta * (a - state[i]);
+        }
+        *state = res;
+        res
+    }
+
+    fn split_pos(&self, pos: f32) -> (usize, f32) {
+        let pos = (pos + self.buffer.len() as f32) % self.buffer.len() as f32;
+        (pos.floor() as usize, pos.fract())
     }
 
     fn sample(&self, i: usize) -> Simd<f32, L> {
@@ -75,6 +197,81 @@ where
     }
 }
 
+/// Delay line backed by a power-of-two ring buffer, trading the rounded-up size for cheaper
+/// indexing than [`Delay`]. Reads and writes behave exactly like a [`Delay`] of the same length.
+#[derive(Debug, Clone)]
+pub struct RingDelay<T> {
+    buffer: Vec<T>,
+    mask: usize,
+    write: usize,
+}
+
+impl<T> RingDelay<T> {
+    pub fn push_next(&mut self, next: T) {
+        self.write = self.write.wrapping_sub(1) & self.mask;
+        self.buffer[self.write] = next;
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Always false, as the buffer holds at least one sample.
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+}
+
+impl<T: Default> RingDelay<T> {
+    /// Creates a ring buffer holding at least `max_delay` samples, rounded up to the next power
+    /// of two.
+    pub fn new(max_delay: usize) -> Self {
+        let len = max_delay.max(1).next_power_of_two();
+        Self {
+            buffer: std::iter::repeat_with(T::default).take(len).collect(),
+            mask: len - 1,
+            write: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for sample in &mut self.buffer {
+            *sample = T::default();
+        }
+    }
+}
+
+impl<const L: usize> RingDelay<Simd<f32, L>>
+where
+    LaneCount<L>: SupportedLaneCount,
+{
+    pub fn get(&self, pos: Simd<f32, L>) -> Simd<f32, L> {
+        let mut res = Simd::splat(0.);
+        for i in 0..L {
+            res[i] = self.tap(pos[i])[i];
+        }
+        res
+    }
+
+    // Cubic interpolation
+    pub fn tap(&self, pos: f32) -> Simd<f32, L> {
+        let pos = pos + self.buffer.len() as f32;
+        let ix = pos.floor() as isize as usize & self.mask;
+        let f = pos - pos.floor();
+
+        let a0 = self.sample(ix.saturating_sub(2));
+        let a1 = self.sample(ix.saturating_sub(1));
+        let b0 = self.sample(ix);
+        let b1 = self.sample(ix.saturating_add(1));
+
+        cubic(f, [a0, a1, b0, b1])
+    }
+
+    fn sample(&self, i: usize) -> Simd<f32, L> {
+        self.buffer[(self.write + i.min(self.mask)) & self.mask]
+    }
+}
+
 #[inline(always)]
 fn cubic<const L: usize>(t: f32, p: [Simd<f32, L>; 4]) -> Simd<f32, L>
 where
@@ -93,3 +290,256 @@ where
             + t * (two * p[0] - five * p[1] + four * p[2] - p[3]
                 + t * (three * (p[1] - p[2]) + p[3] - p[0])))
 }
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use crate::simd::Simd;
+
+    use super::{Delay, Interpolation, RingDelay};
+
+    #[test]
+    fn reset_clears_buffer() {
+        let mut delay = Delay::<Simd<f32, 2>>::new(16);
+        for i in 0..10 {
+            delay.push_next(Simd::splat(i as f32 + 1.));
+        }
+        delay.reset();
+        assert_eq!(16, delay.len());
+        for i in 0..16 {
+            assert_eq!(Simd::splat(0.), delay.tap(i as f32));
+        }
+    }
+
+    #[test]
+    fn max_delay_is_capacity() {
+        let mut delay = Delay::<Simd<f32, 1>>::new(16);
+        assert_eq!(16, delay.max_delay());
+        delay.push_next(Simd::splat(1.));
+        assert_eq!(16, delay.max_delay());
+        delay.resize(4);
+        assert_eq!(4, delay.max_delay());
+        assert_eq!(0, Delay::<Simd<f32, 1>>::new(0).max_delay());
+    }
+
+    #[test]
+    fn tap_near_end() {
+        let mut delay = Delay::<Simd<f32, 1>>::new(8);
+        for i in 0..8 {
+            delay.push_next(Simd::splat(i as f32));
+        }
+        // Buffer now reads [7, 6, ..., 0] from newest to oldest
+        let out = delay.tap(delay.len() as f32 - 0.1)[0];
+        assert!(
+            (0. ..=1.).contains(&out),
+            "{out} not within the last two samples"
+        );
+    }
+
+    #[test]
+    fn tap_seconds_converts_and_clamps() {
+        let delay = ramp(16);
+        let samplerate = 1000.;
+        // Reading at `n` samples returns the sample pushed `n` samples ago
+        assert_eq!(12., delay.tap_seconds(4e-3, samplerate)[0]);
+        assert_abs_diff_eq!(
+            delay.tap(4.3)[0],
+            delay.tap_seconds(4.3e-3, samplerate)[0],
+            epsilon = 1e-5
+        );
+        // Positions before the newest sample and past the end of the line are held at the edges
+        assert_eq!(delay.tap(1.), delay.tap_seconds(0., samplerate));
+        assert_eq!(delay.tap(1.), delay.tap_seconds(-1., samplerate));
+        assert_eq!(delay.tap(14.), delay.tap_seconds(1., samplerate));
+        assert_eq!(1., delay.clamp_position(0.5));
+        assert_eq!(14., delay.clamp_position(15.5));
+        assert_eq!(7.5, delay.clamp_position(7.5));
+    }
+
+    #[test]
+    fn wrap_seconds_matches_wrapped_reads() {
+        let delay = ramp(16);
+        let samplerate = 1000.;
+        assert_eq!(4., delay.wrap_seconds(4e-3, samplerate));
+        assert_eq!(12., delay.wrap_seconds(-4e-3, samplerate));
+        assert_eq!(3., delay.wrap_seconds(19e-3, samplerate));
+        // Reads at the raw position land where the wrapped one points
+        for seconds in [-4.5e-3, -1e-3, 2.25e-3, 17.5e-3] {
+            assert_eq!(
+                delay.tap(seconds * samplerate),
+                delay.tap(delay.wrap_seconds(seconds, samplerate)),
+                "{seconds}"
+            );
+        }
+        assert_eq!(
+            0.,
+            Delay::<Simd<f32, 1>>::new(0).wrap_seconds(1., samplerate)
+        );
+    }
+
+    #[test]
+    fn get_seconds_reads_each_lane() {
+        let mut delay = Delay::<Simd<f32, 2>>::new(16);
+        for i in 0..16 {
+            delay.push_next(Simd::from_array([i as f32, -(i as f32)]));
+        }
+        let out = delay.get_seconds(Simd::from_array([2e-3, 5e-3]), 1000.);
+        assert_eq!(Simd::from_array([14., -11.]), out);
+        let clamped = delay.get_seconds(Simd::from_array([-1., 1.]), 1000.);
+        assert_eq!(Simd::from_array([15., -2.]), clamped);
+
+        // Lines too short to interpolate still read the newest sample
+        let short = Delay::<Simd<f32, 2>>::new(2);
+        assert_eq!(1., short.clamp_position(10.));
+        let empty = Delay::<Simd<f32, 2>>::new(0);
+        assert_eq!(Simd::splat(0.), empty.get_seconds(Simd::splat(1.), 1000.));
+    }
+
+    #[test]
+    fn ring_matches_delay() {
+        let mut delay = Delay::<Simd<f32, 2>>::new(64);
+        let mut ring = RingDelay::<Simd<f32, 2>>::new(50);
+        assert_eq!(delay.len(), ring.len());
+
+        for i in 0..500 {
+            let pos = (i as f32 * 0.37) % 80. - 10.;
+            assert_eq!(delay.tap(pos), ring.tap(pos), "mismatch at {pos}");
+            let next = Simd::from_array([i as f32, -(i as f32)]);
+            delay.push_next(next);
+            ring.push_next(next);
+        }
+    }
+
+    fn ramp(len: usize) -> Delay<Simd<f32, 1>> {
+        let mut delay = Delay::new(len);
+        for i in 0..len {
+            delay.push_next(Simd::splat(i as f32));
+        }
+        delay
+    }
+
+    #[test]
+    fn tap_nearest() {
+        let mut delay = ramp(16);
+        assert_eq!(12., delay.tap_with(4.3, Interpolation::Nearest)[0]);
+        assert_eq!(11., delay.tap_with(4.7, Interpolation::Nearest)[0]);
+    }
+
+    #[test]
+    fn tap_linear() {
+        let mut delay = ramp(16);
+        assert_abs_diff_eq!(
+            11.7,
+            delay.tap_with(4.3, Interpolation::Linear)[0],
+            epsilon = 1e-5
+        );
+    }
+
+    #[test]
+    fn tap_cubic() {
+        let mut delay = ramp(16);
+        assert_abs_diff_eq!(
+            11.7,
+            delay.tap_with(4.3, Interpolation::Cubic)[0],
+            epsilon = 1e-5
+        );
+    }
+
+    #[test]
+    fn tap_allpass() {
+        let mut delay = Delay::<Simd<f32, 1>>::new(16);
+        let mut out = 0.;
+        for i in 0..64 {
+            out = delay.tap_with(4.3, Interpolation::Allpass)[0] - i as f32;
+            delay.push_next(Simd::splat(i as f32));
+        }
+        assert_abs_diff_eq!(-4.3, out, epsilon = 1e-4);
+    }
+
+    #[test]
+    fn get_thiran_per_lane() {
+        let mut delay = Delay::<Simd<f32, 2>>::new(16);
+        let mut state = Simd::splat(0.);
+        let mut out = Simd::splat(0.);
+        for i in 0..64 {
+            let pos = Simd::from_array([4.3, 7.8]);
+            out = delay.get_thiran(pos, &mut state) - Simd::splat(i as f32);
+            delay.push_next(Simd::splat(i as f32));
+        }
+        assert_abs_diff_eq!(-4.3, out[0], epsilon = 1e-4);
+        assert_abs_diff_eq!(-7.8, out[1], epsilon = 1e-4);
+    }
+
+    #[test]
+    fn resize_keeps_recent_history() {
+        let mut delay = Delay::<Simd<f32, 1>>::new(8);
+        for i in 0..8 {
+            delay.push_next(Simd::splat(i as f32));
+        }
+
+        delay.resize(16);
+        assert_eq!(16, delay.len());
+        // Reading at `n` returns the sample pushed `n` samples ago
+        for n in 1..=8 {
+            assert_eq!(8. - n as f32, delay.tap(n as f32)[0]);
+        }
+        for n in 9..16 {
+            assert_eq!(0., delay.tap(n as f32)[0]);
+        }
+
+        delay.resize(4);
+        assert_eq!(4, delay.len());
+        for n in 1..4 {
+            assert_eq!(8. - n as f32, delay.tap(n as f32)[0]);
+        }
+
+        delay.resize(0);
+        assert_eq!(Simd::splat(0.), delay.tap(1.));
+    }
+
+    #[test]
+    fn tap_many_matches_tap() {
+        let mut delay = Delay::<Simd<f32, 2>>::new(32);
+        for i in 0..40 {
+            delay.push_next(Simd::from_array([i as f32, (i as f32).sin()]));
+        }
+        let positions = [0., 1., 2.5, 7.25, 31.9, 32., 40.3];
+        let taps = delay.tap_many(positions);
+        for (pos, tap) in positions.into_iter().zip(taps) {
+            assert_eq!(delay.tap(pos), tap, "at {pos}");
+        }
+    }
+
+    #[test]
+    fn empty_is_silent() {
+        let mut delay = Delay::<Simd<f32, 2>>::new(0);
+        assert!(delay.is_empty());
+
+        delay.push_next(Simd::splat(1.));
+        assert!(delay.is_empty());
+        assert_eq!(Simd::splat(0.), delay.tap(1.5));
+        assert_eq!([Simd::splat(0.); 2], delay.tap_many([0., 3.]));
+        assert_eq!(Simd::splat(0.), delay.get(Simd::from_array([1., 2.5])));
+        for mode in [
+            Interpolation::Nearest,
+            Interpolation::Linear,
+            Interpolation::Cubic,
+            Interpolation::Allpass,
+        ] {
+            assert_eq!(Simd::splat(0.), delay.tap_with(2.3, mode), "{mode:?}");
+        }
+        let mut state = Simd::splat(0.);
+        assert_eq!(
+            Simd::splat(0.),
+            delay.get_thiran(Simd::splat(2.3), &mut state)
+        );
+    }
+
+    #[test]
+    fn ring_is_never_empty() {
+        let ring = RingDelay::<Simd<f32, 2>>::new(0);
+        assert!(!ring.is_empty());
+        assert_eq!(1, ring.len());
+    }
+}
@@ -4,67 +4,202 @@
 // https://opensource.org/licenses/MIT
 
 use std::f32::consts::TAU;
-use std::simd::{LaneCount, Simd, SupportedLaneCount};
 
 use nih_plug::nih_debug_assert;
 use rand::prelude::*;
 
 use crate::delay::Delay;
-use crate::householder;
+use crate::mixing::{Hadamard, Householder, Mixing};
+use crate::simd::{LaneCount, Simd, SupportedLaneCount};
 
-pub struct Diffusion<const L: usize>
+/// Spread of the delay times across lanes at full size and the default density, in seconds at a
+/// time scale of 1.
+const SPREAD: f32 = 300e-3;
+/// Smallest spread at the highest density, so that the taps stay apart.
+const MIN_SPREAD: f32 = 40e-3;
+/// Excursion of the modulated taps at full depth, in seconds.
+const MOD_DEPTH: f32 = 3e-3;
+/// Rate of the tap modulation, in Hz. The taps are always modulated by sines, whatever the shape
+/// of the main delay LFO.
+pub(crate) const MOD_RATE: f32 = 0.3;
+
+/// Orthogonal matrix used to mix the lanes of a [`Diffusion`] stage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MixMatrix {
+    Householder,
+    /// Normalized Hadamard matrix; brighter, and cheaper for large lane counts. Requires `L` to be
+    /// a power of two.
+    Hadamard,
+}
+
+impl Default for MixMatrix {
+    fn default() -> Self {
+        Self::Householder
+    }
+}
+
+impl<const L: usize> Mixing<L> for MixMatrix
+where
+    LaneCount<L>: SupportedLaneCount,
+{
+    fn mix(&self, x: Simd<f32, L>) -> Simd<f32, L> {
+        match self {
+            Self::Householder => Householder.mix(x),
+            Self::Hadamard => Hadamard.mix(x),
+        }
+    }
+}
+
+/// Diffusion stage mixing its lanes with `M`, which can be chosen at runtime with the default
+/// [`MixMatrix`].
+#[derive(Clone)]
+pub struct Diffusion<const L: usize, M = MixMatrix>
 where
     LaneCount<L>: SupportedLaneCount,
 {
     delay: Delay<Simd<f32, L>>,
     polarity: Simd<f32, L>,
     offsets: [f32; L],
-    phases: [f32; L],
+    /// Modulation phase of each lane, as a fraction of a cycle. Kept in double precision, as the
+    /// per-sample increments are small enough at high sample rates for single precision rounding
+    /// to stretch the period noticeably.
+    phases: [f64; L],
+    /// Input lane read by each output lane of the shuffle.
+    permutation: [usize; L],
+    /// Sign applied to each output lane of the shuffle, alternating so that half the lanes are
+    /// inverted.
+    signs: Simd<f32, L>,
+    matrix: M,
+    /// How tightly packed the taps are, between 0 (sparse) and 1 (dense).
+    density: f32,
+    /// Scale applied to the modulation depth passed in.
+    mod_scale: f32,
+    /// Factor applied to the delay times of the taps, setting the length of the stage.
+    time_scale: f32,
+    /// Sample rate the stage runs at. The delay line is sized for it, so a new sample rate calls
+    /// for a new stage rather than an update of this value.
     samplerate: f32,
 }
 
-impl<const L: usize> Diffusion<L>
+impl<const L: usize, M: Mixing<L> + Default> Diffusion<L, M>
 where
     LaneCount<L>: SupportedLaneCount,
 {
-    pub fn new(samplerate: f32) -> Self {
-        Self {
-            delay: Delay::new(samplerate as usize),
+    /// Creates a diffusion stage whose random delay offsets and modulation phases are drawn from
+    /// the given generator, making its output reproducible. The delay times of the taps are scaled
+    /// by `time_scale`, and the delay line holds as many seconds.
+    pub fn with_rng<R: Rng>(samplerate: f32, time_scale: f32, rng: &mut R) -> Self {
+        let mut stage = Self {
+            delay: Delay::new((samplerate * time_scale) as usize),
             polarity: {
                 let zeros = Simd::splat(-1.);
                 let ones = Simd::splat(1.);
                 let (_, res) = zeros.interleave(ones);
                 res
             },
-            offsets: {
-                let mut rng = thread_rng();
-                std::array::from_fn(|_| rng.gen_range(-1e-2..1e-2))
-            },
-            phases: std::array::from_fn(|_| rand::random()),
+            offsets: [0.; L],
+            phases: [0.; L],
+            permutation: permutation(),
+            signs: Simd::from_array(std::array::from_fn(|n| if n % 2 == 0 { 1. } else { -1. })),
+            matrix: M::default(),
+            density: 0.5,
+            mod_scale: 1.,
+            time_scale,
             samplerate,
-        }
+        };
+        stage.reseed(rng);
+        stage
+    }
+
+    /// Draws new random delay offsets and modulation phases from the given generator, and clears
+    /// the stage. Unlike creating a new stage, this does not allocate.
+    pub fn reseed<R: Rng>(&mut self, rng: &mut R) {
+        self.offsets = std::array::from_fn(|_| rng.gen_range(-1e-2..1e-2));
+        self.phases = std::array::from_fn(|_| f64::from(rng.gen::<f32>()));
+        self.reset();
+    }
+
+    /// Selects the matrix used to mix the delayed lanes.
+    pub fn with_mix_matrix(mut self, matrix: M) -> Self {
+        self.matrix = matrix;
+        self
+    }
+
+    /// Scales the modulation depth of the stage relative to the one passed in.
+    pub fn with_mod_scale(mut self, scale: f32) -> Self {
+        self.set_mod_scale(scale);
+        self
+    }
+
+    /// Changes the scale of the modulation depth, see [`Self::with_mod_scale`].
+    pub fn set_mod_scale(&mut self, scale: f32) {
+        self.mod_scale = scale;
+    }
+
+    /// Largest offset of the modulated taps at the given depth, in samples.
+    pub fn mod_excursion(&self, mod_depth: f32) -> f32 {
+        self.samplerate * MOD_DEPTH * self.mod_scale * mod_depth
+    }
+
+    /// Sets how tightly packed the taps are, between 0 (sparse, discrete echoes) and 1 (dense,
+    /// smooth).
+    pub fn set_density(&mut self, density: f32) {
+        self.density = density.clamp(0., 1.);
+    }
+
+    /// Spread of the delay times across lanes at full size, in seconds before the time scale.
+    fn spread(&self) -> f32 {
+        (2. * SPREAD * (1. - self.density)).max(MIN_SPREAD)
+    }
+
+    /// Clears the delay line, silencing the stage.
+    pub fn reset(&mut self) {
+        self.delay.reset();
+    }
+
+    /// Average delay across lanes at the given size without modulation, in samples.
+    pub fn mean_delay(&self, size: f32) -> f32 {
+        let spread = self.spread();
+        let sum: f32 = (0..L)
+            .map(|i| {
+                let t = i as f32 / L as f32;
+                self.delay.wrap_seconds(
+                    (spread * t * size + self.offsets[i]) * self.time_scale,
+                    self.samplerate,
+                )
+            })
+            .sum();
+        sum / L as f32
     }
 
     pub fn next_sample(&mut self, size: f32, mod_depth: f32, input: Simd<f32, L>) -> Simd<f32, L> {
+        let spread = self.spread();
+        let excursion = self.mod_excursion(mod_depth);
         let delays = std::array::from_fn(|i| {
             let t = i as f32 / L as f32;
-            self.samplerate
-                * (300e-3 * t * size
-                    + self.offsets[i]
-                    + 3e-3 * mod_depth * f32::sin(TAU * self.phases[i]))
+            // Negative offsets wrap around to the end of the delay line rather than being clamped
+            self.delay.wrap_seconds(
+                (spread * t * size + self.offsets[i]) * self.time_scale,
+                self.samplerate,
+            ) + excursion * f32::sin(TAU * self.phases[i] as f32)
         });
         for p in &mut self.phases {
-            *p += 0.3 / self.samplerate;
+            *p += f64::from(MOD_RATE / self.samplerate);
             if *p > 1. {
                 *p -= 1.;
             }
         }
         let taps = self.delay.get(Simd::from_array(delays));
-        let taps = shuffle(taps);
+        let taps = self.shuffle(taps);
         self.delay.push_next(input);
 
-        householder::transform(self.polarity * taps)
-        // taps
+        self.matrix.mix(self.polarity * taps)
+    }
+
+    /// Signed permutation of the lanes. Every input lane lands on exactly one output lane, so the
+    /// shuffle is orthogonal and preserves energy.
+    fn shuffle(&self, x: Simd<f32, L>) -> Simd<f32, L> {
+        Simd::from_array(std::array::from_fn(|n| x[self.permutation[n]])) * self.signs
     }
 
     pub fn next_block(&mut self, size: &[f32], mod_depth: &[f32], buffer: &mut [Simd<f32, L>]) {
@@ -79,15 +214,129 @@ where
     }
 }
 
-fn shuffle<const N: usize>(inp: Simd<f32, N>) -> Simd<f32, N>
-where
-    LaneCount<N>: SupportedLaneCount,
-{
-    let in_arr = inp.as_array();
-    let out_arr = std::array::from_fn(|n| {
-        let i = (n * 187 + 289) % N;
-        let k = if (n % 2) == 0 { 1. } else { -1. };
-        in_arr[i] * k
-    });
-    Simd::from_array(out_arr)
+/// Lane permutation of the shuffle, mapping output lane `n` to input lane `(187n + 289) mod L`.
+/// The multiplier is odd and so coprime with the power-of-two lane counts SIMD vectors come in,
+/// which makes the map a bijection.
+fn permutation<const L: usize>() -> [usize; L] {
+    std::array::from_fn(|n| (n * 187 + 289) % L)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use rand::prelude::*;
+
+    use crate::simd::Simd;
+
+    use super::{Diffusion, LaneCount, MixMatrix, SupportedLaneCount, MOD_RATE};
+
+    fn impulse_response(matrix: MixMatrix) -> Vec<Simd<f32, 4>> {
+        let mut diffusion = Diffusion::<4>::with_rng(1000., 1., &mut StdRng::seed_from_u64(42))
+            .with_mix_matrix(matrix);
+        (0..1000)
+            .map(|i| diffusion.next_sample(0.5, 0., Simd::splat(if i == 0 { 1. } else { 0. })))
+            .collect()
+    }
+
+    fn assert_shuffle_is_bijective<const L: usize>()
+    where
+        LaneCount<L>: SupportedLaneCount,
+    {
+        let mut permutation = super::permutation::<L>();
+        permutation.sort_unstable();
+        let identity: [usize; L] = std::array::from_fn(|i| i);
+        assert_eq!(identity, permutation);
+
+        let diffusion = Diffusion::<L>::with_rng(1000., 1., &mut StdRng::seed_from_u64(0));
+        let input = Simd::from_array(std::array::from_fn(|i| i as f32 + 1.));
+        let output = diffusion.shuffle(input);
+        assert_eq!((input * input).reduce_sum(), (output * output).reduce_sum());
+    }
+
+    #[test]
+    fn shuffle_is_signed_permutation() {
+        assert_shuffle_is_bijective::<2>();
+        assert_shuffle_is_bijective::<4>();
+        assert_shuffle_is_bijective::<8>();
+    }
+
+    #[test]
+    fn block_matches_per_sample() {
+        let mut diffusion = Diffusion::<4>::with_rng(1000., 1., &mut StdRng::seed_from_u64(0));
+        let mut diffusion_block = diffusion.clone();
+
+        let size: Vec<f32> = (0..256).map(|i| i as f32 / 256.).collect();
+        let mod_depth = vec![0.5; 256];
+        let input: Vec<Simd<f32, 4>> = (0..256)
+            .map(|i| Simd::splat(if i == 0 { 1. } else { 0. }))
+            .collect();
+
+        let expected: Vec<_> = input
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| diffusion.next_sample(size[i], mod_depth[i], v))
+            .collect();
+        let mut actual = input;
+        diffusion_block.next_block(&size, &mod_depth, &mut actual);
+
+        assert_eq!(expected, actual);
+    }
+
+    /// Period of the tap modulation of the stage, in seconds, measured between two wraps of its
+    /// phase.
+    pub(crate) fn modulation_period<const L: usize>(
+        diffusion: &mut Diffusion<L>,
+        samplerate: f32,
+    ) -> f32
+    where
+        LaneCount<L>: SupportedLaneCount,
+    {
+        let mut wraps = vec![];
+        for i in 0..(3. * samplerate / MOD_RATE) as usize {
+            let phase = diffusion.phases[0];
+            diffusion.next_sample(0.5, 0.5, Simd::splat(0.));
+            if diffusion.phases[0] < phase {
+                wraps.push(i);
+            }
+        }
+        (wraps[1] - wraps[0]) as f32 / samplerate
+    }
+
+    #[test]
+    fn modulation_period_is_samplerate_independent() {
+        let period = |samplerate: f32| {
+            let mut diffusion =
+                Diffusion::<4>::with_rng(samplerate, 1., &mut StdRng::seed_from_u64(0));
+            modulation_period(&mut diffusion, samplerate)
+        };
+        let (low, high) = (period(44100.), period(96000.));
+        assert!((low - MOD_RATE.recip()).abs() < 1e-3, "{low} s");
+        assert!((high - low).abs() < 1e-3, "{high} s vs {low} s");
+    }
+
+    #[test]
+    fn seeded_is_reproducible() {
+        let mut a = Diffusion::<4>::with_rng(1000., 1., &mut StdRng::seed_from_u64(42));
+        let mut b = Diffusion::<4>::with_rng(1000., 1., &mut StdRng::seed_from_u64(42));
+        for i in 0..256 {
+            let input = Simd::splat(if i == 0 { 1. } else { 0. });
+            assert_eq!(
+                a.next_sample(0.5, 0.5, input),
+                b.next_sample(0.5, 0.5, input)
+            );
+        }
+    }
+
+    #[test]
+    fn mix_matrices_preserve_energy_decay() {
+        let householder = impulse_response(MixMatrix::Householder);
+        let hadamard = impulse_response(MixMatrix::Hadamard);
+
+        // Both matrices are orthogonal, so the energy envelope is identical and only its
+        // distribution across lanes differs
+        assert_ne!(householder, hadamard);
+        for (a, b) in householder.into_iter().zip(hadamard) {
+            let (ea, eb) = ((a * a).reduce_sum(), (b * b).reduce_sum());
+            assert!((ea - eb).abs() < 1e-5, "{} != {}", ea, eb);
+        }
+    }
 }
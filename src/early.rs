@@ -3,28 +3,96 @@
 // This software is released under the MIT License.
 // https://opensource.org/licenses/MIT
 
-use std::simd::{LaneCount, Simd, SupportedLaneCount};
+use rand::prelude::*;
 
 use crate::diffusion::Diffusion;
+use crate::simd::{LaneCount, Simd, SupportedLaneCount};
+
+/// Largest number of diffusion stages that can be run.
+pub const MAX_STAGES: usize = 8;
+/// Time scale of the first stage, the later ones growing quadratically longer.
+const STAGE_LENGTH: f32 = 400e-3;
+/// Extra modulation depth of the last stage over the first, with the stages in between scaled
+/// linearly. The later stages are longer, and need more modulation to break up their resonances.
+const STAGE_MOD_SCALING: f32 = 1.;
+
+/// Scale of the modulation depth of the given stage, see [`STAGE_MOD_SCALING`].
+fn stage_mod_scale(stage: usize) -> f32 {
+    1. + STAGE_MOD_SCALING * stage as f32 / (MAX_STAGES - 1) as f32
+}
 
 pub struct Early<const LANES: usize>
 where
     LaneCount<LANES>: SupportedLaneCount,
 {
-    ap: [Diffusion<LANES>; LANES],
+    ap: Vec<Diffusion<LANES>>,
+    /// Number of stages the signal goes through, starting from the first.
+    stages: usize,
 }
 
 impl<const LANES: usize> Early<LANES>
 where
     LaneCount<LANES>: SupportedLaneCount,
 {
-    pub fn new(samplerate: f32) -> Self {
+    /// Creates the diffusion stages with their randomization drawn from the given generator.
+    pub fn with_rng<R: Rng>(samplerate: f32, rng: &mut R) -> Self {
         Self {
-            ap: std::array::from_fn(|i| {
-                Diffusion::new(400e-3 * samplerate * (1. + (i as f32 / LANES as f32).powi(2)))
-            }),
+            ap: (0..MAX_STAGES)
+                .map(|i| {
+                    Diffusion::with_rng(
+                        samplerate,
+                        STAGE_LENGTH * (1. + (i as f32 / LANES as f32).powi(2)),
+                        rng,
+                    )
+                    .with_mod_scale(stage_mod_scale(i))
+                })
+                .collect(),
+            stages: LANES.min(MAX_STAGES),
+        }
+    }
+
+    /// Draws new randomization for every stage from the given generator, leaving them as
+    /// [`Self::with_rng`] would have created them, without reallocating. The stages start out
+    /// silent.
+    pub fn reseed<R: Rng>(&mut self, rng: &mut R) {
+        for ap in &mut self.ap {
+            ap.reseed(rng);
+        }
+    }
+
+    /// Sets how tightly packed the taps of every stage are, between 0 (sparse) and 1 (dense).
+    pub fn set_density(&mut self, density: f32) {
+        for ap in &mut self.ap {
+            ap.set_density(density);
+        }
+    }
+
+    /// Sets whether the later stages modulate deeper than the first, as they do by default. Without
+    /// it, every stage modulates by the same depth.
+    pub fn set_stage_mod_scaling(&mut self, scaling: bool) {
+        for (stage, ap) in self.ap.iter_mut().enumerate() {
+            ap.set_mod_scale(if scaling { stage_mod_scale(stage) } else { 1. });
+        }
+    }
+
+    /// Clears all the diffusion stages.
+    pub fn reset(&mut self) {
+        for ap in &mut self.ap {
+            ap.reset();
         }
     }
+
+    /// Sets the number of diffusion stages to run, between 1 and [`MAX_STAGES`]. Stages that get
+    /// enabled start out silent.
+    pub fn set_stages(&mut self, stages: usize) {
+        let stages = stages.clamp(1, MAX_STAGES);
+        if stages > self.stages {
+            for ap in &mut self.ap[self.stages..stages] {
+                ap.reset();
+            }
+        }
+        self.stages = stages;
+    }
 }
 
 impl<const LANES: usize> Early<LANES>
@@ -37,14 +105,123 @@ where
         mod_depth: f32,
         input: Simd<f32, LANES>,
     ) -> Simd<f32, LANES> {
-        self.ap
+        self.ap[..self.stages]
             .iter_mut()
             .fold(input, |s, ap| ap.next_sample(size, mod_depth, s))
     }
 
+    /// Average delay through all the diffusion stages at the given size, in samples.
+    pub fn mean_delay(&self, size: f32) -> f32 {
+        self.ap[..self.stages]
+            .iter()
+            .map(|ap| ap.mean_delay(size))
+            .sum()
+    }
+
     pub fn next_block(&mut self, size: &[f32], mod_depth: &[f32], buffer: &mut [Simd<f32, LANES>]) {
-        for diffuse in self.ap.iter_mut() {
+        for diffuse in self.ap[..self.stages].iter_mut() {
             diffuse.next_block(size, mod_depth, buffer);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+
+    use crate::diffusion::tests::modulation_period;
+    use crate::diffusion::MOD_RATE;
+    use crate::simd::f32x4;
+
+    use super::{Early, MAX_STAGES};
+
+    /// Number of audible samples in the first `len` ms of the impulse response of the given
+    /// number of stages.
+    fn echoes(stages: usize, density: f32, len: usize) -> usize {
+        let mut early = Early::<4>::with_rng(1000., &mut StdRng::seed_from_u64(0));
+        early.set_stages(stages);
+        early.set_density(density);
+        (0..len)
+            .map(|i| {
+                let input = f32x4::splat(if i == 0 { 1. } else { 0. });
+                early.next_sample(1., 0., input)
+            })
+            .filter(|x| x.to_array().iter().any(|x| x.abs() > 1e-4))
+            .count()
+    }
+
+    #[test]
+    fn more_stages_are_denser() {
+        let densities: Vec<usize> = [1, 2, 4, 8]
+            .into_iter()
+            .map(|stages| echoes(stages, 0.5, 2000))
+            .collect();
+        for pair in densities.windows(2) {
+            assert!(pair[1] > pair[0], "{densities:?}");
+        }
+    }
+
+    #[test]
+    fn higher_density_packs_echoes() {
+        let sparse = echoes(4, 0., 150);
+        let dense = echoes(4, 1., 150);
+        assert!(dense > 2 * sparse, "{dense} vs {sparse}");
+    }
+
+    #[test]
+    fn reseed_matches_new_stages() {
+        let impulse_response = |early: &mut Early<4>| {
+            (0..2000)
+                .map(|i| early.next_sample(1., 0.5, f32x4::splat(if i == 0 { 1. } else { 0. })))
+                .collect::<Vec<_>>()
+        };
+        let mut reseeded = Early::<4>::with_rng(1000., &mut StdRng::seed_from_u64(0));
+        impulse_response(&mut reseeded);
+        reseeded.reseed(&mut StdRng::seed_from_u64(1));
+        let mut fresh = Early::<4>::with_rng(1000., &mut StdRng::seed_from_u64(1));
+        assert_eq!(
+            impulse_response(&mut fresh),
+            impulse_response(&mut reseeded)
+        );
+    }
+
+    #[test]
+    fn deeper_stages_modulate_more() {
+        let mut early = Early::<4>::with_rng(1000., &mut StdRng::seed_from_u64(0));
+        early.set_stages(MAX_STAGES);
+        let excursions: Vec<f32> = (0..MAX_STAGES)
+            .map(|stage| early.ap[stage].mod_excursion(0.5))
+            .collect();
+        for pair in excursions.windows(2) {
+            assert!(pair[1] > pair[0], "{excursions:?}");
+        }
+    }
+
+    #[test]
+    fn stage_mod_scaling_can_be_disabled() {
+        let mut early = Early::<4>::with_rng(1000., &mut StdRng::seed_from_u64(0));
+        let scaled: Vec<f32> = early.ap.iter().map(|ap| ap.mod_excursion(0.5)).collect();
+        early.set_stage_mod_scaling(false);
+        assert_eq!(scaled[0], early.ap[0].mod_excursion(0.5));
+        for (ap, scaled) in early.ap.iter().zip(&scaled).skip(1) {
+            assert!(ap.mod_excursion(0.5) < *scaled);
+        }
+
+        early.set_stage_mod_scaling(true);
+        let restored: Vec<f32> = early.ap.iter().map(|ap| ap.mod_excursion(0.5)).collect();
+        assert_eq!(scaled, restored);
+    }
+
+    #[test]
+    fn stages_modulate_at_the_same_rate() {
+        let samplerate = 44100.;
+        let mut early = Early::<4>::with_rng(samplerate, &mut StdRng::seed_from_u64(0));
+        for (stage, ap) in early.ap.iter_mut().enumerate() {
+            let period = modulation_period(ap, samplerate);
+            assert!(
+                (period - MOD_RATE.recip()).abs() < 1e-3,
+                "stage {stage}: {period} s"
+            );
+        }
+    }
+}
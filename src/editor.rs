@@ -1,18 +1,24 @@
-use std::sync::Arc;
+use std::sync::{atomic::Ordering, Arc};
+use std::time::Duration;
 
 // Copyright (c) 2022 solarliner
 //
 // This software is released under the MIT License.
 // https://opensource.org/licenses/MIT
+use atomic_float::AtomicF32;
 use nih_plug::prelude::*;
 use nih_plug_vizia::{
     assets, create_vizia_editor,
     vizia::prelude::*,
-    widgets::{GenericUi, ResizeHandle},
+    widgets::{ParamSlider, PeakMeter, ResizeHandle},
     ViziaState,
 };
 
-use crate::DelayParams;
+use crate::{
+    ab,
+    presets::{Preset, FACTORY_PRESETS},
+    DampingCutoffs, DelayParams,
+};
 
 /// VIZIA uses points instead of pixels for text
 const POINT_SCALE: f32 = 0.75;
@@ -20,22 +26,26 @@ const POINT_SCALE: f32 = 0.75;
 #[derive(Lens)]
 pub(crate) struct DelayEditor {
     params: Arc<DelayParams>,
+    peak_meter: Arc<AtomicF32>,
 }
 
 impl Model for DelayEditor {}
 
 impl DelayEditor {
     pub fn default_state() -> Arc<ViziaState> {
-        ViziaState::from_size(380, 300)
+        ViziaState::from_size(960, 640)
     }
 
     pub fn create(
         params: Arc<DelayParams>,
+        peak_meter: Arc<AtomicF32>,
+        damping_cutoffs: Arc<DampingCutoffs>,
         editor_state: Arc<ViziaState>,
     ) -> Option<Box<dyn Editor>> {
-        create_vizia_editor(editor_state, move |cx, _| {
+        create_vizia_editor(editor_state, move |cx, context| {
             DelayEditor {
                 params: params.clone(),
+                peak_meter: peak_meter.clone(),
             }
             .build(cx);
             ResizeHandle::new(cx);
@@ -47,19 +57,260 @@ impl DelayEditor {
                     .child_top(Stretch(1.0))
                     .child_bottom(Pixels(10.0))
                     .right(Percentage(12.0));
+                HStack::new(cx, |cx| {
+                    for preset in FACTORY_PRESETS {
+                        let (params, context) = (params.clone(), context.clone());
+                        Button::new(
+                            cx,
+                            move |_| load_preset(context.as_ref(), &params, preset),
+                            |cx| Label::new(cx, preset.name),
+                        );
+                    }
+                    let (switch_params, switch_context) = (params.clone(), context.clone());
+                    Button::new(
+                        cx,
+                        move |_| {
+                            set_normalized(switch_context.as_ref(), ab::switch(&switch_params))
+                        },
+                        |cx| Label::new(cx, "A/B"),
+                    );
+                    let (copy_params, copy_context) = (params.clone(), context.clone());
+                    Button::new(
+                        cx,
+                        move |_| {
+                            set_normalized(copy_context.as_ref(), ab::copy_a_to_b(&copy_params))
+                        },
+                        |cx| Label::new(cx, "A → B"),
+                    );
+                })
+                .height(Auto)
+                .col_between(Pixels(5.0))
+                .child_left(Stretch(1.0))
+                .child_right(Stretch(1.0));
+                // The longest columns don't fit the window, they scroll vertically instead
                 ScrollView::new(cx, 0.0, 0.0, false, true, |cx| {
-                    GenericUi::new(cx, DelayEditor::params)
-                        .width(Percentage(100.0))
-                        .height(Auto)
-                        .child_top(Pixels(5.0))
-                        .child_right(Pixels(10.0));
+                    HStack::new(cx, |cx| {
+                        section(cx, "Size/Decay", |cx| {
+                            slider(cx, "Mode", |params| &params.mode);
+                            slider(cx, "Size", |params| &params.size);
+                            slider(cx, "Delay", |params| &params.delay);
+                            slider(cx, "Size Scales Delay", |params| &params.size_scales_delay);
+                            slider(cx, "Delay Sync", |params| &params.delay_sync);
+                            slider(cx, "Delay Division", |params| &params.delay_division);
+                            slider(cx, "Delay Crossfade", |params| &params.delay_crossfade);
+                            slider(cx, "Diffuser", |params| &params.diffuser);
+                            slider(cx, "Allpass Gain", |params| &params.diffusion_gain);
+                            slider(cx, "Diffusion", |params| &params.diffusion_stages);
+                            slider(cx, "Stage Mod Scaling", |params| &params.stage_mod_scaling);
+                            slider(cx, "Density", |params| &params.density);
+                            slider(cx, "Seed", |params| &params.seed);
+                            slider(cx, "Decay Mode", |params| &params.decay_mode);
+                            slider(cx, "Feedback", |params| &params.feedback);
+                            slider(cx, "Decay Time", |params| &params.decay_time);
+                            slider(cx, "Low Decay", |params| &params.low_decay);
+                            slider(cx, "High Decay", |params| &params.high_decay);
+                            slider(cx, "Late Level", |params| &params.late);
+                            slider(cx, "Freeze", |params| &params.freeze);
+                        });
+                        section(cx, "Modulation", |cx| {
+                            slider(cx, "Depth", |params| &params.mod_depth);
+                            slider(cx, "Speed", |params| &params.mod_speed);
+                            slider(cx, "Sync", |params| &params.mod_sync);
+                            slider(cx, "Division", |params| &params.mod_division);
+                            slider(cx, "Delay Shape", |params| &params.mod_shape);
+                        });
+                        section(cx, "Gate", |cx| {
+                            slider(cx, "Gate", |params| &params.gate.enabled);
+                            slider(cx, "Threshold", |params| &params.gate.threshold);
+                            slider(cx, "Hold", |params| &params.gate.hold);
+                        });
+                        section(cx, "Damping", |cx| {
+                            slider(cx, "Input Low Cut", |params| &params.input_low_cut);
+                            slider(cx, "Input High Cut", |params| &params.input_high_cut);
+                            slider(cx, "Low", |params| &params.damp_low);
+                            slider(cx, "High", |params| &params.damp_high);
+                            slider(cx, "Slope", |params| &params.damp_slope);
+                            slider(cx, "Tone", |params| &params.tone);
+                            DampingCurve::new(cx, damping_cutoffs.clone())
+                                .width(Percentage(100.0))
+                                .height(Pixels(80.0));
+                        });
+                        section(cx, "Shimmer/Mix", |cx| {
+                            slider(cx, "Shimmer", |params| &params.pitch_amt);
+                            slider(cx, "Interval", |params| &params.pitch_interval);
+                            slider(cx, "Shimmer Feedback", |params| &params.shimmer_feedback);
+                            slider(cx, "Shimmer Tone", |params| &params.shimmer_tone);
+                            slider(cx, "Width", |params| &params.width);
+                            slider(cx, "Decorrelation", |params| &params.decorrelation);
+                            slider(cx, "Cross Feedback", |params| &params.cross_feedback);
+                            slider(cx, "Stereo Offset", |params| &params.stereo_offset);
+                            slider(cx, "Ping-Pong", |params| &params.ping_pong);
+                            slider(cx, "Ping-Pong Amount", |params| &params.ping_pong_amount);
+                            slider(cx, "Mono Tank", |params| &params.mono_tank);
+                            slider(cx, "Mix", |params| &params.mix);
+                            slider(cx, "Wet Solo", |params| &params.wet_solo);
+                            slider(cx, "Output", |params| &params.output_gain);
+                            slider(cx, "Limiter", |params| &params.limiter);
+                            slider(cx, "Ceiling", |params| &params.ceiling);
+                            slider(cx, "Ducking", |params| &params.duck_amount);
+                            slider(cx, "Duck Release", |params| &params.duck_release);
+                            slider(cx, "Duck Source", |params| &params.duck_source);
+                            slider(cx, "Saturation", |params| &params.saturation);
+                            slider(cx, "Drive", |params| &params.drive);
+                            slider(cx, "Oversampling", |params| &params.oversampling);
+                            slider(cx, "Reverse", |params| &params.reverse);
+                            slider(cx, "Bypass", |params| &params.bypass);
+                            momentary_button(cx, "Panic", &context, &params, |params| {
+                                &params.panic
+                            });
+                        });
+                    })
+                    .height(Auto)
+                    .col_between(Pixels(15.0))
+                    .child_left(Stretch(1.0))
+                    .child_right(Stretch(1.0));
                 })
-                .width(Percentage(100.0));
+                .width(Percentage(100.0))
+                .height(Stretch(1.0));
+                PeakMeter::new(
+                    cx,
+                    DelayEditor::peak_meter
+                        .map(|peak_meter| util::gain_to_db(peak_meter.load(Ordering::Relaxed))),
+                    Some(Duration::from_millis(600)),
+                )
+                .top(Pixels(10.0));
             })
             .width(Percentage(100.0))
+            .height(Percentage(100.0))
             .row_between(Pixels(0.0))
             .child_left(Stretch(1.0))
             .child_right(Stretch(1.0));
         })
     }
 }
+
+/// Sets every parameter to its value in `preset`, as if the user had moved them all at once.
+fn load_preset(context: &dyn GuiContext, params: &DelayParams, preset: &Preset) {
+    let values = preset.normalized_values(params);
+    set_normalized(
+        context,
+        values.into_iter().map(|(_, param, value)| (param, value)),
+    );
+}
+
+/// Sets each parameter to its normalized value, as if the user had moved them all at once.
+fn set_normalized(context: &dyn GuiContext, values: impl IntoIterator<Item = (ParamPtr, f32)>) {
+    for (param, value) in values {
+        // SAFETY: the parameters live as long as the editor that is holding on to them
+        unsafe {
+            context.raw_begin_set_parameter(param);
+            context.raw_set_parameter_normalized(param, value);
+            context.raw_end_set_parameter(param);
+        }
+    }
+}
+
+/// A titled column of controls.
+fn section(cx: &mut Context, title: &str, content: impl FnOnce(&mut Context)) {
+    VStack::new(cx, |cx| {
+        Label::new(cx, title)
+            .font_size(20.0 * POINT_SCALE)
+            .height(Pixels(30.0))
+            .child_top(Stretch(1.0))
+            .child_bottom(Pixels(5.0));
+        content(cx);
+    })
+    .width(Pixels(170.0))
+    .height(Auto)
+    .row_between(Pixels(5.0));
+}
+
+/// A button switching a parameter on while it is held down, and back off when released.
+fn momentary_button<F>(
+    cx: &mut Context,
+    label: &'static str,
+    context: &Arc<dyn GuiContext>,
+    params: &Arc<DelayParams>,
+    params_to_param: F,
+) where
+    F: Fn(&DelayParams) -> &BoolParam + Copy + 'static,
+{
+    let (press_context, press_params) = (context.clone(), params.clone());
+    let (release_context, release_params) = (context.clone(), params.clone());
+    Button::new(
+        cx,
+        move |_| {
+            let setter = ParamSetter::new(press_context.as_ref());
+            setter.begin_set_parameter(params_to_param(&press_params));
+            setter.set_parameter(params_to_param(&press_params), true);
+        },
+        |cx| Label::new(cx, label),
+    )
+    .on_release(move |_| {
+        let setter = ParamSetter::new(release_context.as_ref());
+        setter.set_parameter(params_to_param(&release_params), false);
+        setter.end_set_parameter(params_to_param(&release_params));
+    })
+    .width(Percentage(100.0));
+}
+
+/// A labelled slider for a single parameter.
+fn slider<P, F>(cx: &mut Context, label: &str, params_to_param: F)
+where
+    P: Param + 'static,
+    F: Fn(&Arc<DelayParams>) -> &P + Copy + 'static,
+{
+    Label::new(cx, label).height(Pixels(20.0));
+    ParamSlider::new(cx, DelayEditor::params, params_to_param).width(Percentage(100.0));
+}
+
+/// Lower and upper bounds of the damping curve display, in Hz.
+const CURVE_FREQ_RANGE: (f32, f32) = (20.0, 20e3);
+/// Lower and upper bounds of the damping curve display, in dB.
+const CURVE_DB_RANGE: (f32, f32) = (-24.0, 6.0);
+/// Number of points the damping curve is drawn with.
+const CURVE_POINTS: usize = 128;
+
+/// Frequency-magnitude plot of the combined damping filters.
+struct DampingCurve {
+    cutoffs: Arc<DampingCutoffs>,
+}
+
+impl DampingCurve {
+    fn new(cx: &mut Context, cutoffs: Arc<DampingCutoffs>) -> Handle<Self> {
+        Self { cutoffs }.build(cx, |_| {})
+    }
+}
+
+impl View for DampingCurve {
+    fn element(&self) -> Option<&'static str> {
+        Some("damping-curve")
+    }
+
+    fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
+        let bounds = cx.bounds();
+        if bounds.w == 0.0 || bounds.h == 0.0 {
+            return;
+        }
+
+        let (f_min, f_max) = CURVE_FREQ_RANGE;
+        let (db_min, db_max) = CURVE_DB_RANGE;
+        let mut path = vg::Path::new();
+        for i in 0..CURVE_POINTS {
+            let t = i as f32 / (CURVE_POINTS - 1) as f32;
+            let freq = f_min * (f_max / f_min).powf(t);
+            let db = util::gain_to_db(self.cutoffs.response(freq)).clamp(db_min, db_max);
+            let x = bounds.x + t * bounds.w;
+            let y = bounds.y + (db_max - db) / (db_max - db_min) * bounds.h;
+            if i == 0 {
+                path.move_to(x, y);
+            } else {
+                path.line_to(x, y);
+            }
+        }
+        canvas.stroke_path(
+            &mut path,
+            &vg::Paint::color(vg::Color::rgb(200, 200, 200)).with_line_width(1.5),
+        );
+    }
+}
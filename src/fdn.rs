@@ -0,0 +1,121 @@
+// Copyright (c) 2022 solarliner
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use crate::biquad::{Biquad, BiquadParams};
+use crate::delay::Delay;
+use crate::mixing::{Householder, Mixing};
+use crate::simd::{f32x2, f32x8, Simd};
+
+/// Delay line lengths at 48 kHz and a size of 1, in samples. They are mutually prime so that the
+/// echoes of the different lines never line up.
+const LENGTHS: [f32; 8] = [1433., 1601., 1867., 2053., 2251., 2399., 2687., 2903.];
+/// Scale applied to the delay lengths at the smallest size.
+const MIN_SCALE: f32 = 0.25;
+
+/// Feedback delay network producing a dense late reverberation tail from a stereo input, with
+/// the lines fed back through the matrix `M`.
+#[derive(Debug, Clone)]
+pub struct Fdn<M = Householder> {
+    delay: Delay<f32x8>,
+    damping: Biquad<8>,
+    matrix: M,
+    samplerate: f32,
+}
+
+impl<M: Mixing<8> + Default> Fdn<M> {
+    pub fn new(samplerate: f32) -> Self {
+        let max_len = LENGTHS[7] * samplerate / 48e3;
+        Self {
+            delay: Delay::new(max_len.ceil() as usize + 4),
+            damping: Biquad::default(),
+            matrix: M::default(),
+            samplerate,
+        }
+    }
+
+    /// Clears the delay lines and the damping filters.
+    pub fn reset(&mut self) {
+        self.delay.reset();
+        self.damping.reset();
+    }
+
+    /// Sets the cutoff of the lowpass filters damping each line, normalized to the sample rate.
+    pub fn set_damping(&mut self, fc: f32) {
+        self.damping.params = BiquadParams::lowpass_1p(Simd::splat(fc));
+    }
+
+    /// Processes the next stereo sample through the network, decaying by 60 dB in `rt60` seconds.
+    pub fn next_sample(&mut self, size: f32, rt60: f32, input: f32x2) -> f32x2 {
+        let scale = self.samplerate / 48e3 * (MIN_SCALE + (1. - MIN_SCALE) * size);
+        let lengths = f32x8::from_array(LENGTHS) * Simd::splat(scale);
+        // Each line is attenuated proportionally to its length so that they all decay together
+        let gains = f32x8::from_array(
+            (lengths / Simd::splat(rt60 * self.samplerate))
+                .to_array()
+                .map(|t| f32::powf(10., -3. * t)),
+        );
+
+        let taps = self.delay.get(lengths);
+        let feedback = self.matrix.mix(self.damping.next_sample(taps) * gains);
+        // Spread each input channel over half the lines, keeping the injected energy unchanged
+        let [l, r] = input.to_array();
+        let input = f32x8::from_array([l, r, l, r, l, r, l, r]) * Simd::splat(0.5);
+        self.delay.push_next(feedback + input);
+
+        let taps = taps.to_array();
+        let (l, r) = (0..4).fold((0., 0.), |(l, r), i| (l + taps[2 * i], r + taps[2 * i + 1]));
+        f32x2::from_array([l, r]) * Simd::splat(0.5)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simd::f32x2;
+
+    use super::Fdn;
+
+    fn impulse_response(samplerate: f32, rt60: f32, len: usize) -> Vec<f32> {
+        let mut fdn: Fdn = Fdn::new(samplerate);
+        (0..len)
+            .map(|i| {
+                let input = f32x2::from_array([if i == 0 { 1. } else { 0. }, 0.]);
+                fdn.next_sample(1., rt60, input)[0]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn impulse_response_is_dense() {
+        let samplerate = 48e3;
+        let ir = impulse_response(samplerate, 2., samplerate as usize);
+
+        // After a few round-trips, echoes should overlap into a continuous tail
+        let tail = &ir[samplerate as usize / 4..];
+        let nonzero = tail.iter().filter(|x| x.abs() > 1e-6).count();
+        assert!(nonzero as f32 > 0.9 * tail.len() as f32);
+
+        // No lag within the range of the delay lines should stand out as a periodic echo
+        let energy: f32 = tail.iter().map(|x| x * x).sum();
+        for lag in 1000..6000 {
+            let corr: f32 = tail.iter().zip(&tail[lag..]).map(|(a, b)| a * b).sum();
+            assert!(corr.abs() < 0.5 * energy, "periodic echo at lag {lag}");
+        }
+    }
+
+    #[test]
+    fn impulse_response_decays() {
+        let samplerate = 48e3;
+        let ir = impulse_response(samplerate, 1., 2 * samplerate as usize);
+
+        let window = samplerate as usize / 10;
+        let energies: Vec<f32> = ir[window..]
+            .chunks(window)
+            .map(|c| c.iter().map(|x| x * x).sum())
+            .collect();
+        for pair in energies.windows(2) {
+            assert!(pair[1] < pair[0], "{} >= {}", pair[1], pair[0]);
+        }
+    }
+}
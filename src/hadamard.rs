@@ -3,16 +3,35 @@
 // This software is released under the MIT License.
 // https://opensource.org/licenses/MIT
 
-use std::simd::{LaneCount, Simd, SupportedLaneCount};
+use crate::simd::{LaneCount, Simd, SupportedLaneCount};
 
+/// Unnormalized fast Walsh–Hadamard transform. Lane counts are always powers of two, as
+/// required by [`fwht_in_place`].
 #[inline]
 pub fn fwht<const L: usize>(mut a: Simd<f32, L>) -> Simd<f32, L>
 where
     LaneCount<L>: SupportedLaneCount,
 {
+    fwht_in_place(a.as_mut_array());
+    a
+}
+
+/// Unnormalized fast Walsh–Hadamard transform of `a` in place.
+///
+/// # Panics
+///
+/// The butterflies pair up the halves of ever larger blocks, which only covers every element
+/// when the length of `a` is a power of two. Other lengths would silently give a transform that
+/// isn't orthogonal, so they panic instead in debug builds.
+pub fn fwht_in_place(a: &mut [f32]) {
+    let len = a.len();
+    debug_assert!(
+        len.is_power_of_two(),
+        "the Hadamard transform needs a power of two length, got {len}"
+    );
     let mut h = 1;
-    while h < L {
-        for i in (0..L).step_by(h * 2) {
+    while h < len {
+        for i in (0..len).step_by(h * 2) {
             for j in i..i + h {
                 let x = a[j];
                 let y = a[j + h];
@@ -22,6 +41,65 @@ where
         }
         h *= 2;
     }
+}
 
-    a
+/// Orthonormal fast Walsh–Hadamard transform, scaled by `1/sqrt(L)` so that the signal energy is
+/// preserved, making it suitable as a feedback mixing matrix.
+#[inline]
+pub fn fwht_normalized<const L: usize>(a: Simd<f32, L>) -> Simd<f32, L>
+where
+    LaneCount<L>: SupportedLaneCount,
+{
+    fwht(a) * Simd::splat((L as f32).sqrt().recip())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+
+    use crate::simd::Simd;
+
+    use super::*;
+
+    fn norm<const L: usize>(a: Simd<f32, L>) -> f32
+    where
+        LaneCount<L>: SupportedLaneCount,
+    {
+        (a * a).reduce_sum().sqrt()
+    }
+
+    #[test]
+    fn normalized_preserves_norm() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            let input =
+                Simd::<f32, 8>::from_array(std::array::from_fn(|_| rng.gen_range(-1. ..1.)));
+            let output = fwht_normalized(input);
+            assert!((norm(input) - norm(output)).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn normalized_is_involution() {
+        let input = Simd::from_array([1., -2., 0.5, 3.]);
+        let output = fwht_normalized(fwht_normalized(input));
+        for (a, b) in input.to_array().into_iter().zip(output.to_array()) {
+            assert!((a - b).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn slice_matches_simd() {
+        let input = [1., -2., 0.5, 3., 0., 1., -1., 2.];
+        let mut output = input;
+        fwht_in_place(&mut output);
+        assert_eq!(fwht(Simd::from_array(input)).to_array(), output);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "power of two")]
+    fn rejects_non_power_of_two() {
+        fwht_in_place(&mut [1., 2., 3.]);
+    }
 }
@@ -3,15 +3,50 @@
 // This software is released under the MIT License.
 // https://opensource.org/licenses/MIT
 
-use std::simd::{LaneCount, Simd, SupportedLaneCount};
+use crate::simd::{LaneCount, Simd, SupportedLaneCount};
 
-pub fn transform<const L: usize>(mut v: Simd<f32, L>) -> Simd<f32, L>
+/// Householder reflection `I - 2/L * 1 1ᵀ`, mixing every lane into every other with a single
+/// horizontal sum.
+#[inline]
+pub fn transform<const L: usize>(v: Simd<f32, L>) -> Simd<f32, L>
 where
     LaneCount<L>: SupportedLaneCount,
 {
-    let s = v.to_array().into_iter().sum::<f32>() * (-2.0 / L as f32);
-    for i in 0..L {
-        v[i] += s;
+    v + Simd::splat(v.reduce_sum() * (-2.0 / L as f32))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+
+    use crate::simd::Simd;
+
+    use super::*;
+
+    /// Lane-by-lane version of [`transform`].
+    fn transform_scalar<const L: usize>(mut v: Simd<f32, L>) -> Simd<f32, L>
+    where
+        LaneCount<L>: SupportedLaneCount,
+    {
+        let s = v.to_array().into_iter().sum::<f32>() * (-2.0 / L as f32);
+        for i in 0..L {
+            v[i] += s;
+        }
+        v
+    }
+
+    #[test]
+    fn matches_scalar() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..1000 {
+            let input =
+                Simd::<f32, 8>::from_array(std::array::from_fn(|_| rng.gen_range(-1. ..1.)));
+            let expected = transform_scalar(input);
+            let actual = transform(input);
+            for (a, b) in actual.to_array().into_iter().zip(expected.to_array()) {
+                // Only the order of the additions in the sum may differ
+                assert!((a - b).abs() <= 1e-6, "{a} != {b}");
+            }
+        }
     }
-    v
 }
@@ -0,0 +1,148 @@
+// Copyright (c) 2022 solarliner
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use std::f32::consts::TAU;
+
+use nih_plug::prelude::*;
+use rand::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum LfoShape {
+    #[id = "sine"]
+    #[name = "Sine"]
+    Sine,
+    #[id = "tri"]
+    #[name = "Triangle"]
+    Triangle,
+    /// Random value held for a whole cycle.
+    #[id = "snh"]
+    #[name = "Sample & Hold"]
+    SampleAndHold,
+    /// Random values glided between over each cycle.
+    #[id = "noise"]
+    #[name = "Noise"]
+    Noise,
+}
+
+/// Low-frequency oscillator producing values in `[-1, 1]`.
+#[derive(Debug, Clone)]
+pub struct Lfo {
+    phase: f32,
+    held: f32,
+    next: f32,
+    rng: StdRng,
+}
+
+impl Lfo {
+    pub fn new() -> Self {
+        Self::with_rng(StdRng::from_entropy())
+    }
+
+    pub fn with_rng(mut rng: StdRng) -> Self {
+        Self {
+            phase: 0.,
+            held: rng.gen_range(-1. ..=1.),
+            next: rng.gen_range(-1. ..=1.),
+            rng,
+        }
+    }
+
+    /// Starts the oscillator at the given phase, as a fraction of a cycle.
+    pub fn with_phase(mut self, phase: f32) -> Self {
+        self.phase = phase.rem_euclid(1.);
+        self
+    }
+
+    pub fn value(&self, shape: LfoShape) -> f32 {
+        match shape {
+            LfoShape::Sine => f32::sin(TAU * self.phase),
+            // Aligned with the sine: rising through zero at the start of the cycle
+            LfoShape::Triangle => 4. * f32::abs((self.phase + 0.75).fract() - 0.5) - 1.,
+            LfoShape::SampleAndHold => self.held,
+            LfoShape::Noise => self.held + (self.next - self.held) * self.phase,
+        }
+    }
+
+    /// Advances the oscillator by one sample, drawing new random values at each cycle.
+    pub fn tick(&mut self, samplerate: f32, freq: f32) {
+        self.phase += freq / samplerate;
+        if self.phase >= 1. {
+            self.phase -= 1.;
+            self.held = self.next;
+            self.next = self.rng.gen_range(-1. ..=1.);
+        }
+    }
+}
+
+impl Default for Lfo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+
+    use super::{Lfo, LfoShape};
+
+    fn render(shape: LfoShape, len: usize) -> Vec<f32> {
+        let mut lfo = Lfo::with_rng(StdRng::seed_from_u64(0));
+        (0..len)
+            .map(|_| {
+                let value = lfo.value(shape);
+                // Exactly 128 samples per cycle, without rounding errors in the phase
+                lfo.tick(1024., 8.);
+                value
+            })
+            .collect()
+    }
+
+    #[test]
+    fn values_in_range() {
+        for shape in [
+            LfoShape::Sine,
+            LfoShape::Triangle,
+            LfoShape::SampleAndHold,
+            LfoShape::Noise,
+        ] {
+            assert!(render(shape, 1024).iter().all(|x| (-1. ..=1.).contains(x)));
+        }
+    }
+
+    #[test]
+    fn triangle_is_piecewise_linear() {
+        let values = render(LfoShape::Triangle, 1024);
+        assert_eq!(0., values[0]);
+        let slopes: Vec<f32> = values.windows(2).map(|w| w[1] - w[0]).collect();
+        for slope in &slopes {
+            assert!((slope.abs() - 4. / 128.).abs() < 1e-4, "slope {slope}");
+        }
+        // Only changes direction at the peaks, twice per cycle
+        let turns = slopes
+            .windows(2)
+            .filter(|w| w[0].signum() != w[1].signum())
+            .count();
+        assert_eq!(16, turns);
+    }
+
+    #[test]
+    fn sample_and_hold_is_held_per_cycle() {
+        let values = render(LfoShape::SampleAndHold, 1024);
+        for cycle in values.chunks(128) {
+            assert!(cycle.iter().all(|&x| x == cycle[0]));
+        }
+        let changes = values.windows(2).filter(|w| w[0] != w[1]).count();
+        assert!(changes > 0);
+    }
+
+    #[test]
+    fn noise_is_continuous() {
+        let values = render(LfoShape::Noise, 1024);
+        for w in values.windows(2) {
+            assert!((w[1] - w[0]).abs() <= 2. / 128. + 1e-4);
+        }
+    }
+}
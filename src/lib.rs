@@ -3,55 +3,405 @@
 // This software is released under the MIT License.
 // https://opensource.org/licenses/MIT
 
-#![feature(portable_simd)]
-#![feature(array_from_fn)]
-#![feature(const_for)]
+#![cfg_attr(feature = "simd", feature(portable_simd))]
+#![cfg_attr(feature = "simd", feature(array_from_fn))]
+#![cfg_attr(feature = "simd", feature(const_for))]
 
-use std::f32::consts::TAU;
 use std::{
-    simd::{f32x2, Simd},
-    sync::Arc,
+    f32::consts::{FRAC_1_SQRT_2, FRAC_PI_2},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
 };
 
-use biquad::{Biquad, BiquadParams};
+use allpass::{AllpassLine, Decorrelator};
+use atomic_float::AtomicF32;
+use biquad::{Biquad, BiquadParams, CoefficientCache};
+use crossover::Crossover;
 use editor::DelayEditor;
 use nih_plug::prelude::*;
 
+use ab::AbComparison;
 use early::Early;
+use fdn::Fdn;
+use lfo::{Lfo, LfoShape};
+use limiter::Limiter;
+use midi::MidiActions;
 use nih_plug_vizia::ViziaState;
+use oversample::{Oversampler, Oversampling};
 use pitch::PitchShifter;
-use simdmath::simd_f32tanh;
+use rand::prelude::*;
+use reverse::Reverser;
+use simd::{f32x2, LaneCount, Mask, Simd, SupportedLaneCount};
+use simdmath::{simd_f32hardclip, simd_f32softclip, simd_f32tanh, simd_f32tube};
+use state::StateVersion;
 
-use crate::delay::Delay;
+use crate::delay::{Delay, Interpolation};
 
+mod ab;
+pub mod allpass;
 pub mod biquad;
+mod crossover;
 pub mod delay;
 mod diffusion;
 mod early;
 mod editor;
-mod hadamard;
-mod householder;
+mod fdn;
+pub mod hadamard;
+pub mod householder;
+mod lfo;
+mod limiter;
+mod midi;
+pub mod mixing;
+mod oversample;
 pub mod pitch;
-mod simdmath;
+mod presets;
+mod reverse;
+pub mod simd;
+pub mod simdmath;
+mod state;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+enum DecayMode {
+    #[id = "fbck"]
+    #[name = "Feedback"]
+    Feedback,
+    #[id = "rt60"]
+    #[name = "Decay Time"]
+    DecayTime,
+}
+
+/// Current version of the saved state. Bump it whenever a change to the parameters would alter the
+/// sound of existing sessions, and add the corresponding step to [`migrate_params`].
+const STATE_VERSION: u32 = 6;
+
+/// Note length, relative to the host tempo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+enum NoteDivision {
+    #[id = "4"]
+    #[name = "4 Bars"]
+    FourBars,
+    #[id = "2"]
+    #[name = "2 Bars"]
+    TwoBars,
+    #[id = "1"]
+    #[name = "1/1"]
+    Whole,
+    #[id = "1/2"]
+    #[name = "1/2"]
+    Half,
+    #[id = "1/4d"]
+    #[name = "1/4D"]
+    DottedQuarter,
+    #[id = "1/4"]
+    #[name = "1/4"]
+    Quarter,
+    #[id = "1/4t"]
+    #[name = "1/4T"]
+    TripletQuarter,
+    #[id = "1/8d"]
+    #[name = "1/8D"]
+    DottedEighth,
+    #[id = "1/8"]
+    #[name = "1/8"]
+    Eighth,
+    #[id = "1/8t"]
+    #[name = "1/8T"]
+    TripletEighth,
+    #[id = "1/16"]
+    #[name = "1/16"]
+    Sixteenth,
+}
+
+impl NoteDivision {
+    /// Length of the note in quarter notes.
+    fn beats(self) -> f32 {
+        match self {
+            Self::FourBars => 16.,
+            Self::TwoBars => 8.,
+            Self::Whole => 4.,
+            Self::Half => 2.,
+            Self::DottedQuarter => 1.5,
+            Self::Quarter => 1.,
+            Self::TripletQuarter => 2. / 3.,
+            Self::DottedEighth => 0.75,
+            Self::Eighth => 0.5,
+            Self::TripletEighth => 1. / 3.,
+            Self::Sixteenth => 0.25,
+        }
+    }
+
+    /// Rate at which notes of this length repeat at the given tempo in BPM, in Hz.
+    fn rate(self, tempo: f64) -> f32 {
+        tempo as f32 / 60. / self.beats()
+    }
+
+    /// Length of the note at the given tempo in BPM, in seconds.
+    fn seconds(self, tempo: f64) -> f32 {
+        60. / tempo as f32 * self.beats()
+    }
+}
+
+/// Slope of the damping filters in the feedback loop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+enum DampingSlope {
+    /// One-pole filters, for a smooth classic sound.
+    #[id = "6db"]
+    #[name = "6 dB/oct"]
+    Gentle,
+    /// Resonant two-pole filters, sharper and with a slight emphasis around the cutoffs.
+    #[id = "12db"]
+    #[name = "12 dB/oct"]
+    Steep,
+}
+
+impl DampingSlope {
+    /// Highpass removing the lows below `fc`, normalized to the sample rate.
+    fn highpass<const LANES: usize>(self, fc: f32) -> BiquadParams<LANES>
+    where
+        LaneCount<LANES>: SupportedLaneCount,
+    {
+        match self {
+            Self::Gentle => BiquadParams::highpass_1p(Simd::splat(fc)),
+            Self::Steep => BiquadParams::highpass(Simd::splat(fc), Simd::splat(DAMPING_Q)),
+        }
+    }
+
+    /// Lowpass removing the highs above `fc`, normalized to the sample rate.
+    fn lowpass<const LANES: usize>(self, fc: f32) -> BiquadParams<LANES>
+    where
+        LaneCount<LANES>: SupportedLaneCount,
+    {
+        match self {
+            Self::Gentle => BiquadParams::lowpass_1p(Simd::splat(fc)),
+            Self::Steep => BiquadParams::lowpass(Simd::splat(fc), Simd::splat(DAMPING_Q)),
+        }
+    }
+}
+
+/// Signal the wet level is ducked under.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+enum DuckSource {
+    #[id = "input"]
+    Input,
+    /// The first auxiliary input, silent when the host leaves it unconnected.
+    #[id = "sidechain"]
+    Sidechain,
+}
+
+/// Routing of the signal through the reverb.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+enum ReverbMode {
+    #[id = "reverb"]
+    #[name = "Reverb"]
+    Reverb,
+    /// The modulated diffusers alone, without recirculating the signal or the late tank.
+    #[id = "chorus"]
+    #[name = "Chorus"]
+    Chorus,
+    /// The full reverb with its decay time capped to a short room.
+    #[id = "amb"]
+    #[name = "Ambience"]
+    Ambience,
+}
+
+/// Algorithm used by the diffusers in the feedback loop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+enum Diffuser {
+    #[id = "hh"]
+    #[name = "Velvet/Householder"]
+    Householder,
+    #[id = "ap"]
+    #[name = "Schroeder Allpass"]
+    Allpass,
+}
+
+/// Nonlinearity applied to the signal going around the feedback loop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+enum Saturation {
+    #[id = "none"]
+    #[name = "None"]
+    None,
+    #[id = "tanh"]
+    #[name = "Tanh"]
+    Tanh,
+    #[id = "soft"]
+    #[name = "Soft Clip"]
+    SoftClip,
+    #[id = "hard"]
+    #[name = "Hard Clip"]
+    HardClip,
+    #[id = "tube"]
+    #[name = "Tube"]
+    Tube,
+}
+
+impl Saturation {
+    /// Saturates the signal driven by `drive`, scaling it back down afterwards so that only the
+    /// amount of saturation changes with the drive, not the level of quiet signals.
+    fn process(self, x: f32x2, drive: f32) -> f32x2 {
+        let drive = Simd::splat(drive);
+        let driven = x * drive;
+        let saturated = match self {
+            Self::None => driven,
+            Self::Tanh => simd_f32tanh(driven),
+            Self::SoftClip => simd_f32softclip(driven),
+            Self::HardClip => simd_f32hardclip(driven),
+            Self::Tube => simd_f32tube(driven),
+        };
+        saturated / drive
+    }
+}
 
 #[derive(Params)]
 struct DelayParams {
     #[id = "ersize"]
     size: FloatParam,
+    #[id = "dfstages"]
+    diffusion_stages: IntParam,
+    /// Modulates the later diffusion stages deeper than the first. Off for sessions saved before
+    /// it was added.
+    #[id = "stgmod"]
+    stage_mod_scaling: BoolParam,
+    #[id = "density"]
+    density: FloatParam,
+    #[id = "dftype"]
+    diffuser: EnumParam<Diffuser>,
+    /// Gain of the allpass diffuser's stages.
+    #[id = "apgain"]
+    diffusion_gain: FloatParam,
     #[id = "fbck"]
     feedback: FloatParam,
+    #[id = "mode"]
+    mode: EnumParam<ReverbMode>,
+    #[id = "dcmode"]
+    decay_mode: EnumParam<DecayMode>,
+    #[id = "decay"]
+    decay_time: FloatParam,
+    /// Decay time of the lows relative to the mids.
+    #[id = "lowmult"]
+    low_decay: FloatParam,
+    /// Decay time of the highs relative to the mids.
+    #[id = "highmult"]
+    high_decay: FloatParam,
     #[id = "delay"]
     delay: FloatParam,
+    /// Whether the size stretches the feedback delay time along with the diffusers, see
+    /// [`Reverb::room_scale`]. Off for sessions saved before it did.
+    #[id = "sizedly"]
+    size_scales_delay: BoolParam,
+    /// Follows the host tempo with the delay division, clamped to the range of the delay time
+    /// parameter. The longer divisions reach its top at slow tempos.
+    #[id = "dlsync"]
+    delay_sync: BoolParam,
+    #[id = "dldiv"]
+    delay_division: EnumParam<NoteDivision>,
+    /// Jumps to new delay times and crossfades the read positions, instead of sliding to them.
+    #[id = "dlxfade"]
+    delay_crossfade: BoolParam,
     #[id = "mddpt"]
     mod_depth: FloatParam,
     #[id = "mdspd"]
     mod_speed: FloatParam,
+    /// Shape of the LFO modulating the main delay. The taps of the diffusion stages keep their own
+    /// free-running sines, as a random shape would need its own generator for every tap.
+    #[id = "mdshape"]
+    mod_shape: EnumParam<LfoShape>,
+    #[id = "mdsync"]
+    mod_sync: BoolParam,
+    #[id = "mddiv"]
+    mod_division: EnumParam<NoteDivision>,
+    #[id = "inlow"]
+    input_low_cut: FloatParam,
+    #[id = "inhigh"]
+    input_high_cut: FloatParam,
     #[id = "dlow"]
     damp_low: FloatParam,
     #[id = "dhigh"]
     damp_high: FloatParam,
-    // #[id = "shimr"]
+    #[id = "dslope"]
+    damp_slope: EnumParam<DampingSlope>,
+    /// Tilt of the wet signal's spectrum, darker when negative and brighter when positive.
+    #[id = "tone"]
+    tone: FloatParam,
+    #[id = "shimr"]
     pitch_amt: FloatParam,
+    #[id = "shimint"]
+    pitch_interval: FloatParam,
+    #[id = "shimfb"]
+    shimmer_feedback: BoolParam,
+    /// Cutoff of the lowpass on the pitch-shifted signal, which keeps the octaves stacked up by
+    /// the shimmer feedback from turning harsh.
+    #[id = "shimtone"]
+    shimmer_tone: FloatParam,
+    #[id = "mix"]
+    mix: FloatParam,
+    #[id = "outgain"]
+    output_gain: FloatParam,
+    /// Whether the wet signal goes through the output limiter. Off for sessions saved before it
+    /// was added.
+    #[id = "limiter"]
+    limiter: BoolParam,
+    #[id = "ceiling"]
+    ceiling: FloatParam,
+    #[id = "width"]
+    width: FloatParam,
+    #[id = "decorr"]
+    decorrelation: FloatParam,
+    /// How much of each channel of the feedback loop crosses over into the other.
+    #[id = "xfeed"]
+    cross_feedback: FloatParam,
+    /// Delay of the right channel of the feedback loop behind the left, in milliseconds.
+    #[id = "stoffset"]
+    stereo_offset: FloatParam,
+    /// Swaps the channels of the feedback loop on every pass, bouncing the echoes between sides.
+    #[id = "pingpong"]
+    ping_pong: BoolParam,
+    /// Balance between the straight and the swapped feedback in the ping-pong mode.
+    #[id = "ppamt"]
+    ping_pong_amount: FloatParam,
+    #[id = "monotank"]
+    mono_tank: BoolParam,
+    #[id = "late"]
+    late: FloatParam,
+    #[id = "duck"]
+    duck_amount: FloatParam,
+    #[id = "duckrel"]
+    duck_release: FloatParam,
+    #[id = "ducksrc"]
+    duck_source: EnumParam<DuckSource>,
+    #[id = "freeze"]
+    freeze: BoolParam,
+    #[id = "bypass"]
+    bypass: BoolParam,
+    /// Monitors the wet signal alone without touching the mix. This is not part of the sound, so
+    /// it is switched back off when a state is loaded, see [`apply_loaded_state`].
+    #[id = "wetsolo"]
+    wet_solo: BoolParam,
+    #[id = "reverse"]
+    reverse: BoolParam,
+    /// Silences the reverb when switched on, see [`Reverb::clear_state`].
+    #[id = "panic"]
+    panic: BoolParam,
+    #[id = "drive"]
+    drive: FloatParam,
+    #[id = "sat"]
+    saturation: EnumParam<Saturation>,
+    #[id = "os"]
+    oversampling: EnumParam<Oversampling>,
+    /// Seeds the randomization of the diffusers and modulation, so that the same seed always
+    /// gives the same tail.
+    #[id = "seed"]
+    seed: IntParam,
+    #[nested = "Gate"]
+    gate: GateParams,
+    /// Version of the parameters this state was saved with, see [`migrate_params`].
+    #[nested = "State"]
+    version: StateVersion,
+    /// Settings of the inactive slot of the A/B comparison, see [`ab::switch`].
+    #[nested = "AB"]
+    ab: AbComparison,
 }
 
 impl Default for DelayParams {
@@ -62,13 +412,92 @@ impl Default for DelayParams {
                 .with_string_to_value(formatters::s2v_f32_percentage())
                 .with_value_to_string(formatters::v2s_f32_percentage(2))
                 .with_smoother(SmoothingStyle::Linear(20.)),
+            // Stages being switched on start out silent, which would drop out of the tail if
+            // automated
+            diffusion_stages: IntParam::new(
+                "Diffusion Stages",
+                4,
+                IntRange::Linear {
+                    min: 1,
+                    max: early::MAX_STAGES as i32,
+                },
+            )
+            .non_automatable(),
+            stage_mod_scaling: BoolParam::new("Stage Mod Scaling", true),
+            density: FloatParam::new("Density", 0.5, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_unit("%")
+                .with_smoother(SmoothingStyle::Linear(100.0))
+                .with_string_to_value(formatters::s2v_f32_percentage())
+                .with_value_to_string(formatters::v2s_f32_percentage(2)),
+            diffuser: EnumParam::new("Diffuser", Diffuser::Householder).non_automatable(),
+            diffusion_gain: FloatParam::new(
+                "Allpass Gain",
+                ALLPASS_GAIN,
+                FloatRange::Linear {
+                    min: 0.3,
+                    max: allpass::MAX_GAIN,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(100.0))
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
             feedback: FloatParam::new("Feedback", 0.7, FloatRange::Linear { min: 0., max: 1.25 })
                 .with_unit("%")
                 .with_string_to_value(formatters::s2v_f32_percentage())
                 .with_value_to_string(formatters::v2s_f32_percentage(2)),
-            delay: FloatParam::new("Delay", 0.2, FloatRange::Linear { min: 1e-3, max: 2. })
-                .with_unit("s")
-                .with_smoother(SmoothingStyle::Linear(200.)),
+            mode: EnumParam::new("Mode", ReverbMode::Reverb),
+            decay_mode: EnumParam::new("Decay Mode", DecayMode::Feedback),
+            decay_time: FloatParam::new(
+                "Decay Time",
+                2.0,
+                FloatRange::Skewed {
+                    min: 0.1,
+                    max: 20.0,
+                    factor: FloatRange::skew_factor(-1.5),
+                },
+            )
+            .with_unit(" s")
+            .with_smoother(SmoothingStyle::Logarithmic(100.0))
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            low_decay: FloatParam::new(
+                "Low Decay",
+                1.0,
+                // Puts the neutral multiplier in the middle
+                FloatRange::Skewed {
+                    min: 0.25,
+                    max: 4.0,
+                    factor: FloatRange::skew_factor(-1.2),
+                },
+            )
+            .with_unit("x")
+            .with_smoother(SmoothingStyle::Logarithmic(100.0))
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            high_decay: FloatParam::new(
+                "High Decay",
+                1.0,
+                // Puts the neutral multiplier in the middle
+                FloatRange::Skewed {
+                    min: 0.25,
+                    max: 4.0,
+                    factor: FloatRange::skew_factor(-1.2),
+                },
+            )
+            .with_unit("x")
+            .with_smoother(SmoothingStyle::Logarithmic(100.0))
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            delay: FloatParam::new(
+                "Delay",
+                0.2,
+                FloatRange::Linear {
+                    min: 1e-3,
+                    max: MAX_DELAY,
+                },
+            )
+            .with_unit("s")
+            .with_smoother(SmoothingStyle::Linear(200.)),
+            size_scales_delay: BoolParam::new("Size Scales Delay", true),
+            delay_sync: BoolParam::new("Delay Sync", false),
+            delay_division: EnumParam::new("Delay Division", NoteDivision::Quarter),
+            delay_crossfade: BoolParam::new("Delay Crossfade", false),
             mod_depth: FloatParam::new(
                 "Mod Depth",
                 0.1,
@@ -94,6 +523,33 @@ impl Default for DelayParams {
             .with_smoother(SmoothingStyle::Exponential(150.0))
             .with_string_to_value(formatters::s2v_f32_hz_then_khz())
             .with_value_to_string(formatters::v2s_f32_hz_then_khz(2)),
+            mod_shape: EnumParam::new("Delay Mod Shape", LfoShape::Sine),
+            mod_sync: BoolParam::new("Mod Sync", false),
+            mod_division: EnumParam::new("Mod Division", NoteDivision::Whole),
+            input_low_cut: FloatParam::new(
+                "Input Low Cut",
+                20.,
+                FloatRange::Skewed {
+                    min: 20.,
+                    max: 20e3,
+                    factor: FloatRange::skew_factor(-2.5),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(100.))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz())
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(2)),
+            input_high_cut: FloatParam::new(
+                "Input High Cut",
+                20e3,
+                FloatRange::Skewed {
+                    min: 20.,
+                    max: 20e3,
+                    factor: FloatRange::skew_factor(-2.5),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(100.))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz())
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(2)),
             damp_low: FloatParam::new(
                 "Low Damping",
                 100.,
@@ -118,36 +574,751 @@ impl Default for DelayParams {
             .with_smoother(SmoothingStyle::Logarithmic(100.))
             .with_string_to_value(formatters::s2v_f32_hz_then_khz())
             .with_value_to_string(formatters::v2s_f32_hz_then_khz(2)),
+            damp_slope: EnumParam::new("Damping Slope", DampingSlope::Gentle),
+            tone: FloatParam::new(
+                "Tone",
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
             pitch_amt: FloatParam::new("Shimmer", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
                 .with_smoother(SmoothingStyle::Linear(100.0))
                 .with_string_to_value(formatters::s2v_f32_percentage())
                 .with_value_to_string(formatters::v2s_f32_percentage(2)),
+            pitch_interval: FloatParam::new(
+                "Shimmer Interval",
+                12.0,
+                FloatRange::Linear {
+                    min: -24.0,
+                    max: 24.0,
+                },
+            )
+            .with_unit(" st")
+            .with_step_size(1.0),
+            shimmer_feedback: BoolParam::new("Shimmer Feedback", true),
+            shimmer_tone: FloatParam::new(
+                "Shimmer Tone",
+                7e3,
+                FloatRange::Skewed {
+                    min: 1e3,
+                    max: SHIMMER_TONE_OPEN,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(100.))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz())
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(2)),
+            mix: FloatParam::new("Mix", 1.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_unit("%")
+                .with_smoother(SmoothingStyle::Linear(20.0))
+                .with_string_to_value(formatters::s2v_f32_percentage())
+                .with_value_to_string(formatters::v2s_f32_percentage(2)),
+            output_gain: FloatParam::new(
+                "Output Gain",
+                util::db_to_gain(0.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-60.0),
+                    max: util::db_to_gain(12.0),
+                    factor: FloatRange::gain_skew_factor(-60.0, 12.0),
+                },
+            )
+            .with_unit(" dB")
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db())
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2)),
+            limiter: BoolParam::new("Limiter", true),
+            ceiling: FloatParam::new(
+                "Ceiling",
+                util::db_to_gain(0.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-24.0),
+                    max: util::db_to_gain(0.0),
+                    factor: FloatRange::gain_skew_factor(-24.0, 0.0),
+                },
+            )
+            .with_unit(" dBFS")
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db())
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2)),
+            width: FloatParam::new("Width", 1.0, FloatRange::Linear { min: 0.0, max: 2.0 })
+                .with_unit("%")
+                .with_smoother(SmoothingStyle::Linear(20.0))
+                .with_string_to_value(formatters::s2v_f32_percentage())
+                .with_value_to_string(formatters::v2s_f32_percentage(2)),
+            decorrelation: FloatParam::new(
+                "Decorrelation",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_string_to_value(formatters::s2v_f32_percentage())
+            .with_value_to_string(formatters::v2s_f32_percentage(2)),
+            cross_feedback: FloatParam::new(
+                "Cross Feedback",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_string_to_value(formatters::s2v_f32_percentage())
+            .with_value_to_string(formatters::v2s_f32_percentage(2)),
+            ping_pong: BoolParam::new("Ping-Pong", false),
+            ping_pong_amount: FloatParam::new(
+                "Ping-Pong Amount",
+                1.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_string_to_value(formatters::s2v_f32_percentage())
+            .with_value_to_string(formatters::v2s_f32_percentage(2)),
+            stereo_offset: FloatParam::new(
+                "Stereo Offset",
+                0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: MAX_STEREO_OFFSET * 1e3,
+                },
+            )
+            .with_unit(" ms")
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+            mono_tank: BoolParam::new("Mono Tank", false),
+            late: FloatParam::new("Late Level", 0.5, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_unit("%")
+                .with_smoother(SmoothingStyle::Linear(20.0))
+                .with_string_to_value(formatters::s2v_f32_percentage())
+                .with_value_to_string(formatters::v2s_f32_percentage(2)),
+            duck_amount: FloatParam::new("Ducking", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_unit("%")
+                .with_smoother(SmoothingStyle::Linear(20.0))
+                .with_string_to_value(formatters::s2v_f32_percentage())
+                .with_value_to_string(formatters::v2s_f32_percentage(2)),
+            duck_release: FloatParam::new(
+                "Ducking Release",
+                250.0,
+                FloatRange::Skewed {
+                    min: 10.0,
+                    max: 2000.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(0)),
+            duck_source: EnumParam::new("Ducking Source", DuckSource::Input),
+            freeze: BoolParam::new("Freeze", false).non_automatable(),
+            bypass: BoolParam::new("Bypass", false).non_automatable(),
+            wet_solo: BoolParam::new("Wet Solo", false).non_automatable(),
+            // Changes the latency, which hosts do not expect to happen during playback
+            reverse: BoolParam::new("Reverse", false).non_automatable(),
+            // Only clears on being switched on, the editor holds it on while its button is pressed
+            panic: BoolParam::new("Panic", false).non_automatable(),
+            drive: FloatParam::new(
+                "Drive",
+                util::db_to_gain(0.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(0.0),
+                    max: util::db_to_gain(24.0),
+                    factor: FloatRange::gain_skew_factor(0.0, 24.0),
+                },
+            )
+            .with_unit(" dB")
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_string_to_value(formatters::s2v_f32_gain_to_db())
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2)),
+            saturation: EnumParam::new("Saturation", Saturation::Tanh),
+            // Changes the latency as well
+            oversampling: EnumParam::new("Oversampling", Oversampling::Off).non_automatable(),
+            // Rebuilds the diffusers, which would drop out of the tail if automated
+            seed: IntParam::new("Seed", 0, IntRange::Linear { min: 0, max: 9999 })
+                .non_automatable(),
+            gate: GateParams::default(),
+            version: StateVersion::default(),
+            ab: AbComparison::default(),
+        }
+    }
+}
+
+impl DelayParams {
+    /// Jumps every smoothed parameter to its current value. The smoothers work out their step
+    /// sizes from the sample rate when a ramp starts, so a ramp still going when the sample rate
+    /// changes would otherwise finish at the wrong speed.
+    fn reset_smoothers(&self, samplerate: f32) {
+        // Goes through the whole parameter map so that nested and newly added parameters are not
+        // missed
+        for (_, param, _) in self.param_map() {
+            // SAFETY: the parameters are alive, and the host is not processing audio or
+            // automating them while the sample rate changes
+            unsafe { param.update_smoother(samplerate, true) };
+        }
+    }
+}
+
+/// Cuts the reverb tail off once the input has been quiet for a while.
+#[derive(Params)]
+struct GateParams {
+    #[id = "gate"]
+    enabled: BoolParam,
+    #[id = "gtthr"]
+    threshold: FloatParam,
+    #[id = "gthold"]
+    hold: FloatParam,
+}
+
+impl Default for GateParams {
+    fn default() -> Self {
+        Self {
+            enabled: BoolParam::new("Gate", false),
+            threshold: FloatParam::new(
+                "Gate Threshold",
+                util::db_to_gain(-30.0),
+                FloatRange::Skewed {
+                    min: util::db_to_gain(-60.0),
+                    max: util::db_to_gain(0.0),
+                    factor: FloatRange::gain_skew_factor(-60.0, 0.0),
+                },
+            )
+            .with_unit(" dB")
+            .with_string_to_value(formatters::s2v_f32_gain_to_db())
+            .with_value_to_string(formatters::v2s_f32_gain_to_db(2)),
+            hold: FloatParam::new(
+                "Gate Hold",
+                150.0,
+                FloatRange::Skewed {
+                    min: 10.0,
+                    max: 1000.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(0)),
+        }
+    }
+}
+
+/// Channel layouts the plugin can run in. Processing is always done in stereo; mono inputs are
+/// duplicated to both sides and mono outputs receive the sum of both sides.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChannelLayout {
+    Mono,
+    MonoToStereo,
+    Stereo,
+}
+
+impl ChannelLayout {
+    fn from_bus_config(config: &BusConfig) -> Option<Self> {
+        match (config.num_input_channels, config.num_output_channels) {
+            (1, 1) => Some(Self::Mono),
+            (1, 2) => Some(Self::MonoToStereo),
+            (2, 2) => Some(Self::Stereo),
+            _ => None,
+        }
+    }
+
+    fn upmix(self, frame: [f32; 2]) -> f32x2 {
+        match self {
+            Self::Mono | Self::MonoToStereo => f32x2::splat(frame[0]),
+            Self::Stereo => f32x2::from_array(frame),
+        }
+    }
+
+    fn downmix(self, x: f32x2) -> [f32; 2] {
+        match self {
+            Self::Mono => [x.reduce_sum() / 2., 0.],
+            Self::MonoToStereo | Self::Stereo => x.to_array(),
+        }
+    }
+}
+
+/// Damping filter cutoffs used by the audio thread, shared with the editor to draw the damping
+/// curve.
+#[derive(Debug, Default)]
+struct DampingCutoffs {
+    samplerate: AtomicF32,
+    low: AtomicF32,
+    high: AtomicF32,
+    /// Whether the filters are [`DampingSlope::Steep`].
+    steep: AtomicBool,
+}
+
+impl DampingCutoffs {
+    fn store(&self, samplerate: f32, (low, high): (f32, f32), slope: DampingSlope) {
+        self.samplerate.store(samplerate, Ordering::Relaxed);
+        self.low.store(low, Ordering::Relaxed);
+        self.high.store(high, Ordering::Relaxed);
+        self.steep
+            .store(slope == DampingSlope::Steep, Ordering::Relaxed);
+    }
+
+    /// Combined magnitude response of the damping filters at the given frequency in Hz.
+    fn response(&self, freq: f32) -> f32 {
+        let samplerate = self.samplerate.load(Ordering::Relaxed);
+        if samplerate <= 0. {
+            return 1.;
         }
+        let low = self.low.load(Ordering::Relaxed) / samplerate;
+        let high = self.high.load(Ordering::Relaxed) / samplerate;
+        let slope = if self.steep.load(Ordering::Relaxed) {
+            DampingSlope::Steep
+        } else {
+            DampingSlope::Gentle
+        };
+        let (low, _) = slope.highpass::<1>(low).response(freq / samplerate);
+        let (high, _) = slope.lowpass::<1>(high).response(freq / samplerate);
+        low * high
     }
 }
 
-struct Reverb {
+/// Frequency splitting the low band of the feedback from the mids, in Hz.
+const LOW_CROSSOVER: f32 = 250.;
+/// Frequency splitting the high band of the feedback from the mids, in Hz.
+const HIGH_CROSSOVER: f32 = 4000.;
+/// Quality factor of the [`DampingSlope::Steep`] filters, just above a flat Butterworth response.
+const DAMPING_Q: f32 = 1.;
+/// Phase offset of the right channel's modulation relative to the left, as a fraction of a cycle.
+const STEREO_MOD_PHASE: f32 = 0.25;
+/// Maximum excursion of the modulated feedback delay, in seconds.
+const DELAY_MOD_DEPTH: f32 = 15e-3;
+/// Scale of the feedback delay time at the smallest size. The scale is unity at the default size.
+const MIN_ROOM_SCALE: f32 = 0.25;
+/// Scale of the feedback delay time at the largest size.
+const MAX_ROOM_SCALE: f32 = 2. - MIN_ROOM_SCALE;
+/// Window of the shimmer pitch shifter, in seconds. See [`PitchShifter::new`] for the trade-offs.
+const PITCH_WINDOW: f32 = 50e-3;
+/// Longest delay time that can be set, in seconds, before the room size scaling.
+const MAX_DELAY: f32 = 2.;
+/// Length of the feedback delay line past the longest scaled delay time, in seconds. This covers
+/// the delay modulation and the samples around the read position used by the interpolation.
+pub const DELAY_HEADROOM: f32 = 20e-3;
+/// Longest offset of the right channel's feedback delay behind the left, in seconds.
+const MAX_STEREO_OFFSET: f32 = 30e-3;
+/// Top of the range of the shimmer tone, in Hz, where the shimmer is left unfiltered.
+const SHIMMER_TONE_OPEN: f32 = 20e3;
+
+/// Duration of the crossfade between the old and new read positions when the delay time jumps, in
+/// seconds.
+const RETIME_FADE: f32 = 50e-3;
+/// Smallest change in the free delay time that jumps and crossfades when the delay crossfade is
+/// on, in seconds. Smaller changes still slide, as the repitching they cause stays subtle.
+const RETIME_THRESHOLD: f32 = 5e-3;
+/// Time it takes for the freeze mode to fully engage or release, in seconds.
+const FREEZE_FADE: f32 = 20e-3;
+/// Duration of the crossfade between the processed and dry signals when toggling the bypass, in
+/// seconds.
+const BYPASS_FADE: f32 = 10e-3;
+/// Time it takes for the peak meter to fall by 12 dB, in seconds.
+const PEAK_METER_DECAY: f32 = 150e-3;
+/// Default feedback gain of the allpass diffusers.
+const ALLPASS_GAIN: f32 = 0.6;
+/// Length of the segments the wet signal is reversed in, in seconds.
+const REVERSE_LENGTH: f32 = 400e-3;
+/// Upper bound on the latency of the oversampler, in samples.
+const MAX_OVERSAMPLING_LATENCY: usize = 32;
+/// Fade applied at both ends of each reversed segment, in seconds.
+const REVERSE_FADE: f32 = 5e-3;
+/// Duration of the crossfade when toggling the reverse mode, in seconds.
+const REVERSE_CROSSFADE: f32 = 10e-3;
+/// Attack time of the envelope follower ducking the wet signal, in seconds.
+const DUCK_ATTACK: f32 = 5e-3;
+/// Release time of the envelope follower driving the gate, in seconds.
+const GATE_RELEASE: f32 = 10e-3;
+/// Time it takes for the gate to open, in seconds.
+const GATE_ATTACK: f32 = 1e-3;
+/// Time it takes for the gate to close once the hold time has elapsed, in seconds. Short enough to
+/// sound abrupt, long enough not to click.
+const GATE_FADE: f32 = 5e-3;
+/// Peak level the recirculating signal is held under by the feedback limiter.
+const LOOP_CEILING: f32 = 0.5;
+/// Release time of the feedback limiter, in seconds.
+const LOOP_RELEASE: f32 = 100e-3;
+/// Cutoff of the DC blocker on the wet signal, in Hz.
+const DC_BLOCKER_CUTOFF: f32 = 5.;
+/// Time constant of the filters gliding towards their cached coefficients, in seconds.
+const COEFFS_GLIDE: f32 = 2e-3;
+/// Largest number of samples processed with a single pull from the parameter smoothers.
+const MAX_BLOCK_SIZE: usize = 64;
+/// Frequency the tone control tilts the wet signal around, in Hz.
+const TILT_PIVOT: f32 = 1000.;
+/// Gain of each shelf of the tone control at either end of its range, in dB.
+const TILT_GAIN: f32 = 6.;
+/// Longest decay time of the ambience mode, in seconds.
+const AMBIENCE_DECAY: f32 = 0.6;
+
+/// Smoothed parameter values for a block of samples, pulled from the smoothers all at once
+/// instead of one call per parameter on every sample.
+struct ParamBlock {
+    feedback: [f32; MAX_BLOCK_SIZE],
+    decay_time: [f32; MAX_BLOCK_SIZE],
+    low_decay: [f32; MAX_BLOCK_SIZE],
+    high_decay: [f32; MAX_BLOCK_SIZE],
+    size: [f32; MAX_BLOCK_SIZE],
+    drive: [f32; MAX_BLOCK_SIZE],
+    density: [f32; MAX_BLOCK_SIZE],
+    diffusion_gain: [f32; MAX_BLOCK_SIZE],
+    mod_depth: [f32; MAX_BLOCK_SIZE],
+    mod_speed: [f32; MAX_BLOCK_SIZE],
+    pitch_amt: [f32; MAX_BLOCK_SIZE],
+    shimmer_tone: [f32; MAX_BLOCK_SIZE],
+    mix: [f32; MAX_BLOCK_SIZE],
+    output_gain: [f32; MAX_BLOCK_SIZE],
+    ceiling: [f32; MAX_BLOCK_SIZE],
+    width: [f32; MAX_BLOCK_SIZE],
+    decorrelation: [f32; MAX_BLOCK_SIZE],
+    cross_feedback: [f32; MAX_BLOCK_SIZE],
+    stereo_offset: [f32; MAX_BLOCK_SIZE],
+    ping_pong_amount: [f32; MAX_BLOCK_SIZE],
+    delay: [f32; MAX_BLOCK_SIZE],
+    late: [f32; MAX_BLOCK_SIZE],
+    duck_amount: [f32; MAX_BLOCK_SIZE],
+    input_low_cut: [f32; MAX_BLOCK_SIZE],
+    input_high_cut: [f32; MAX_BLOCK_SIZE],
+    damp_low: [f32; MAX_BLOCK_SIZE],
+    damp_high: [f32; MAX_BLOCK_SIZE],
+    tone: [f32; MAX_BLOCK_SIZE],
+}
+
+impl ParamBlock {
+    fn new() -> Self {
+        Self {
+            feedback: [0.; MAX_BLOCK_SIZE],
+            decay_time: [0.; MAX_BLOCK_SIZE],
+            low_decay: [0.; MAX_BLOCK_SIZE],
+            high_decay: [0.; MAX_BLOCK_SIZE],
+            size: [0.; MAX_BLOCK_SIZE],
+            drive: [0.; MAX_BLOCK_SIZE],
+            density: [0.; MAX_BLOCK_SIZE],
+            diffusion_gain: [0.; MAX_BLOCK_SIZE],
+            mod_depth: [0.; MAX_BLOCK_SIZE],
+            mod_speed: [0.; MAX_BLOCK_SIZE],
+            pitch_amt: [0.; MAX_BLOCK_SIZE],
+            shimmer_tone: [0.; MAX_BLOCK_SIZE],
+            mix: [0.; MAX_BLOCK_SIZE],
+            output_gain: [0.; MAX_BLOCK_SIZE],
+            ceiling: [0.; MAX_BLOCK_SIZE],
+            width: [0.; MAX_BLOCK_SIZE],
+            decorrelation: [0.; MAX_BLOCK_SIZE],
+            cross_feedback: [0.; MAX_BLOCK_SIZE],
+            stereo_offset: [0.; MAX_BLOCK_SIZE],
+            ping_pong_amount: [0.; MAX_BLOCK_SIZE],
+            delay: [0.; MAX_BLOCK_SIZE],
+            late: [0.; MAX_BLOCK_SIZE],
+            duck_amount: [0.; MAX_BLOCK_SIZE],
+            input_low_cut: [0.; MAX_BLOCK_SIZE],
+            input_high_cut: [0.; MAX_BLOCK_SIZE],
+            damp_low: [0.; MAX_BLOCK_SIZE],
+            damp_high: [0.; MAX_BLOCK_SIZE],
+            tone: [0.; MAX_BLOCK_SIZE],
+        }
+    }
+
+    /// Advances the smoothers by `len` samples, at most [`MAX_BLOCK_SIZE`].
+    fn fill(&mut self, params: &DelayParams, len: usize) {
+        params.feedback.smoothed.next_block(&mut self.feedback, len);
+        params
+            .decay_time
+            .smoothed
+            .next_block(&mut self.decay_time, len);
+        params
+            .low_decay
+            .smoothed
+            .next_block(&mut self.low_decay, len);
+        params
+            .high_decay
+            .smoothed
+            .next_block(&mut self.high_decay, len);
+        params.size.smoothed.next_block(&mut self.size, len);
+        params.drive.smoothed.next_block(&mut self.drive, len);
+        params.density.smoothed.next_block(&mut self.density, len);
+        params
+            .diffusion_gain
+            .smoothed
+            .next_block(&mut self.diffusion_gain, len);
+        params
+            .mod_depth
+            .smoothed
+            .next_block(&mut self.mod_depth, len);
+        params
+            .mod_speed
+            .smoothed
+            .next_block(&mut self.mod_speed, len);
+        params
+            .pitch_amt
+            .smoothed
+            .next_block(&mut self.pitch_amt, len);
+        params
+            .shimmer_tone
+            .smoothed
+            .next_block(&mut self.shimmer_tone, len);
+        params.mix.smoothed.next_block(&mut self.mix, len);
+        params
+            .output_gain
+            .smoothed
+            .next_block(&mut self.output_gain, len);
+        params.ceiling.smoothed.next_block(&mut self.ceiling, len);
+        params.width.smoothed.next_block(&mut self.width, len);
+        params
+            .decorrelation
+            .smoothed
+            .next_block(&mut self.decorrelation, len);
+        params
+            .cross_feedback
+            .smoothed
+            .next_block(&mut self.cross_feedback, len);
+        params
+            .stereo_offset
+            .smoothed
+            .next_block(&mut self.stereo_offset, len);
+        params
+            .ping_pong_amount
+            .smoothed
+            .next_block(&mut self.ping_pong_amount, len);
+        params.delay.smoothed.next_block(&mut self.delay, len);
+        params.late.smoothed.next_block(&mut self.late, len);
+        params
+            .duck_amount
+            .smoothed
+            .next_block(&mut self.duck_amount, len);
+        params
+            .input_low_cut
+            .smoothed
+            .next_block(&mut self.input_low_cut, len);
+        params
+            .input_high_cut
+            .smoothed
+            .next_block(&mut self.input_high_cut, len);
+        params.damp_low.smoothed.next_block(&mut self.damp_low, len);
+        params
+            .damp_high
+            .smoothed
+            .next_block(&mut self.damp_high, len);
+        params.tone.smoothed.next_block(&mut self.tone, len);
+    }
+}
+
+/// Parameter values for a single frame of [`Reverb::next_sample`], once the smoothers, the tempo
+/// sync and the reverb mode have been applied.
+#[derive(Debug, Clone, Copy)]
+struct FrameParams {
+    size: f32,
+    feedback: f32,
+    /// Decay time of the late reverberation, in seconds.
+    rt60: f32,
+    /// Feedback delay time of each channel, in seconds.
+    delay: f32x2,
+    mod_depth: f32,
+    pitch_amt: f32,
+    pitch_ratio: f32,
+    shimmer_feedback: bool,
+    mix: f32,
+    output_gain: f32,
+    width: f32,
+    mono_tank: bool,
+    late: f32,
+    duck_amount: f32,
+    duck_release: f32,
+}
+
+pub struct Reverb {
     params: Arc<DelayParams>,
+    block: ParamBlock,
+    samplerate: f32,
     editor_state: Arc<ViziaState>,
+    /// Peak level of the wet signal, shown in the editor.
+    peak_meter: Arc<AtomicF32>,
+    damping_cutoffs: Arc<DampingCutoffs>,
     diffusion: Early<4>,
+    allpass: AllpassLine<4, 2>,
+    diffuser: Diffuser,
     delay: Delay<f32x2>,
+    /// Filters on the signal going into the tank; the dry signal is left untouched.
+    input_low: Biquad<2>,
+    input_high: Biquad<2>,
+    input_low_coeffs: CoefficientCache<2>,
+    input_high_coeffs: CoefficientCache<2>,
     damp_low: Biquad<2>,
     damp_high: Biquad<2>,
+    damp_low_coeffs: CoefficientCache<2>,
+    damp_high_coeffs: CoefficientCache<2>,
+    damp_slope: DampingSlope,
+    /// Splits the feedback into bands that each decay at their own rate.
+    crossover: Crossover,
+    /// Decay time multipliers of the low and high bands.
+    band_decay: (f32, f32),
+    /// Feedback gains of the low and high bands, see [`Self::band_feedback`].
+    band_gains: (f32, f32),
+    /// Feedback amount and band decay multipliers `band_gains` were worked out for.
+    band_gains_for: (f32, (f32, f32)),
+    /// Removes the DC offset the saturation and feedback can build up in the wet signal.
+    dc_blocker: Biquad<2>,
+    /// Complementary shelves tilting the spectrum of the wet signal around [`TILT_PIVOT`].
+    tilt_low: Biquad<2>,
+    tilt_high: Biquad<2>,
+    tilt_low_coeffs: CoefficientCache<2>,
+    tilt_high_coeffs: CoefficientCache<2>,
+    /// Amount the input, damping, tilt and shimmer filters move towards their coefficients on each
+    /// sample.
+    coeffs_glide: f32,
+    /// Smoothed damping cutoffs in Hz, shown in the editor.
+    damping: (f32, f32),
     pitch: PitchShifter<2>,
-    phase: f32,
+    /// Lowpass on the output of the pitch shifter.
+    shimmer_filter: Biquad<2>,
+    shimmer_coeffs: CoefficientCache<2>,
+    /// Whether the shimmer tone is at the top of its range, leaving the shimmer unfiltered.
+    shimmer_open: bool,
+    /// Runs the saturation in the feedback loop at a higher rate.
+    oversampler: Oversampler,
+    saturation: Saturation,
+    /// Gain applied before the saturation and removed after it.
+    drive: f32,
+    /// Runs the channels of the wet signal through different allpass chains.
+    decorrelator: Decorrelator,
+    /// Amount of the decorrelated side signal in the wet signal.
+    decorrelation: f32,
+    /// Amount of each channel fed back into the other, see [`cross_feed`].
+    cross_feedback: f32,
+    /// Delay of the right channel's read of the feedback delay behind the left, in seconds.
+    stereo_offset: f32,
+    /// Amount of the feedback swapped across the channels on each pass, see [`ping_pong`].
+    ping_pong: f32,
+    late: Fdn,
+    reverser: Reverser,
+    /// Catches the peaks of the wet signal before they reach the host.
+    limiter: Limiter,
+    /// Peak level the wet signal is limited to.
+    ceiling: f32,
+    /// Dry signal delayed by the latency of the reverse mode.
+    dry_delay: Delay<f32x2>,
+    /// Dry signal of the last sample, lined up with the wet signal, that the bypass fades to.
+    dry: f32x2,
+    layout: ChannelLayout,
+    lfo: [Lfo; 2],
+    /// Seed the diffusers and LFOs were built from.
+    seed: i32,
+    /// Delay time before modulation, in seconds.
+    base_delay: f32,
+    /// Whether the delay time followed the host tempo on the previous sample.
+    delay_synced: bool,
+    /// Offset from the current read position to the one being faded out, in seconds.
+    retime_offset: f32,
+    /// Amount of the previous read position still in the mix, going from 1 to 0.
+    retime: f32,
+    freeze: f32,
+    bypass: f32,
+    reverse: f32,
+    /// Crossfade of the dry signal to the longer delay of the reverse mode. This only follows the
+    /// reverse parameter, so that holding the reverse mode from MIDI does not change the latency in
+    /// the middle of playback; the swell then trails its transient instead of leading into it.
+    reverse_latency: f32,
+    /// Freeze and reverse modes held from MIDI notes and pedals.
+    midi: MidiActions,
+    /// Latency last reported to the host, in samples.
+    latency: u32,
+    /// Envelope of the dry signal, ducking the wet signal.
+    duck_envelope: f32,
+    /// Sample of the sidechain input driving the ducking in place of the dry signal, if any.
+    sidechain: Option<f32x2>,
+    /// Gain applied to the wet signal by the gate.
+    gate: f32,
+    /// Envelope of the input, compared against the gate threshold.
+    gate_envelope: f32,
+    /// Time left before the gate closes, in samples.
+    gate_hold: f32,
+    loop_gain: f32,
+    peak: f32,
+    /// Value of the panic parameter on the previous block, so that it only clears on a change.
+    panic: bool,
 }
 
 impl Reverb {
     fn new_with_params(params: Arc<DelayParams>, samplerate: f32) -> Self {
+        let seed = params.seed.value;
+        let mut rng = StdRng::seed_from_u64(seed as u64);
+        let diffusion = Early::with_rng(samplerate, &mut rng);
+        let lfo = Self::seeded_lfos(&mut rng);
         Self {
             params,
+            block: ParamBlock::new(),
+            samplerate,
             editor_state: DelayEditor::default_state(),
-            diffusion: Early::new(samplerate),
-            delay: Delay::new(samplerate as usize * 2),
+            peak_meter: Arc::default(),
+            damping_cutoffs: Arc::default(),
+            diffusion,
+            allpass: AllpassLine::new(samplerate, ALLPASS_GAIN),
+            diffuser: Diffuser::Householder,
+            delay: Delay::new(max_delay_samples(samplerate)),
+            input_low: Biquad::default(),
+            input_high: Biquad::default(),
+            input_low_coeffs: CoefficientCache::default(),
+            input_high_coeffs: CoefficientCache::default(),
             damp_low: Biquad::default(),
             damp_high: Biquad::default(),
-            pitch: PitchShifter::new(f32::ceil(300.0 * samplerate) as _),
-            phase: 0.,
+            damp_low_coeffs: CoefficientCache::default(),
+            damp_high_coeffs: CoefficientCache::default(),
+            damp_slope: DampingSlope::Gentle,
+            crossover: Crossover::new(LOW_CROSSOVER / samplerate, HIGH_CROSSOVER / samplerate),
+            band_decay: (1., 1.),
+            band_gains: (1., 1.),
+            // Never equal to an actual feedback amount, so that the gains get worked out first
+            band_gains_for: (f32::NAN, (1., 1.)),
+            dc_blocker: Biquad::new(BiquadParams::highpass_1p(Simd::splat(
+                DC_BLOCKER_CUTOFF / samplerate,
+            ))),
+            tilt_low: Biquad::default(),
+            tilt_high: Biquad::default(),
+            tilt_low_coeffs: CoefficientCache::default(),
+            tilt_high_coeffs: CoefficientCache::default(),
+            coeffs_glide: 1. - f32::exp(-1. / (COEFFS_GLIDE * samplerate)),
+            damping: (0., 0.),
+            pitch: PitchShifter::new(f32::ceil(PITCH_WINDOW * samplerate) as _),
+            shimmer_filter: Biquad::default(),
+            shimmer_coeffs: CoefficientCache::default(),
+            shimmer_open: false,
+            oversampler: Oversampler::new(),
+            saturation: Saturation::Tanh,
+            drive: 1.,
+            decorrelator: Decorrelator::new(samplerate),
+            decorrelation: 0.,
+            cross_feedback: 0.,
+            stereo_offset: 0.,
+            ping_pong: 0.,
+            late: Fdn::new(samplerate),
+            reverser: Reverser::new(
+                (REVERSE_LENGTH * samplerate) as usize,
+                (REVERSE_FADE * samplerate) as usize,
+            ),
+            limiter: Limiter::new(samplerate),
+            ceiling: 1.,
+            dry_delay: Delay::new(
+                (REVERSE_LENGTH * samplerate) as usize + MAX_OVERSAMPLING_LATENCY,
+            ),
+            dry: f32x2::splat(0.),
+            layout: ChannelLayout::Stereo,
+            lfo,
+            seed,
+            base_delay: 0.,
+            delay_synced: false,
+            retime_offset: 0.,
+            retime: 0.,
+            freeze: 0.,
+            bypass: 0.,
+            reverse: 0.,
+            reverse_latency: 0.,
+            midi: MidiActions::default(),
+            latency: 0,
+            duck_envelope: 0.,
+            sidechain: None,
+            gate: 1.,
+            gate_envelope: 0.,
+            gate_hold: 0.,
+            loop_gain: 1.,
+            peak: 0.,
+            panic: false,
         }
     }
 
@@ -155,126 +1326,2401 @@ impl Reverb {
         Self::new_with_params(Arc::default(), samplerate)
     }
 
-    fn next_sample(
-        &mut self,
-        samplerate: f32,
-        size: f32,
-        feedback: f32,
-        delay: f32,
-        mod_depth: f32,
-        pitch_amt: f32,
-        sample: Simd<f32, 2>,
-    ) -> Simd<f32, 2> {
-        let delayed = sample
-            + self
-                .delay
-                .tap((delay * samplerate).max(1.).min(samplerate - 1.))
-                * Simd::splat(feedback);
-        let delayed = self.damp_low.next_sample(delayed);
-        let delayed = self.damp_high.next_sample(delayed);
-        let diffuse_input =
-            Simd::gather_or_default(delayed.as_array(), Simd::from_array([0, 1, 0, 1]));
-        let diffused = self.diffusion.next_sample(size, mod_depth, diffuse_input);
-        let diffused = f32x2::gather_or_default(diffused.as_array(), Simd::from_array([0, 1]));
-        let shifted = self.pitch.next_sample(samplerate, 2., diffused);
-        let diffused = diffused * Simd::splat(1.0 - pitch_amt) + shifted * Simd::splat(pitch_amt);
-        let diffused = simd_f32tanh(diffused);
-        self.delay.push_next(diffused);
-        diffused
+    fn next_sample(&mut self, samplerate: f32, frame: &FrameParams, sample: f32x2) -> f32x2 {
+        let FrameParams {
+            size,
+            feedback,
+            rt60,
+            delay,
+            mod_depth,
+            pitch_amt,
+            pitch_ratio,
+            shimmer_feedback,
+            mix,
+            output_gain,
+            width,
+            mono_tank,
+            late,
+            duck_amount,
+            duck_release,
+        } = *frame;
+        let freeze = Simd::splat(self.freeze);
+        let feedback = feedback + (1. - feedback) * self.freeze;
+        let tail = self.read_delay(samplerate, size, delay);
+        let limit = feedback > 1.;
+        let feedback = self.band_feedback(tail, feedback);
+        let input = self
+            .input_high
+            .next_sample(self.input_low.next_sample(sample));
+        let input = if mono_tank {
+            Simd::splat(input.reduce_sum() / 2.)
+        } else {
+            input
+        };
+        let delayed =
+            input * Simd::splat(1. - self.freeze) + self.limit(samplerate, limit, feedback);
+        let damped = self.damp_low.next_sample(delayed);
+        let damped = self.damp_high.next_sample(damped);
+        let delayed = damped + (delayed - damped) * freeze;
+        let diffused = match self.diffuser {
+            Diffuser::Householder => {
+                // Each channel goes into two lanes, scaled so that the four carry the energy of
+                // the stereo input; the mixing matrices are orthogonal, so the two lanes kept can
+                // never carry more than that, even when the copies add up coherently
+                let diffuse_input =
+                    Simd::gather_or_default(delayed.as_array(), Simd::from_array([0, 1, 0, 1]))
+                        * Simd::splat(FRAC_1_SQRT_2);
+                let diffused = self.diffusion.next_sample(size, mod_depth, diffuse_input);
+                f32x2::gather_or_default(diffused.as_array(), Simd::from_array([0, 1]))
+            }
+            Diffuser::Allpass => self.allpass.next_sample(size, delayed),
+        };
+        // The diffusers spread the stereo signal over more lanes than are fed back, which is not
+        // energy-preserving; when frozen, the tank recirculates the delay line on its own instead
+        let diffused = diffused + (delayed - diffused) * freeze;
+        let shifted = self.pitch.next_sample(samplerate, pitch_ratio, diffused);
+        // The filter keeps running while bypassed, so that it picks up without a jump
+        let filtered = self.shimmer_filter.next_sample(shifted);
+        let shifted = if self.shimmer_open { shifted } else { filtered };
+        let shimmer =
+            |x: f32x2| x * Simd::splat(1.0 - pitch_amt) + shifted * Simd::splat(pitch_amt);
+        // Fed back, the shimmer is shifted again on each pass and stacks up intervals; otherwise
+        // it is only a parallel layer on the output
+        let looped = if shimmer_feedback {
+            shimmer(diffused)
+        } else {
+            diffused
+        };
+        let (saturation, drive) = (self.saturation, self.drive);
+        let saturated = self
+            .oversampler
+            .process(looped, |x| saturation.process(x, drive));
+        let looped = saturated + (looped - saturated) * freeze;
+        let crossed = cross_feed(looped, self.cross_feedback);
+        self.delay.push_next(ping_pong(crossed, self.ping_pong));
+        let diffused = if shimmer_feedback {
+            looped
+        } else {
+            shimmer(looped)
+        };
+        let tail = self.late.next_sample(
+            size,
+            rt60 / (1. - self.freeze),
+            diffused * Simd::splat(1. - self.freeze),
+        );
+        let wet = self
+            .dc_blocker
+            .next_sample(diffused + tail * Simd::splat(late));
+        let wet = self.tilt_high.next_sample(self.tilt_low.next_sample(wet));
+        // The lanes of the tank still differ in their delays and modulation; folding them back
+        // down keeps the tail centered. That difference is the only stereo information a mono tank
+        // has, widths past 100% bring it back as a spread around the center.
+        let (wet, width) = if mono_tank {
+            (stereo_width(wet, (width - 1.).max(0.)), 1.)
+        } else {
+            (wet, width)
+        };
+        let wet = self.decorrelator.next_sample(self.decorrelation, wet);
+        let duck = self.duck(
+            samplerate,
+            self.sidechain.unwrap_or(sample),
+            duck_amount,
+            duck_release,
+        );
+        let wet = stereo_width(wet, width) * Simd::splat(output_gain * self.gate * duck);
+        let reverse = Simd::splat(self.reverse);
+        let wet = wet + (self.reverser.next_sample(wet) - wet) * reverse;
+        let wet = if self.params.limiter.value {
+            self.limiter.next_sample(self.ceiling, wet)
+        } else {
+            wet
+        };
+        self.peak = self.peak.max(wet.abs().reduce_max());
+        let dry = self.compensate_latency(sample);
+        self.dry = dry;
+        let mix = if self.params.wet_solo.value { 1.0 } else { mix };
+        dry * Simd::splat(1.0 - mix) + wet * Simd::splat(mix)
     }
 
-    fn tick_phase(&mut self, samplerate: f32, mod_speed: f32) {
-        self.phase += mod_speed / samplerate;
-        if self.phase > 1. {
-            self.phase -= 1.;
+    /// Rebuilds all the processing state at the given sample rate, keeping the parameters and the
+    /// handles shared with the editor.
+    fn reset_state(&mut self, samplerate: f32) {
+        let editor_state = self.editor_state.clone();
+        let peak_meter = self.peak_meter.clone();
+        let damping_cutoffs = self.damping_cutoffs.clone();
+        let layout = self.layout;
+        *self = Self::new_with_params(self.params.clone(), samplerate);
+        // The editor may already be open and holding on to these
+        self.editor_state = editor_state;
+        self.peak_meter = peak_meter;
+        self.damping_cutoffs = damping_cutoffs;
+        self.layout = layout;
+    }
+
+    /// Rebuilds the processing state if the sample rate changed, as the buffers are sized from it,
+    /// and settles the parameter ramps timed for the old rate. The state is kept as it is
+    /// otherwise.
+    fn set_samplerate(&mut self, samplerate: f32) {
+        if samplerate != self.samplerate {
+            self.reset_state(samplerate);
+            self.params.reset_smoothers(samplerate);
         }
     }
-}
 
-impl Default for Reverb {
-    fn default() -> Self {
-        Self::new(44100.)
+    /// Silences every delay line and filter in place, cutting the tail off immediately. Unlike
+    /// [`Self::reset_state`], this does not allocate and the parameter ramps are left as they are.
+    fn clear_state(&mut self) {
+        self.diffusion.reset();
+        self.allpass.reset();
+        self.delay.reset();
+        for filter in [
+            &mut self.input_low,
+            &mut self.input_high,
+            &mut self.damp_low,
+            &mut self.damp_high,
+            &mut self.dc_blocker,
+            &mut self.tilt_low,
+            &mut self.tilt_high,
+            &mut self.shimmer_filter,
+        ] {
+            filter.reset();
+        }
+        self.crossover.reset();
+        self.pitch.reset();
+        self.oversampler.reset();
+        self.late.reset();
+        self.reverser.reset();
+        self.limiter.reset();
+        self.dry_delay.reset();
+        self.dry = f32x2::splat(0.);
+        self.decorrelator.reset();
+        self.retime = 0.;
+        self.duck_envelope = 0.;
+        self.gate_envelope = 0.;
+        self.gate_hold = 0.;
+        self.loop_gain = 1.;
+        self.peak = 0.;
     }
-}
 
-impl Plugin for Reverb {
-    const NAME: &'static str = "NIH Reverb";
-    const VENDOR: &'static str = env!("CARGO_PKG_AUTHORS");
-    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
-    const EMAIL: &'static str = "N/A";
-    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+    /// Renders the response of the full processing chain to a unit impulse, at the current sample
+    /// rate and parameter values. The reverb is cleared first so that the response starts from
+    /// silence.
+    pub fn render_impulse_response(&mut self, len_samples: usize) -> Vec<[f32; 2]> {
+        self.reset_state(self.samplerate);
+        let mut impulse = vec![[0.; 2]; len_samples];
+        if let Some(first) = impulse.first_mut() {
+            *first = [1.; 2];
+        }
+        self.process_slice(&impulse)
+    }
 
-    fn params(&self) -> Arc<dyn Params> {
-        self.params.clone()
+    /// Processes a buffer offline like [`Plugin::process`] would, without a host. Frames hold the
+    /// channels of the current layout, with the second one ignored in mono inputs and left silent
+    /// in mono outputs. The parameters are read as they are, and there is no host tempo to sync to.
+    pub fn process_slice(&mut self, input: &[[f32; 2]]) -> Vec<[f32; 2]> {
+        let layout = self.layout;
+        let mut frames: Vec<_> = input.iter().map(|&frame| layout.upmix(frame)).collect();
+        self.process_in_place(&mut frames);
+        frames
+            .into_iter()
+            .map(|frame| layout.downmix(frame))
+            .collect()
     }
 
-    fn editor(&self) -> Option<Box<dyn Editor>> {
-        DelayEditor::create(self.params.clone(), self.editor_state.clone())
+    /// Same as [`Self::process_slice`] on stereo frames, whatever the layout, overwriting `frames`
+    /// with the output instead of allocating.
+    pub fn process_in_place(&mut self, frames: &mut [f32x2]) {
+        self.prepare_block();
+        self.process_frames(None, frames, None);
     }
 
-    fn accepts_bus_config(&self, config: &BusConfig) -> bool {
-        config.num_input_channels == config.num_output_channels && config.num_input_channels == 2
+    /// Builds the LFOs with their randomization drawn from `rng`, after the diffusers'.
+    fn seeded_lfos(rng: &mut StdRng) -> [Lfo; 2] {
+        let mut lfo = || Lfo::with_rng(StdRng::seed_from_u64(rng.gen()));
+        [lfo(), lfo().with_phase(STEREO_MOD_PHASE)]
     }
 
-    fn initialize(
+    /// Redraws the randomization of the diffusers and LFOs from a new seed, in place so that it
+    /// can happen on the audio thread. The diffusers start out silent.
+    fn reseed(&mut self, seed: i32) {
+        let mut rng = StdRng::seed_from_u64(seed as u64);
+        self.diffusion.reseed(&mut rng);
+        self.lfo = Self::seeded_lfos(&mut rng);
+        self.seed = seed;
+    }
+
+    /// Applies the parameters that only change between blocks.
+    fn prepare_block(&mut self) {
+        let panic = self.params.panic.value;
+        if panic && !self.panic {
+            self.clear_state();
+        }
+        self.panic = panic;
+        if self.params.seed.value != self.seed {
+            self.reseed(self.params.seed.value);
+        }
+        self.oversampler
+            .set_factor(self.params.oversampling.value());
+        self.diffusion
+            .set_stages(self.params.diffusion_stages.value as usize);
+        self.diffusion
+            .set_stage_mod_scaling(self.params.stage_mod_scaling.value);
+        self.set_diffuser(self.params.diffuser.value());
+        self.saturation = self.params.saturation.value();
+        self.set_damp_slope(self.params.damp_slope.value());
+        if self.params.delay_crossfade.value {
+            // Skips the ramp, leaving the jump to be crossfaded by `set_base_delay`
+            let target = self.params.delay.value;
+            if (target - self.params.delay.smoothed.previous_value()).abs() > RETIME_THRESHOLD {
+                self.params.delay.smoothed.reset(target);
+            }
+        }
+    }
+
+    /// Moves the tilt shelves towards the given tone, between -1 and 1. The shelves are keyed by
+    /// their linear gain, which the coefficient caches compare relatively.
+    fn set_tilt(&mut self, samplerate: f32, tone: f32) {
+        let pivot = Simd::splat(TILT_PIVOT / samplerate);
+        let q = Simd::splat(FRAC_1_SQRT_2);
+        let shelf_gain = util::db_to_gain(tone * TILT_GAIN);
+        let low = self.tilt_low_coeffs.get(shelf_gain.recip(), |gain| {
+            BiquadParams::low_shelf(pivot, q, Simd::splat(util::gain_to_db(gain)))
+        });
+        let high = self.tilt_high_coeffs.get(shelf_gain, |gain| {
+            BiquadParams::high_shelf(pivot, q, Simd::splat(util::gain_to_db(gain)))
+        });
+        self.tilt_low.set_params_smoothed(low, self.coeffs_glide);
+        self.tilt_high.set_params_smoothed(high, self.coeffs_glide);
+    }
+
+    /// Switches the damping filters to another slope. Coefficients of different filter types
+    /// don't interpolate well, so the filters jump straight to the new ones.
+    fn set_damp_slope(&mut self, slope: DampingSlope) {
+        if slope == self.damp_slope {
+            return;
+        }
+        self.damp_slope = slope;
+        self.damp_low_coeffs = CoefficientCache::default();
+        self.damp_high_coeffs = CoefficientCache::default();
+        let (low, high) = self.damping;
+        self.damp_low.params = slope.highpass(low / self.samplerate);
+        self.damp_high.params = slope.lowpass(high / self.samplerate);
+    }
+
+    /// Processes `frames` in place, in chunks of at most [`MAX_BLOCK_SIZE`] samples. `tempo` is
+    /// the host tempo, if any, used by the synced delay and modulation.
+    /// `sidechain` holds the auxiliary input for the same frames, when the host provides one.
+    fn process_frames(
         &mut self,
-        _bus_config: &BusConfig,
-        buffer_config: &BufferConfig,
-        _context: &mut impl InitContext,
-    ) -> bool {
-        *self = Self::new_with_params(self.params.clone(), buffer_config.sample_rate);
-        true
+        tempo: Option<f64>,
+        frames: &mut [f32x2],
+        sidechain: Option<&[f32x2]>,
+    ) {
+        for (n, chunk) in frames.chunks_mut(MAX_BLOCK_SIZE).enumerate() {
+            self.block.fill(&self.params, chunk.len());
+            let offset = n * MAX_BLOCK_SIZE;
+            for (index, frame) in chunk.iter_mut().enumerate() {
+                let key = sidechain.map(|sidechain| sidechain[offset + index]);
+                *frame = self.process_frame(tempo, index, *frame, key);
+            }
+            // Once in the feedback loop, a NaN or infinity would never die out on its own
+            if !all_finite(chunk) {
+                self.clear_state();
+                chunk.fill(f32x2::splat(0.));
+            }
+        }
     }
 
-    fn process(
+    /// Processes one stereo frame with the smoothed parameter values at `index` in the current
+    /// block.
+    fn process_frame(
         &mut self,
-        buffer: &mut Buffer,
-        _aux: &mut AuxiliaryBuffers,
-        context: &mut impl ProcessContext,
-    ) -> ProcessStatus {
-        let samplerate = context.transport().sample_rate;
-        for mut channels in buffer.iter_samples() {
-            let feedback = self.params.feedback.smoothed.next();
-            let size = self.params.size.smoothed.next();
-            let mod_depth = self.params.mod_depth.smoothed.next();
-            let mod_speed = self.params.mod_speed.smoothed.next();
-            let pitch_amt = self.params.pitch_amt.smoothed.next();
-            let delay =
-                self.params.delay.smoothed.next() + 15e-3 * mod_depth * f32::sin(TAU * self.phase);
-
-            self.damp_low.params = BiquadParams::highpass_1p(
-                Simd::splat(self.params.damp_low.smoothed.next() / samplerate),
-                Simd::splat(1.),
-            );
-            self.damp_high.params = BiquadParams::lowpass_1p(
-                Simd::splat(self.params.damp_high.smoothed.next() / samplerate),
-                Simd::splat(1.),
+        tempo: Option<f64>,
+        index: usize,
+        input: f32x2,
+        sidechain: Option<f32x2>,
+    ) -> f32x2 {
+        let samplerate = self.samplerate;
+        let feedback = self.block.feedback[index];
+        let decay_time = self.block.decay_time[index];
+        let size = self.block.size[index];
+        self.drive = self.block.drive[index];
+        self.band_decay = (self.block.low_decay[index], self.block.high_decay[index]);
+        self.decorrelation = self.block.decorrelation[index];
+        self.cross_feedback = self.block.cross_feedback[index];
+        self.ping_pong = if self.params.ping_pong.value {
+            self.block.ping_pong_amount[index]
+        } else {
+            0.
+        };
+        // Offsetting one channel would split the mono tank back into two
+        self.stereo_offset = if self.params.mono_tank.value {
+            0.
+        } else {
+            self.block.stereo_offset[index] * 1e-3
+        };
+        self.diffusion.set_density(self.block.density[index]);
+        self.allpass.set_gain(self.block.diffusion_gain[index]);
+        let mod_depth = self.block.mod_depth[index];
+        let mod_speed = mod_rate(
+            self.block.mod_speed[index],
+            self.params
+                .mod_sync
+                .value
+                .then(|| self.params.mod_division.value()),
+            tempo,
+        );
+        let pitch_amt = self.block.pitch_amt[index];
+        let pitch_ratio = f32::powf(2.0, self.params.pitch_interval.value / 12.0);
+        let shimmer_tone = self.block.shimmer_tone[index];
+        // Even at the top of the range a lowpass is not transparent, so it is bypassed there
+        self.shimmer_open = shimmer_tone >= SHIMMER_TONE_OPEN;
+        let shimmer_params = self.shimmer_coeffs.get(shimmer_tone / samplerate, |fc| {
+            BiquadParams::lowpass(Simd::splat(fc.min(0.49)), Simd::splat(FRAC_1_SQRT_2))
+        });
+        self.shimmer_filter
+            .set_params_smoothed(shimmer_params, self.coeffs_glide);
+        let mix = self.block.mix[index];
+        let output_gain = self.block.output_gain[index];
+        self.ceiling = self.block.ceiling[index];
+        let width = self.block.width[index];
+        let free_delay = self.block.delay[index];
+        let synced_delay = self
+            .params
+            .delay_sync
+            .value
+            .then(|| self.params.delay_division.value())
+            .zip(tempo.filter(|&tempo| tempo > 0.))
+            .map(|(division, tempo)| division.seconds(tempo).clamp(1e-3, MAX_DELAY));
+        self.set_base_delay(synced_delay.unwrap_or(free_delay), synced_delay.is_some());
+        let base_delay = self.base_delay;
+        let delay = self.modulated_delay(base_delay, mod_depth, self.params.mod_shape.value());
+        let late = self.block.late[index];
+        let duck_amount = self.block.duck_amount[index];
+        let duck_release = self.params.duck_release.value * 1e-3;
+        self.sidechain = match self.params.duck_source.value() {
+            DuckSource::Input => None,
+            DuckSource::Sidechain => Some(sidechain.unwrap_or_default()),
+        };
+        let loop_delay = self.loop_delay(samplerate, size, base_delay);
+        let (feedback, rt60) = match self.params.decay_mode.value() {
+            DecayMode::Feedback => (feedback, feedback_decay(loop_delay, feedback)),
+            DecayMode::DecayTime => (decay_feedback(loop_delay, decay_time), decay_time),
+        };
+        let (feedback, rt60, late) = match self.params.mode.value() {
+            ReverbMode::Reverb => (feedback, rt60, late),
+            ReverbMode::Chorus => (0., rt60, 0.),
+            ReverbMode::Ambience => {
+                let rt60 = rt60.min(AMBIENCE_DECAY);
+                (feedback.min(decay_feedback(loop_delay, rt60)), rt60, late)
+            }
+        };
+
+        let q = Simd::splat(FRAC_1_SQRT_2);
+        let input_low = self
+            .input_low_coeffs
+            .get(self.block.input_low_cut[index] / samplerate, |fc| {
+                BiquadParams::highpass(Simd::splat(fc), q)
+            });
+        // Keeps the cutoff below Nyquist at low sample rates
+        let input_high = self
+            .input_high_coeffs
+            .get(self.block.input_high_cut[index] / samplerate, |fc| {
+                BiquadParams::lowpass(Simd::splat(fc.min(0.49)), q)
+            });
+        self.input_low
+            .set_params_smoothed(input_low, self.coeffs_glide);
+        self.input_high
+            .set_params_smoothed(input_high, self.coeffs_glide);
+
+        self.damping = (self.block.damp_low[index], self.block.damp_high[index]);
+        let slope = self.damp_slope;
+        let damp_low = self
+            .damp_low_coeffs
+            .get(self.damping.0 / samplerate, |fc| slope.highpass(fc));
+        let damp_high = self
+            .damp_high_coeffs
+            .get(self.damping.1 / samplerate, |fc| slope.lowpass(fc));
+        self.damp_low
+            .set_params_smoothed(damp_low, self.coeffs_glide);
+        self.damp_high
+            .set_params_smoothed(damp_high, self.coeffs_glide);
+        self.late.set_damping(self.damping.1 / samplerate);
+        self.set_tilt(samplerate, self.block.tone[index]);
+
+        self.tick_lfo(samplerate, mod_speed);
+        self.tick_freeze(samplerate, self.frozen());
+        self.tick_bypass(samplerate, self.params.bypass.value);
+        self.tick_reverse(samplerate, self.reversed(), self.params.reverse.value);
+
+        self.tick_gate(
+            samplerate,
+            input,
+            self.params.gate.enabled.value,
+            self.params.gate.threshold.value,
+            self.params.gate.hold.value * 1e-3,
+        );
+        let frame = FrameParams {
+            size,
+            feedback,
+            rt60,
+            delay,
+            mod_depth,
+            pitch_amt,
+            pitch_ratio,
+            shimmer_feedback: self.params.shimmer_feedback.value,
+            mix,
+            output_gain,
+            width,
+            mono_tank: self.params.mono_tank.value,
+            late,
+            duck_amount,
+            duck_release,
+        };
+        let output = self.next_sample(samplerate, &frame, input);
+        self.apply_bypass(output)
+    }
+
+    /// Applies the feedback gain to `x`, with the gain of the low and high bands raised to the
+    /// power that stretches their decay time by their multiplier.
+    /// The powers are only worked out again when the feedback or the multipliers change.
+    fn band_feedback(&mut self, x: f32x2, feedback: f32) -> f32x2 {
+        let [low, mid, high] = self.crossover.split(x);
+        if self.band_gains_for != (feedback, self.band_decay) {
+            let (low_mult, high_mult) = self.band_decay;
+            self.band_gains = (
+                feedback.powf(low_mult.recip()),
+                feedback.powf(high_mult.recip()),
             );
+            self.band_gains_for = (feedback, self.band_decay);
+        }
+        let (low_gain, high_gain) = self.band_gains;
+        low * Simd::splat(low_gain) + mid * Simd::splat(feedback) + high * Simd::splat(high_gain)
+    }
 
-            self.tick_phase(samplerate, mod_speed);
+    /// Reads the feedback delay line `delay` seconds back, scaled by the room size, fading out the
+    /// previous read position after a jump in the delay time. The right channel is read a further
+    /// [`Self::stereo_offset`] back, which is left unscaled as it is heard as a placement rather
+    /// than as part of the room.
+    fn read_delay(&mut self, samplerate: f32, size: f32, delay: f32x2) -> f32x2 {
+        let scale = self.room_scale(size);
+        let offset = f32x2::from_array([0., self.stereo_offset]);
+        let read = |delay: f32x2| {
+            self.delay
+                .get_seconds(delay * Simd::splat(scale) + offset, samplerate)
+        };
+        let current = read(delay);
+        if self.retime <= 0. {
+            return current;
+        }
+        let previous = read(delay + Simd::splat(self.retime_offset));
+        let out = current + (previous - current) * Simd::splat(self.retime);
+        self.retime = (self.retime - 1. / (RETIME_FADE * samplerate)).max(0.);
+        out
+    }
 
-            channels.from_simd(self.next_sample(
-                samplerate,
-                size,
-                feedback,
-                delay,
-                mod_depth,
-                pitch_amt,
-                channels.to_simd::<2>(),
-            ));
+    /// Sets the delay time before modulation. Changes while following the host tempo, and larger
+    /// ones with the delay crossfade on, jump straight to the new time and crossfade the read
+    /// positions, as sweeping the delay time would repitch the tail. A jump coming while the
+    /// previous one is still fading waits for it to finish, as there is only one read position to
+    /// fade out.
+    fn set_base_delay(&mut self, delay: f32, synced: bool) {
+        let crossfade =
+            self.params.delay_crossfade.value && (delay - self.base_delay).abs() > RETIME_THRESHOLD;
+        if (synced || self.delay_synced || crossfade) && delay != self.base_delay {
+            if self.retime > 0. {
+                // Called again with the new time on the next samples
+                return;
+            }
+            self.retime_offset = self.base_delay - delay;
+            self.retime = 1.;
         }
-        ProcessStatus::Normal
+        self.base_delay = delay;
+        self.delay_synced = synced;
     }
-}
 
-#[cfg(feature = "vst3")]
-impl Vst3Plugin for Reverb {
-    const VST3_CLASS_ID: [u8; 16] = *b"SolarLinerNihPlg";
-    const VST3_CATEGORIES: &'static str = "Fx|Delay|Reverb";
-}
+    /// Feedback limiter keeping the loop stable.
+    ///
+    /// The loop is stable as long as its gain stays at or below unity, which the feedback amount
+    /// goes past above 100%. The limiter only `engage`s then, holding the recirculating signal under
+    /// [`LOOP_CEILING`] with an instant attack: whenever it would grow past the ceiling, the
+    /// limiter gain drops to cancel the excess, bringing the loop gain back to unity at most.
+    /// Otherwise it releases back to unity gain, leaving loud but decaying tails untouched.
+    fn limit(&mut self, samplerate: f32, engage: bool, x: f32x2) -> f32x2 {
+        let peak = x.abs().reduce_max();
+        let target = if engage && peak > LOOP_CEILING {
+            LOOP_CEILING / peak
+        } else {
+            1.
+        };
+        self.loop_gain = if target < self.loop_gain {
+            target
+        } else {
+            let release = 1. - f32::exp(-1. / (LOOP_RELEASE * samplerate));
+            self.loop_gain + (target - self.loop_gain) * release
+        };
+        x * Simd::splat(self.loop_gain)
+    }
 
-#[cfg(feature = "vst3")]
-nih_export_vst3!(Reverb);
+    /// Average round-trip time of the feedback loop, in seconds.
+    fn loop_delay(&self, samplerate: f32, size: f32, delay: f32) -> f32 {
+        let diffusion = match self.diffuser {
+            Diffuser::Householder => self.diffusion.mean_delay(size),
+            Diffuser::Allpass => self.allpass.mean_delay(size),
+        };
+        delay * self.room_scale(size) + (diffusion + self.oversampler.latency() as f32) / samplerate
+    }
 
-#[cfg(test)]
-mod tests {}
+    /// Scale of the feedback delay time at the given size, so that the size stretches the whole
+    /// loop along with the diffusers. Delay times following the host tempo are left as they are,
+    /// as are all of them with [`DelayParams::size_scales_delay`] off.
+    fn room_scale(&self, size: f32) -> f32 {
+        if self.delay_synced || !self.params.size_scales_delay.value {
+            1.
+        } else {
+            MIN_ROOM_SCALE + (MAX_ROOM_SCALE - MIN_ROOM_SCALE) * size
+        }
+    }
+
+    /// Switches the diffusion algorithm, starting the newly selected diffusers from silence.
+    fn set_diffuser(&mut self, diffuser: Diffuser) {
+        if diffuser != self.diffuser {
+            match diffuser {
+                Diffuser::Householder => self.diffusion.reset(),
+                Diffuser::Allpass => self.allpass.reset(),
+            }
+            self.diffuser = diffuser;
+        }
+    }
+
+    /// Publishes the peak level of the block that was just processed to the editor's meter.
+    fn update_peak_meter(&mut self, samplerate: f32, num_samples: usize) {
+        if self.editor_state.is_open() {
+            let current = self.peak_meter.load(Ordering::Relaxed);
+            let peak = if self.peak > current {
+                self.peak
+            } else {
+                let decay = f32::powf(0.25, num_samples as f32 / (PEAK_METER_DECAY * samplerate));
+                current * decay + self.peak * (1. - decay)
+            };
+            self.peak_meter.store(peak, Ordering::Relaxed);
+        }
+        self.peak = 0.;
+    }
+
+    /// Feedback delay times for each channel, modulated by their own LFO so that the modulation
+    /// decorrelates the stereo field.
+    fn modulated_delay(&self, delay: f32, mod_depth: f32, shape: LfoShape) -> f32x2 {
+        let lfo = f32x2::from_array([self.lfo[0].value(shape), self.lfo[1].value(shape)]);
+        Simd::splat(delay) + Simd::splat(DELAY_MOD_DEPTH * mod_depth) * lfo
+    }
+
+    fn tick_lfo(&mut self, samplerate: f32, mod_speed: f32) {
+        for lfo in &mut self.lfo {
+            lfo.tick(samplerate, mod_speed);
+        }
+    }
+
+    /// Whether the freeze mode is on, from its parameter or held from MIDI.
+    fn frozen(&self) -> bool {
+        self.params.freeze.value || self.midi.freeze()
+    }
+
+    /// Whether the reverse mode is on, from its parameter or held from MIDI.
+    fn reversed(&self) -> bool {
+        self.params.reverse.value || self.midi.reverse()
+    }
+
+    fn tick_freeze(&mut self, samplerate: f32, frozen: bool) {
+        self.freeze = ramp(self.freeze, frozen, FREEZE_FADE * samplerate);
+    }
+
+    fn tick_bypass(&mut self, samplerate: f32, bypassed: bool) {
+        self.bypass = ramp(self.bypass, bypassed, BYPASS_FADE * samplerate);
+    }
+
+    /// Gain ducking the wet signal by `amount` as the dry signal gets louder, recovering over
+    /// `release` seconds once it gets quieter.
+    fn duck(&mut self, samplerate: f32, dry: f32x2, amount: f32, release: f32) -> f32 {
+        let level = dry.abs().reduce_max();
+        let time = if level > self.duck_envelope {
+            DUCK_ATTACK
+        } else {
+            release
+        };
+        self.duck_envelope +=
+            (level - self.duck_envelope) * (1. - f32::exp(-1. / (time * samplerate)));
+        1. - amount * self.duck_envelope.min(1.)
+    }
+
+    /// Advances the crossfades to the reverse mode, `reversed` from any source and `compensated`
+    /// from the parameter alone, see [`Self::reverse_latency`].
+    fn tick_reverse(&mut self, samplerate: f32, reversed: bool, compensated: bool) {
+        self.reverse = ramp(self.reverse, reversed, REVERSE_CROSSFADE * samplerate);
+        self.reverse_latency = ramp(
+            self.reverse_latency,
+            compensated,
+            REVERSE_CROSSFADE * samplerate,
+        );
+    }
+
+    /// Latency of the plugin, in samples. The oversampler delays the wet signal by a few samples,
+    /// and the reversed wet signal lags its input by a full segment on average. The dry signal is
+    /// delayed by as much, keeping the swell of the reverse mode leading into the transient that
+    /// caused it. Only the reverse parameter counts towards `reversed`, not MIDI.
+    fn latency(&self, reversed: bool) -> u32 {
+        let reverse = if reversed {
+            self.reverser.segment_len() as u32
+        } else {
+            0
+        };
+        let latency = self.oversampler.latency() + reverse;
+        // The dry signal can only be held back as far as its delay line reaches
+        nih_debug_assert!((latency as usize) < self.dry_delay.max_delay());
+        latency
+    }
+
+    /// Delays the dry signal to line up with the wet signal, crossfading to the longer delay of
+    /// the reverse mode as its parameter engages.
+    fn compensate_latency(&mut self, dry: f32x2) -> f32x2 {
+        let oversampling = self.oversampler.latency() as f32;
+        let reverse = self.reverser.segment_len() as f32;
+        let forward = if oversampling > 0. {
+            self.dry_delay
+                .tap_with(oversampling, Interpolation::Nearest)
+        } else {
+            dry
+        };
+        let reversed = self
+            .dry_delay
+            .tap_with(oversampling + reverse, Interpolation::Nearest);
+        self.dry_delay.push_next(dry);
+        forward + (reversed - forward) * Simd::splat(self.reverse_latency)
+    }
+
+    /// Follows the input level, keeping the gate open while it is above `threshold` and for `hold`
+    /// seconds after it falls below, then fading the wet signal out. A disabled gate stays open.
+    fn tick_gate(
+        &mut self,
+        samplerate: f32,
+        input: f32x2,
+        enabled: bool,
+        threshold: f32,
+        hold: f32,
+    ) {
+        let level = input.abs().reduce_max();
+        self.gate_envelope = if level > self.gate_envelope {
+            level
+        } else {
+            self.gate_envelope * f32::exp(-1. / (GATE_RELEASE * samplerate))
+        };
+        if !enabled || self.gate_envelope > threshold {
+            self.gate_hold = hold * samplerate;
+        } else {
+            self.gate_hold = (self.gate_hold - 1.).max(0.);
+        }
+
+        let open = self.gate_hold > 0.;
+        let fade = if open { GATE_ATTACK } else { GATE_FADE };
+        self.gate = ramp(self.gate, open, fade * samplerate);
+    }
+
+    /// Crossfades from the processed output to the dry signal as the bypass engages, delayed by
+    /// the latency like the dry signal in the mix so that the fade does not shift it in time. The
+    /// reverb keeps running underneath so that its tail is intact when the bypass is released.
+    fn apply_bypass(&self, processed: f32x2) -> f32x2 {
+        processed + (self.dry - processed) * Simd::splat(self.bypass)
+    }
+}
+
+/// Length of the feedback delay line at the given sample rate, fitting the longest delay time at
+/// the largest size along with the stereo offset and [`DELAY_HEADROOM`].
+fn max_delay_samples(samplerate: f32) -> usize {
+    f32::ceil((MAX_DELAY * MAX_ROOM_SCALE + MAX_STEREO_OFFSET + DELAY_HEADROOM) * samplerate)
+        as usize
+}
+
+/// Moves `value` one step towards 1 when `on`, or towards 0 otherwise, taking `length` samples to
+/// go across the whole range.
+fn ramp(value: f32, on: bool, length: f32) -> f32 {
+    let step = 1. / length;
+    if on {
+        (value + step).min(1.)
+    } else {
+        (value - step).max(0.)
+    }
+}
+
+/// Feedback gain making a loop of the given round-trip time decay by 60 dB in `rt60` seconds.
+fn decay_feedback(loop_delay: f32, rt60: f32) -> f32 {
+    f32::powf(10.0, -3.0 * loop_delay / rt60)
+}
+
+/// Decay time of a loop of the given round-trip time attenuated by `feedback` on each pass; the
+/// inverse of [`decay_feedback`]. Capped to the range of the decay time parameter, loops that do
+/// not lose energy report the longest decay.
+fn feedback_decay(loop_delay: f32, feedback: f32) -> f32 {
+    if feedback >= 1.0 {
+        return 20.0;
+    }
+    (-3.0 * loop_delay / feedback.log10()).clamp(0.1, 20.0)
+}
+
+/// Adjusts the parameter values of a state that was just loaded, before processing with them.
+fn apply_loaded_state(params: &DelayParams, samplerate: f32) {
+    migrate_params(params, samplerate);
+    // Monitoring aid, loading a session should not leave the dry signal muted
+    set_param(&params.wet_solo, false, samplerate);
+}
+
+/// Upgrades parameter values saved by an older version of the plugin so that they keep sounding
+/// the same.
+fn migrate_params(params: &DelayParams, samplerate: f32) {
+    let version = params.version.get();
+    if version < 1 {
+        // The reverb used to be fully wet, and had no late reverberation tank
+        set_param(&params.mix, 1.0, samplerate);
+        set_param(&params.late, 0.0, samplerate);
+    }
+    if version < 2 {
+        // The size used to leave the feedback delay time alone
+        set_param(&params.size_scales_delay, false, samplerate);
+    }
+    if version < 3 {
+        // The wet signal used to reach the output unlimited
+        set_param(&params.limiter, false, samplerate);
+    }
+    if version < 4 {
+        // The shimmer used to be unfiltered
+        set_param(&params.shimmer_tone, SHIMMER_TONE_OPEN, samplerate);
+    }
+    if version < 5 {
+        // The shimmer amount was not saved, and always started out at none
+        set_param(&params.pitch_amt, 0.0, samplerate);
+    }
+    if version < 6 {
+        // All the diffusion stages used to modulate by the same depth
+        set_param(&params.stage_mod_scaling, false, samplerate);
+    }
+    params.version.set(STATE_VERSION);
+}
+
+/// Overwrites the value of a parameter from the plugin, snapping its smoother to the new value.
+/// Only meant for while the plugin is being initialized, when the host is not processing audio or
+/// automating the parameters.
+fn set_param<P: Param>(param: &P, value: P::Plain, samplerate: f32) {
+    let normalized = param.preview_normalized(value);
+    let param = param.as_ptr();
+    // SAFETY: the parameter is alive, and nothing else is setting it at the same time
+    unsafe {
+        param.set_normalized_value(normalized);
+        param.update_smoother(samplerate, true);
+    }
+}
+
+/// Modulation rate in Hz: the rate of the note division at the host tempo when synced, or the free
+/// rate otherwise. Falls back to the free rate when the host does not provide a tempo.
+fn mod_rate(free_rate: f32, sync: Option<NoteDivision>, tempo: Option<f64>) -> f32 {
+    match (sync, tempo) {
+        (Some(division), Some(tempo)) if tempo > 0. => division.rate(tempo),
+        _ => free_rate,
+    }
+}
+
+/// Scales the side component of a stereo signal, collapsing it to mono at 0 and doubling the
+/// side level at 2, while leaving the mid component untouched.
+fn stereo_width(x: f32x2, width: f32) -> f32x2 {
+    let [l, r] = x.to_array();
+    let mid = (l + r) / 2.;
+    let side = (l - r) / 2. * width;
+    f32x2::from_array([mid + side, mid - side])
+}
+
+/// Rotates the channels of the feedback loop into each other by `amount` of a quarter turn. The
+/// rotation is orthogonal, so the loop keeps its energy; at the full amount the channels swap
+/// sides on every pass, one of them inverted, which ping-pongs the echoes.
+fn cross_feed(x: f32x2, amount: f32) -> f32x2 {
+    let (s, c) = (amount * FRAC_PI_2).sin_cos();
+    let [l, r] = x.to_array();
+    f32x2::from_array([c * l + s * r, c * r - s * l])
+}
+
+/// Crossfades `x` towards its channels swapped by `amount`. At the full amount, each pass through
+/// the feedback loop comes back on the opposite side.
+fn ping_pong(x: f32x2, amount: f32) -> f32x2 {
+    let [l, r] = x.to_array();
+    x * Simd::splat(1. - amount) + f32x2::from_array([r, l]) * Simd::splat(amount)
+}
+
+/// Whether none of the frames hold a NaN or an infinity.
+fn all_finite(frames: &[f32x2]) -> bool {
+    frames
+        .iter()
+        .fold(Mask::splat(true), |finite, x| finite & x.is_finite())
+        .all()
+}
+
+impl Default for Reverb {
+    fn default() -> Self {
+        Self::new(44100.)
+    }
+}
+
+impl Plugin for Reverb {
+    const NAME: &'static str = "NIH Reverb";
+    const VENDOR: &'static str = env!("CARGO_PKG_AUTHORS");
+    const URL: &'static str = env!("CARGO_PKG_HOMEPAGE");
+    const EMAIL: &'static str = "N/A";
+    const VERSION: &'static str = env!("CARGO_PKG_VERSION");
+
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
+    const DEFAULT_AUX_INPUTS: Option<AuxiliaryIOConfig> = Some(AuxiliaryIOConfig {
+        num_busses: 1,
+        num_channels: 2,
+    });
+    const PORT_NAMES: PortNames = PortNames {
+        main_input: None,
+        main_output: None,
+        aux_inputs: Some(&["Sidechain"]),
+        aux_outputs: None,
+    };
+
+    fn params(&self) -> Arc<dyn Params> {
+        self.params.clone()
+    }
+
+    fn editor(&self) -> Option<Box<dyn Editor>> {
+        DelayEditor::create(
+            self.params.clone(),
+            self.peak_meter.clone(),
+            self.damping_cutoffs.clone(),
+            self.editor_state.clone(),
+        )
+    }
+
+    fn accepts_bus_config(&self, config: &BusConfig) -> bool {
+        ChannelLayout::from_bus_config(config).is_some()
+    }
+
+    fn initialize(
+        &mut self,
+        bus_config: &BusConfig,
+        buffer_config: &BufferConfig,
+        context: &mut impl InitContext,
+    ) -> bool {
+        // nih-plug reinitializes the plugin after loading a state
+        if self.params.version.take_loaded() {
+            apply_loaded_state(&self.params, buffer_config.sample_rate);
+        }
+        // Hosts call `reset` after this, which clears the state without reallocating
+        self.set_samplerate(buffer_config.sample_rate);
+        self.layout = match ChannelLayout::from_bus_config(bus_config) {
+            Some(layout) => layout,
+            None => return false,
+        };
+        // The dry signal is passed through sample-aligned, and the wet path's delays (pre-delay,
+        // diffusion and the shimmer's pitch shifter) are part of the effect itself. Compensating
+        // for them would pull the dry signal ahead of the rest of the mix, so the only latency
+        // reported is the one added by the oversampling and the reverse mode.
+        self.oversampler
+            .set_factor(self.params.oversampling.value());
+        self.latency = self.latency(self.params.reverse.value);
+        context.set_latency_samples(self.latency);
+        true
+    }
+
+    fn reset(&mut self) {
+        self.clear_state();
+        // The host stopped sending events, the notes and pedals held so far will not be released
+        self.midi = MidiActions::default();
+    }
+
+    fn process(
+        &mut self,
+        buffer: &mut Buffer,
+        aux: &mut AuxiliaryBuffers,
+        context: &mut impl ProcessContext,
+    ) -> ProcessStatus {
+        let samplerate = self.samplerate;
+        let tempo = context.transport().tempo;
+        self.prepare_block();
+        let latency = self.latency(self.params.reverse.value);
+        if latency != self.latency {
+            self.latency = latency;
+            context.set_latency_samples(latency);
+        }
+        let mut sidechain_blocks = aux
+            .inputs
+            .first_mut()
+            .map(|sidechain| sidechain.iter_blocks(MAX_BLOCK_SIZE));
+        let mut next_event = context.next_event();
+        for (block_start, mut block) in buffer.iter_blocks(MAX_BLOCK_SIZE) {
+            // Events take effect from the start of the block they fall in
+            let block_end = block_start + block.samples();
+            while let Some(event) = next_event.filter(|event| (event.timing() as usize) < block_end)
+            {
+                self.midi.handle(event);
+                next_event = context.next_event();
+            }
+            let mut frames = [f32x2::splat(0.); MAX_BLOCK_SIZE];
+            let frames = &mut frames[..block.samples()];
+            for (mut channels, frame) in block.iter_samples().zip(frames.iter_mut()) {
+                let mut samples = [0.; 2];
+                for (sample, channel) in samples.iter_mut().zip(channels.iter_mut()) {
+                    *sample = *channel;
+                }
+                *frame = self.layout.upmix(samples);
+            }
+            let mut keys = [f32x2::splat(0.); MAX_BLOCK_SIZE];
+            let keys =
+                sidechain_blocks
+                    .as_mut()
+                    .and_then(Iterator::next)
+                    .map(|(_, mut sidechain)| {
+                        // The ducking follows the loudest channel, so a mono sidechain needs no upmix
+                        for (mut channels, key) in sidechain.iter_samples().zip(keys.iter_mut()) {
+                            let mut samples = [0.; 2];
+                            for (sample, channel) in samples.iter_mut().zip(channels.iter_mut()) {
+                                *sample = *channel;
+                            }
+                            *key = f32x2::from_array(samples);
+                        }
+                        &keys[..frames.len()]
+                    });
+            self.process_frames(tempo, frames, keys);
+            for (mut channels, frame) in block.iter_samples().zip(frames.iter()) {
+                for (channel, sample) in channels.iter_mut().zip(self.layout.downmix(*frame)) {
+                    *channel = sample;
+                }
+            }
+        }
+        self.update_peak_meter(samplerate, buffer.samples());
+        self.damping_cutoffs
+            .store(samplerate, self.damping, self.damp_slope);
+        ProcessStatus::Normal
+    }
+}
+
+#[cfg(feature = "vst3")]
+impl Vst3Plugin for Reverb {
+    const VST3_CLASS_ID: [u8; 16] = *b"SolarLinerNihPlg";
+    const VST3_CATEGORIES: &'static str = "Fx|Delay|Reverb";
+}
+
+#[cfg(feature = "vst3")]
+nih_export_vst3!(Reverb);
+
+#[cfg(feature = "clap")]
+impl ClapPlugin for Reverb {
+    const CLAP_ID: &'static str = "com.solarliner.nih-reverb";
+    const CLAP_DESCRIPTION: Option<&'static str> =
+        Some("Diffusion delay design for anything from simple delays to washed out reverbs");
+    const CLAP_MANUAL_URL: Option<&'static str> = None;
+    const CLAP_SUPPORT_URL: Option<&'static str> = None;
+    // Matches the layouts of `ChannelLayout`
+    const CLAP_FEATURES: &'static [ClapFeature] = &[
+        ClapFeature::AudioEffect,
+        ClapFeature::Reverb,
+        ClapFeature::Mono,
+        ClapFeature::Stereo,
+    ];
+}
+
+#[cfg(feature = "clap")]
+nih_export_clap!(Reverb);
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::f32::consts::{FRAC_1_SQRT_2, TAU};
+    use std::sync::Arc;
+
+    use nih_plug::prelude::*;
+
+    use rand::prelude::*;
+
+    use crate::midi::{tests::note, FREEZE_NOTE, REVERSE_NOTE};
+    use crate::simd::f32x2;
+
+    use super::{
+        apply_loaded_state, decay_feedback, feedback_decay, mod_rate, stereo_width, BiquadParams,
+        ChannelLayout, Crossover, DampingCutoffs, DampingSlope, DelayParams, Diffuser, DuckSource,
+        Early, FrameParams, Lfo, LfoShape, NoteDivision, Oversampling, Reverb, ReverbMode,
+        Saturation, DELAY_HEADROOM, DELAY_MOD_DEPTH, HIGH_CROSSOVER, LOW_CROSSOVER, MAX_DELAY,
+        MAX_ROOM_SCALE, MAX_STEREO_OFFSET, PITCH_WINDOW,
+    };
+
+    fn rms(samples: &[f32x2]) -> f32 {
+        let sum_sq: f32 = samples.iter().flat_map(|s| (*s * *s).to_array()).sum();
+        (sum_sq / (2 * samples.len()) as f32).sqrt()
+    }
+
+    /// Frame parameters the tests run [`Reverb::next_sample`] with, unless they override some.
+    fn frame() -> FrameParams {
+        FrameParams {
+            size: 0.5,
+            feedback: 0.7,
+            rt60: 2.0,
+            delay: f32x2::splat(0.2),
+            mod_depth: 0.1,
+            pitch_amt: 0.0,
+            pitch_ratio: 2.0,
+            shimmer_feedback: true,
+            mix: 1.0,
+            output_gain: 1.0,
+            width: 1.0,
+            mono_tank: false,
+            late: 0.5,
+            duck_amount: 0.0,
+            duck_release: 0.25,
+        }
+    }
+
+    fn process(reverb: &mut Reverb, samplerate: f32, input: f32x2) -> f32x2 {
+        reverb.next_sample(samplerate, &frame(), input)
+    }
+
+    #[test]
+    fn accepted_bus_configs_process() {
+        let samplerate = 44100.;
+        let render = |(inputs, outputs), impulse: [f32; 2]| {
+            let config = BusConfig {
+                num_input_channels: inputs,
+                num_output_channels: outputs,
+                aux_input_busses: AuxiliaryIOConfig::default(),
+                aux_output_busses: AuxiliaryIOConfig::default(),
+            };
+            let mut reverb = Reverb::new(samplerate);
+            reverb.layout = ChannelLayout::from_bus_config(&config).unwrap();
+            let mut input = vec![[0.; 2]; samplerate as usize];
+            input[0] = impulse;
+            reverb.process_slice(&input)
+        };
+
+        let stereo = render((2, 2), [1., 1.]);
+        assert!(stereo.iter().flatten().any(|x| x.abs() > 1e-3));
+        assert!(stereo.iter().flatten().all(|x| x.is_finite()));
+        // Mono inputs are duplicated to both sides, whatever is in the unused channel
+        assert_eq!(stereo, render((1, 2), [1., 0.]));
+        assert_eq!(stereo, render((1, 2), [1., -1.]));
+        assert_ne!(stereo, render((2, 2), [1., 0.]));
+        // Mono outputs get the average of both sides
+        for (mono, stereo) in render((1, 1), [1., 0.]).into_iter().zip(stereo) {
+            assert!((mono[0] - (stereo[0] + stereo[1]) / 2.).abs() < 1e-6);
+            assert_eq!(0., mono[1]);
+        }
+
+        let surround = BusConfig {
+            num_input_channels: 6,
+            num_output_channels: 6,
+            aux_input_busses: AuxiliaryIOConfig::default(),
+            aux_output_busses: AuxiliaryIOConfig::default(),
+        };
+        assert_eq!(None, ChannelLayout::from_bus_config(&surround));
+    }
+
+    #[test]
+    fn decay_time_matches_rt60() {
+        let samplerate = 44100.;
+        let rt60 = 4.0;
+        let mut reverb = Reverb::new(samplerate);
+        reverb.diffusion = Early::with_rng(samplerate, &mut StdRng::seed_from_u64(1));
+        let feedback = decay_feedback(reverb.loop_delay(samplerate, 0.5, 0.2), rt60);
+        let frame = FrameParams {
+            feedback,
+            rt60,
+            mod_depth: 0.0,
+            ..frame()
+        };
+
+        let energy: Vec<f32> = (0..(2. * rt60 * samplerate) as usize)
+            .map(|i| {
+                let input = f32x2::splat(if i == 0 { 0.1 } else { 0. });
+                let out = reverb.next_sample(samplerate, &frame, input);
+                (out * out).reduce_sum()
+            })
+            .collect();
+
+        // Schroeder backward integration, with the decay time extrapolated from the -15 to -35 dB
+        // range of the energy decay curve, past the build-up of the diffusers
+        let mut edc: Vec<f32> = energy
+            .iter()
+            .rev()
+            .scan(0., |acc, e| {
+                *acc += e;
+                Some(*acc)
+            })
+            .collect();
+        edc.reverse();
+        let time_at = |db: f32| {
+            let threshold = edc[0] * f32::powf(10.0, db / 10.);
+            edc.iter().position(|&e| e < threshold).unwrap() as f32 / samplerate
+        };
+        let measured = 3. * (time_at(-35.) - time_at(-15.));
+        assert!(
+            (measured - rt60).abs() < 0.2 * rt60,
+            "measured {measured} s, expected {rt60} s"
+        );
+    }
+
+    #[test]
+    fn unity_feedback_decays_longest() {
+        assert_eq!(20.0, feedback_decay(0.1, 1.0));
+        assert_eq!(20.0, feedback_decay(0.1, 1.25));
+        assert!(feedback_decay(0.1, 0.99) > feedback_decay(0.1, 0.5));
+    }
+
+    #[test]
+    fn damping_curve_response() {
+        let cutoffs = DampingCutoffs::default();
+        assert_eq!(1., cutoffs.response(1000.));
+
+        cutoffs.store(48e3, (100., 5000.), DampingSlope::Gentle);
+        assert!((cutoffs.response(1000.) - 1.).abs() < 0.05);
+        assert!(cutoffs.response(20.) < 0.3);
+        assert!(cutoffs.response(20e3) < 0.3);
+    }
+
+    #[test]
+    fn steep_damping_rolls_off_faster() {
+        let fc = 0.01;
+        let slope = |slope: DampingSlope| {
+            let (a, _) = slope.lowpass::<1>(fc).response(4. * fc);
+            let (b, _) = slope.lowpass::<1>(fc).response(8. * fc);
+            util::gain_to_db(b) - util::gain_to_db(a)
+        };
+        let gentle = slope(DampingSlope::Gentle);
+        let steep = slope(DampingSlope::Steep);
+        assert!((gentle + 6.).abs() < 1., "{gentle} dB/oct");
+        assert!((steep + 12.).abs() < 1.5, "{steep} dB/oct");
+
+        let cutoffs = DampingCutoffs::default();
+        cutoffs.store(48e3, (20., 1000.), DampingSlope::Steep);
+        let (a, b) = (cutoffs.response(4000.), cutoffs.response(8000.));
+        assert!(util::gain_to_db(b) - util::gain_to_db(a) < -10.);
+    }
+
+    #[test]
+    fn cross_feedback_moves_energy_across() {
+        let x = f32x2::from_array([0.6, -0.8]);
+        for amount in [0., 0.3, 0.7, 1.] {
+            let y = super::cross_feed(x, amount);
+            assert!(
+                ((y * y).reduce_sum() - 1.).abs() < 1e-6,
+                "{y:?} at {amount}"
+            );
+        }
+        assert_eq!(x, super::cross_feed(x, 0.));
+        let swapped = super::cross_feed(f32x2::from_array([1., 0.]), 1.);
+        assert!(
+            swapped[0].abs() < 1e-6 && swapped[1].abs() > 0.999,
+            "{swapped:?}"
+        );
+
+        // The Householder diffuser already spreads each channel over both; the allpass one keeps
+        // them apart, leaving the crossing to the feedback
+        let samplerate = 44100.;
+        let right_share = |amount: f32| {
+            let mut params = DelayParams::default();
+            params.mix.smoothed.reset(1.);
+            params.late.smoothed.reset(0.);
+            params.cross_feedback.smoothed.reset(amount);
+            params.diffuser = EnumParam::new("Diffuser", Diffuser::Allpass);
+            let mut reverb = Reverb::new_with_params(Arc::new(params), samplerate);
+            reverb.prepare_block();
+            let mut input = vec![[0.; 2]; samplerate as usize];
+            input[0] = [1., 0.];
+            let output = reverb.process_slice(&input);
+            let [l, r] = output
+                .iter()
+                .fold([0f32; 2], |[l, r], [x, y]| [l + x * x, r + y * y]);
+            r / (l + r)
+        };
+        let (straight, crossed) = (right_share(0.), right_share(1.));
+        assert!(straight < 1e-6, "{straight}");
+        assert!(crossed > 0.05, "{crossed}");
+    }
+
+    #[test]
+    fn ping_pong_alternates_sides() {
+        let x = f32x2::from_array([0.6, -0.8]);
+        assert_eq!(x, super::ping_pong(x, 0.));
+        assert_eq!(f32x2::from_array([-0.8, 0.6]), super::ping_pong(x, 1.));
+
+        // The allpass diffuser keeps the channels apart, so each echo lands on a single side
+        let samplerate = 44100.;
+        let delay = 0.3;
+        let mut params = DelayParams::default();
+        params.mix.smoothed.reset(1.);
+        params.late.smoothed.reset(0.);
+        params.delay.value = delay;
+        params.delay.smoothed.reset(delay);
+        params.ping_pong.value = true;
+        params.diffuser = EnumParam::new("Diffuser", Diffuser::Allpass);
+        let mut reverb = Reverb::new_with_params(Arc::new(params), samplerate);
+        reverb.prepare_block();
+        let period = reverb.loop_delay(samplerate, reverb.params.size.value, delay);
+        let period = (period * samplerate) as usize;
+        let mut input = vec![[0.; 2]; 4 * period];
+        input[0] = [1., 0.];
+        let output = reverb.process_slice(&input);
+        // The diffuser spreads each echo around the loop delay, so the windows are centered on it
+        for pass in 0..4 {
+            let echo =
+                &output[(pass * period).saturating_sub(period / 2)..pass * period + period / 2];
+            let [l, r] = echo
+                .iter()
+                .fold([0f32; 2], |[l, r], [x, y]| [l + x * x, r + y * y]);
+            if pass % 2 == 0 {
+                assert!(l > 10. * r, "pass {pass}: {l} vs {r}");
+            } else {
+                assert!(r > 10. * l, "pass {pass}: {l} vs {r}");
+            }
+        }
+    }
+
+    #[test]
+    fn tone_tilts_the_spectrum() {
+        let samplerate = 44100.;
+        // Gain of the tilt at 100 Hz and 8 kHz, in dB
+        let tilt = |tone: f32| {
+            let params = DelayParams::default();
+            params.tone.smoothed.reset(tone);
+            let mut reverb = Reverb::new_with_params(Arc::new(params), samplerate);
+            reverb.process_slice(&vec![[0.; 2]; samplerate as usize / 10]);
+            [100., 8000.].map(|freq| {
+                let freq = freq / samplerate;
+                let (low, _) = reverb.tilt_low.params.response(freq);
+                let (high, _) = reverb.tilt_high.params.response(freq);
+                util::gain_to_db(low * high)
+            })
+        };
+        let [lows, highs] = tilt(0.);
+        assert!(lows.abs() < 0.1 && highs.abs() < 0.1, "{lows} {highs}");
+        let [lows, highs] = tilt(1.);
+        assert!(lows < -4. && highs > 4., "{lows} {highs}");
+        let [lows, highs] = tilt(-1.);
+        assert!(lows > 4. && highs < -4., "{lows} {highs}");
+    }
+
+    #[test]
+    fn modes_shorten_the_tail() {
+        let samplerate = 44100.;
+        // Share of the impulse response's energy past the first second
+        let tail = |mode: ReverbMode| {
+            let mut params = DelayParams::default();
+            params.mix.smoothed.reset(1.);
+            params.mode = EnumParam::new("Mode", mode);
+            let mut reverb = Reverb::new_with_params(Arc::new(params), samplerate);
+            reverb.prepare_block();
+            let ir = reverb.render_impulse_response(2 * samplerate as usize);
+            let energy =
+                |frames: &[[f32; 2]]| -> f32 { frames.iter().flatten().map(|x| x * x).sum() };
+            energy(&ir[samplerate as usize..]) / energy(&ir)
+        };
+        let reverb = tail(ReverbMode::Reverb);
+        let ambience = tail(ReverbMode::Ambience);
+        let chorus = tail(ReverbMode::Chorus);
+        assert!(reverb > 1e-2, "{reverb}");
+        assert!(ambience < 0.1 * reverb, "{ambience} vs {reverb}");
+        assert!(chorus < 1e-6, "{chorus}");
+    }
+
+    #[test]
+    fn high_band_decays_faster() {
+        let samplerate = 44100.;
+        // Ratio of the energy in the last two seconds to the first one, per band
+        let band_decay = |high_decay: f32| {
+            let params = DelayParams::default();
+            params.mix.smoothed.reset(1.);
+            params.high_decay.smoothed.reset(high_decay);
+            // Leaves the feedback loop alone in the tail, with its highs intact
+            params.late.smoothed.reset(0.);
+            params.damp_high.smoothed.reset(20e3);
+            let mut reverb = Reverb::new_with_params(Arc::new(params), samplerate);
+            reverb.prepare_block();
+            let ir = reverb.render_impulse_response(3 * samplerate as usize);
+            let mut crossover =
+                Crossover::new(LOW_CROSSOVER / samplerate, HIGH_CROSSOVER / samplerate);
+            let mut early = [0.; 3];
+            let mut late = [0.; 3];
+            for (i, frame) in ir.into_iter().enumerate() {
+                let energy = if i < samplerate as usize {
+                    &mut early
+                } else {
+                    &mut late
+                };
+                for (e, band) in energy
+                    .iter_mut()
+                    .zip(crossover.split(f32x2::from_array(frame)))
+                {
+                    *e += (band * band).reduce_sum();
+                }
+            }
+            [late[0] / early[0], late[2] / early[2]]
+        };
+        let [low, high] = band_decay(0.25);
+        let [_, neutral] = band_decay(1.);
+        assert!(high < low, "{high} vs {low}");
+        assert!(high < 0.5 * neutral, "{high} vs {neutral}");
+    }
+
+    #[test]
+    fn bypass_is_click_free() {
+        let samplerate = 44100.;
+        let mut reverb = Reverb::new(samplerate);
+        let mut last = f32x2::splat(0.);
+        for i in 0..samplerate as usize {
+            let input = f32x2::splat(0.5 * f32::sin(TAU * 440. * i as f32 / samplerate));
+            reverb.tick_bypass(samplerate, i >= samplerate as usize / 2);
+            let processed = process(&mut reverb, samplerate, input);
+            let output = reverb.apply_bypass(processed);
+            let jump = (output - last).abs().reduce_max();
+            assert!(jump < 0.1, "jump of {jump} at sample {i}");
+            last = output;
+        }
+        assert_eq!(1., reverb.bypass);
+    }
+
+    #[test]
+    fn bypass_keeps_latency() {
+        let samplerate = 44100.;
+        let mut params = DelayParams::default();
+        params.bypass.value = true;
+        params.oversampling = EnumParam::new("Oversampling", Oversampling::X2);
+        let mut reverb = Reverb::new_with_params(Arc::new(params), samplerate);
+        reverb.bypass = 1.;
+        let mut frames = vec![f32x2::splat(0.); 100];
+        frames[0] = f32x2::splat(1.);
+        reverb.process_in_place(&mut frames);
+        let latency = reverb.latency(false) as usize;
+        assert!(latency > 0);
+        assert_eq!(
+            Some(latency),
+            frames.iter().position(|x| (x[0] - 1.).abs() < 1e-6)
+        );
+    }
+
+    #[test]
+    fn dry_is_delayed_by_latency() {
+        let samplerate = 44100.;
+        let mut reverb = Reverb::new(samplerate);
+        reverb.reverse_latency = 1.;
+        let latency = reverb.latency(true) as usize;
+        let impulse = (0..2 * latency)
+            .map(|i| f32x2::splat(if i == 0 { 1. } else { 0. }))
+            .map(|x| reverb.compensate_latency(x)[0])
+            .position(|x| x == 1.);
+        assert_eq!(Some(latency), impulse);
+        assert_eq!(0, reverb.latency(false));
+
+        // Oversampling delays the dry signal on its own
+        let mut reverb = Reverb::new(samplerate);
+        reverb.oversampler.set_factor(Oversampling::X2);
+        let latency = reverb.latency(false) as usize;
+        assert!(latency > 0);
+        let impulse = (0..2 * latency)
+            .map(|i| f32x2::splat(if i == 0 { 1. } else { 0. }))
+            .map(|x| reverb.compensate_latency(x)[0])
+            .position(|x| x == 1.);
+        assert_eq!(Some(latency), impulse);
+    }
+
+    #[test]
+    fn ramps_keep_their_duration_across_samplerates() {
+        let mut reverb = Reverb::new(96000.);
+        Arc::get_mut(&mut reverb.params).unwrap().mix.value = 0.;
+        reverb.params.mix.smoothed.set_target(96000., 0.);
+        // The ramp timed at 96 kHz would take more than twice as long at 44.1 kHz
+        reverb.set_samplerate(44100.);
+        assert_eq!(0., reverb.params.mix.smoothed.next());
+
+        let mut ramp_seconds = |samplerate: f32, target: f32| {
+            reverb.set_samplerate(samplerate);
+            Arc::get_mut(&mut reverb.params).unwrap().mix.value = target;
+            reverb.params.mix.smoothed.set_target(samplerate, target);
+            let samples = (1..)
+                .find(|_| reverb.params.mix.smoothed.next() == target)
+                .unwrap();
+            samples as f32 / samplerate
+        };
+        let slow = ramp_seconds(44100., 1.);
+        let fast = ramp_seconds(96000., 0.);
+        assert!((slow - fast).abs() < 1. / 44100., "{slow} vs {fast}");
+        assert!((slow - 20e-3).abs() < 1e-3, "{slow}");
+    }
+
+    #[test]
+    fn wet_solo_overrides_mix() {
+        let samplerate = 8000.;
+        let render = |mix: f32, wet_solo: bool| {
+            let mut params = DelayParams::default();
+            params.mix.value = mix;
+            params.mix.smoothed.reset(mix);
+            params.wet_solo.value = wet_solo;
+            let mut reverb = Reverb::new_with_params(Arc::new(params), samplerate);
+            reverb.prepare_block();
+            let mut rng = StdRng::seed_from_u64(0);
+            let input: Vec<[f32; 2]> = (0..samplerate as usize)
+                .map(|_| [rng.gen_range(-0.5..0.5); 2])
+                .collect();
+            let output = reverb.process_slice(&input);
+            (output, reverb.params.mix.value)
+        };
+        let (wet, _) = render(1., false);
+        let (soloed, mix) = render(0.3, true);
+        assert_eq!(wet, soloed);
+        assert_eq!(0.3, mix);
+    }
+
+    #[test]
+    fn sidechain_ducks_wet() {
+        let samplerate = 8000.;
+        let wet_level = |sidechain: f32| {
+            let mut params = DelayParams::default();
+            params.duck_amount.smoothed.reset(1.);
+            params.mix.smoothed.reset(1.);
+            params.duck_source = EnumParam::new("Ducking Source", DuckSource::Sidechain);
+            let mut reverb = Reverb::new_with_params(Arc::new(params), samplerate);
+            reverb.prepare_block();
+            let mut rng = StdRng::seed_from_u64(0);
+            let mut frames: Vec<f32x2> = (0..2 * samplerate as usize)
+                .map(|_| f32x2::splat(rng.gen_range(-0.5..0.5)))
+                .collect();
+            let keys = vec![f32x2::splat(sidechain); frames.len()];
+            reverb.process_frames(None, &mut frames, Some(&keys));
+            rms(&frames[samplerate as usize..])
+        };
+        let open = wet_level(0.);
+        let ducked = wet_level(0.9);
+        assert!(open > 0.01, "{open}");
+        assert!(ducked < 0.2 * open, "{ducked} vs {open}");
+    }
+
+    #[test]
+    fn ducking_recovers() {
+        let samplerate = 44100.;
+        let mut reverb = Reverb::new(samplerate);
+        let loud: Vec<f32> = (0..samplerate as usize / 2)
+            .map(|i| f32::sin(TAU * 440. * i as f32 / samplerate) * 0.8)
+            .map(|x| reverb.duck(samplerate, f32x2::splat(x), 1., 0.25))
+            .collect();
+        // Ducked while the dry signal plays
+        assert!(loud[loud.len() / 2..].iter().all(|&g| g < 0.3));
+
+        let quiet: Vec<f32> = (0..samplerate as usize * 2)
+            .map(|_| reverb.duck(samplerate, f32x2::splat(0.), 1., 0.25))
+            .collect();
+        // Smoothly recovers once it stops
+        assert!(quiet.windows(2).all(|w| w[1] >= w[0]));
+        assert!(quiet[(0.25 * samplerate) as usize] < 0.9);
+        assert!(*quiet.last().unwrap() > 0.99);
+    }
+
+    /// Wet output and gate gain after a burst of noise followed by silence, with the gate on or
+    /// off.
+    fn gated_tail(enabled: bool) -> (Vec<f32x2>, Vec<f32>) {
+        let samplerate = 44100.;
+        let mut reverb = Reverb::new(samplerate);
+        let mut rng = StdRng::seed_from_u64(0);
+        (0..samplerate as usize)
+            .map(|i| {
+                let input = if i < samplerate as usize / 10 {
+                    f32x2::splat(rng.gen_range(-0.5..0.5))
+                } else {
+                    f32x2::splat(0.)
+                };
+                reverb.tick_gate(samplerate, input, enabled, util::db_to_gain(-30.), 0.1);
+                (process(&mut reverb, samplerate, input), reverb.gate)
+            })
+            .unzip()
+    }
+
+    #[test]
+    fn gate_cuts_tail() {
+        let samplerate = 44100.;
+        // Burst, envelope release down to the threshold, hold then fade
+        let release = super::GATE_RELEASE * 30. / 20. * std::f32::consts::LN_10;
+        let closed = ((0.1 + release + 0.1 + super::GATE_FADE) * samplerate) as usize + 1;
+        let (gated, gains) = gated_tail(true);
+        assert!(rms(&gated[..closed]) > 1e-3);
+        assert!(gated[closed..].iter().all(|x| *x == f32x2::splat(0.)));
+        // Without the gate, the tail is still ringing at that point
+        let (ungated, _) = gated_tail(false);
+        assert!(rms(&ungated[closed..]) > 1e-3);
+
+        // The gate fades out over several milliseconds instead of cutting instantly
+        let max_step = 1. / (super::GATE_FADE * samplerate) + 1e-6;
+        for w in gains.windows(2) {
+            assert!(w[0] - w[1] <= max_step, "{} -> {}", w[0], w[1]);
+        }
+    }
+
+    /// Correlation coefficient between the left and right taps of the feedback delay line fed
+    /// with mono noise, with the modulation either decoupled or identical between channels.
+    fn tap_correlation(decoupled: bool) -> f32 {
+        let samplerate = 44100.;
+        let mut reverb = Reverb::new(samplerate);
+        if !decoupled {
+            reverb.lfo[1] = reverb.lfo[0].clone();
+        }
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let (mut lr, mut ll, mut rr) = (0., 0., 0.);
+        for _ in 0..samplerate as usize {
+            reverb
+                .delay
+                .push_next(f32x2::splat(rng.gen_range(-1. ..1.)));
+            let delay = reverb.modulated_delay(0.2, 1.0, LfoShape::Sine);
+            reverb.tick_lfo(samplerate, 2.0);
+            let [l, r] = reverb
+                .delay
+                .get(delay * f32x2::splat(samplerate))
+                .to_array();
+            lr += l * r;
+            ll += l * l;
+            rr += r * r;
+        }
+        lr / f32::sqrt(ll * rr)
+    }
+
+    #[test]
+    fn decoupled_modulation_decorrelates() {
+        assert!(tap_correlation(false) > 0.99);
+        assert!(tap_correlation(true) < 0.5);
+    }
+
+    #[test]
+    fn width_unity_is_transparent() {
+        let x = f32x2::from_array([0.8, -0.3]);
+        assert_eq!(x, stereo_width(x, 1.));
+    }
+
+    #[test]
+    fn width_hard_panned() {
+        let left = f32x2::from_array([1., 0.]);
+        let right = f32x2::from_array([0., 1.]);
+
+        assert_eq!(f32x2::splat(0.5), stereo_width(left, 0.));
+        assert_eq!(f32x2::splat(0.5), stereo_width(right, 0.));
+        assert_eq!(f32x2::from_array([1.5, -0.5]), stereo_width(left, 2.));
+        assert_eq!(f32x2::from_array([-0.5, 1.5]), stereo_width(right, 2.));
+    }
+
+    #[test]
+    fn limiter_only_engages_past_unity_feedback() {
+        let samplerate = 44100.;
+        let mut reverb = Reverb::new(samplerate);
+        let loud = f32x2::splat(0.9);
+        assert_eq!(loud, reverb.limit(samplerate, false, loud));
+        let limited = reverb.limit(samplerate, true, loud);
+        assert!(
+            (limited[0] - super::LOOP_CEILING).abs() < 1e-6,
+            "{limited:?}"
+        );
+    }
+
+    #[test]
+    fn max_feedback_stays_bounded() {
+        let samplerate = 44100.;
+        let mut reverb = Reverb::new(samplerate);
+        // Unlike tanh, the hard clipper lets the loop grow up to full scale on its own
+        reverb.saturation = Saturation::HardClip;
+        let frame = FrameParams {
+            size: 1.0,
+            feedback: 1.25,
+            rt60: 20.0,
+            mod_depth: 1.0,
+            late: 0.0,
+            ..frame()
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut energy = 0.;
+        for i in 0..10 * samplerate as usize {
+            let input = if i < samplerate as usize / 10 {
+                f32x2::splat(rng.gen_range(-1.0..1.0))
+            } else {
+                f32x2::splat(0.)
+            };
+            let out = reverb.next_sample(samplerate, &frame, input);
+            for x in out.to_array() {
+                assert!(x.is_finite());
+                assert!(x.abs() <= 2., "{x} out of bounds at sample {i}");
+            }
+            if i >= 9 * samplerate as usize {
+                energy += (out * out).reduce_sum() / 2.;
+            }
+        }
+        let rms = (energy / samplerate).sqrt();
+        assert!(rms > 0.01, "the loop died out, RMS {rms}");
+        assert!(
+            rms < super::LOOP_CEILING,
+            "the loop kept growing, RMS {rms}"
+        );
+    }
+
+    #[test]
+    fn synced_mod_rate_follows_tempo() {
+        assert_eq!(2., mod_rate(0.3, Some(NoteDivision::Quarter), Some(120.)));
+        assert_eq!(0.5, mod_rate(0.3, Some(NoteDivision::Whole), Some(120.)));
+        assert_eq!(
+            3.,
+            mod_rate(0.3, Some(NoteDivision::TripletEighth), Some(60.))
+        );
+        // Free rate when not synced, or without a tempo
+        assert_eq!(0.3, mod_rate(0.3, None, Some(120.)));
+        assert_eq!(0.3, mod_rate(0.3, Some(NoteDivision::Quarter), None));
+
+        // A quarter-note rate at 120 BPM starts a new cycle every half second
+        let samplerate = 48e3;
+        let mut lfo = Lfo::new();
+        let rate = mod_rate(0.3, Some(NoteDivision::Quarter), Some(120.));
+        let mut cycles = 0;
+        let mut last = lfo.value(LfoShape::Sine);
+        for _ in 0..(2.25 * samplerate) as usize {
+            lfo.tick(samplerate, rate);
+            let value = lfo.value(LfoShape::Sine);
+            if last < 0. && value >= 0. {
+                cycles += 1;
+            }
+            last = value;
+        }
+        assert_eq!(4, cycles);
+    }
+
+    #[test]
+    fn synced_delay_matches_note_length() {
+        assert_eq!(0.5, NoteDivision::Quarter.seconds(120.));
+        assert_eq!(0.375, NoteDivision::DottedEighth.seconds(120.));
+        assert_eq!(2., NoteDivision::Whole.seconds(120.));
+
+        let samplerate = 48e3;
+        let mut reverb = Reverb::new(samplerate);
+        reverb.delay.push_next(f32x2::splat(1.));
+        let echo = (1..samplerate as usize)
+            .position(|_| {
+                let delay = f32x2::splat(NoteDivision::Quarter.seconds(120.));
+                let out = reverb.read_delay(samplerate, 0.5, delay);
+                reverb.delay.push_next(f32x2::splat(0.));
+                out[0] == 1.
+            })
+            .map(|i| i + 1);
+        assert_eq!(Some(samplerate as usize / 2), echo);
+    }
+
+    #[test]
+    fn synced_delay_crossfades_on_tempo_change() {
+        let samplerate = 48e3;
+        let mut reverb = Reverb::new(samplerate);
+        // Silence at the new delay time, constant signal at the old one
+        for i in 0..samplerate as usize - 2 {
+            let x = if i < samplerate as usize / 2 { 0. } else { 1. };
+            reverb.delay.push_next(f32x2::splat(x));
+        }
+        reverb.set_base_delay(0.25, true);
+        reverb.retime = 0.;
+        reverb.set_base_delay(0.75, true);
+
+        let fade = (super::RETIME_FADE * samplerate) as usize;
+        let out: Vec<f32> = (0..2 * fade)
+            .map(|_| {
+                let delay = f32x2::splat(reverb.base_delay);
+                reverb.read_delay(samplerate, 0.5, delay)[0]
+            })
+            .collect();
+        assert_eq!(1., out[0]);
+        assert!(out
+            .windows(2)
+            .all(|w| w[1] <= w[0] && w[0] - w[1] < 2. / fade as f32));
+        assert_eq!(0., out[fade + 1]);
+    }
+
+    #[test]
+    fn retime_waits_for_running_fade() {
+        let samplerate = 48e3;
+        let mut reverb = Reverb::new(samplerate);
+        reverb.set_base_delay(0.25, true);
+        reverb.retime = 0.;
+        reverb.set_base_delay(0.5, true);
+
+        let fade = (super::RETIME_FADE * samplerate) as usize;
+        for _ in 0..fade / 2 {
+            reverb.read_delay(samplerate, 0.5, f32x2::splat(reverb.base_delay));
+        }
+        let retime = reverb.retime;
+        reverb.set_base_delay(0.75, true);
+        assert_eq!(0.5, reverb.base_delay);
+        assert_eq!(retime, reverb.retime);
+        assert_eq!(-0.25, reverb.retime_offset);
+
+        while reverb.retime > 0. {
+            reverb.read_delay(samplerate, 0.5, f32x2::splat(reverb.base_delay));
+        }
+        reverb.set_base_delay(0.75, true);
+        assert_eq!(0.75, reverb.base_delay);
+        assert_eq!(1., reverb.retime);
+        assert_eq!(-0.25, reverb.retime_offset);
+    }
+
+    /// Share of the energy of a 1 kHz tone read from the feedback delay line that is moved away
+    /// from 1 kHz while the delay time goes from 0.2 to 0.3 seconds, sliding over the smoothing
+    /// ramp or jumping.
+    fn retime_artifacts(crossfade: bool) -> f32 {
+        let samplerate = 48e3;
+        let mut reverb = Reverb::new(samplerate);
+        Arc::get_mut(&mut reverb.params)
+            .unwrap()
+            .delay_crossfade
+            .value = crossfade;
+        let tone = |i: usize| f32::sin(TAU * 1e3 * i as f32 / samplerate);
+        let len = 2 * samplerate as usize;
+        for i in 0..len {
+            reverb.delay.push_next(f32x2::splat(tone(i)));
+        }
+        reverb.set_base_delay(0.2, false);
+
+        // Same length as the smoothing of the delay parameter
+        let ramp = (0.2 * samplerate) as usize;
+        let out: Vec<f32> = (0..ramp)
+            .map(|n| {
+                let delay = if crossfade {
+                    0.3
+                } else {
+                    0.2 + 0.1 * (n + 1) as f32 / ramp as f32
+                };
+                reverb.set_base_delay(delay, false);
+                let delay = f32x2::splat(reverb.base_delay);
+                let out = reverb.read_delay(samplerate, 0.5, delay)[0];
+                reverb.delay.push_next(f32x2::splat(tone(len + n)));
+                out
+            })
+            .collect();
+
+        let (re, im) = out.iter().enumerate().fold((0., 0.), |(re, im), (i, x)| {
+            let phase = TAU * 1e3 * i as f32 / samplerate;
+            (re + x * phase.cos(), im - x * phase.sin())
+        });
+        let tone_energy = 2. * (re * re + im * im) / (ramp * ramp) as f32;
+        let energy = out.iter().map(|x| x * x).sum::<f32>() / ramp as f32;
+        1. - tone_energy / energy
+    }
+
+    #[test]
+    fn delay_crossfade_reduces_repitching() {
+        let slide = retime_artifacts(false);
+        let crossfade = retime_artifacts(true);
+        assert!(slide > 0.5, "{slide}");
+        assert!(crossfade < 0.1 * slide, "{crossfade} vs {slide}");
+
+        // Large changes skip the smoothing ramp
+        let mut reverb = Reverb::new(44100.);
+        let params = Arc::get_mut(&mut reverb.params).unwrap();
+        params.delay_crossfade.value = true;
+        params.delay.smoothed.reset(0.2);
+        params.delay.value = 0.8;
+        reverb.prepare_block();
+        assert_eq!(0.8, reverb.params.delay.smoothed.previous_value());
+    }
+
+    /// Ratio of the energy of the first difference to the energy of the signal, rising with the
+    /// amount of high frequencies in it.
+    fn brightness(samples: &[f32x2]) -> f32 {
+        let diff: Vec<f32x2> = samples.windows(2).map(|w| w[1] - w[0]).collect();
+        rms(&diff) / rms(samples)
+    }
+
+    /// Tail of the reverb at full shimmer after a low sine burst, with the shimmer lowpassed at
+    /// `shimmer_tone` Hz.
+    fn shimmer_tail(shimmer_feedback: bool, shimmer_tone: Option<f32>) -> Vec<f32x2> {
+        let samplerate = 44100.;
+        let mut reverb = Reverb::new(samplerate);
+        if let Some(fc) = shimmer_tone {
+            reverb.shimmer_filter.params =
+                BiquadParams::lowpass(f32x2::splat(fc / samplerate), f32x2::splat(FRAC_1_SQRT_2));
+        }
+        let frame = FrameParams {
+            feedback: 0.8,
+            mod_depth: 0.0,
+            pitch_amt: 1.0,
+            shimmer_feedback,
+            late: 0.0,
+            ..frame()
+        };
+        let out: Vec<f32x2> = (0..3 * samplerate as usize)
+            .map(|i| {
+                let input = if i < samplerate as usize / 10 {
+                    f32x2::splat(0.5 * f32::sin(TAU * 100. * i as f32 / samplerate))
+                } else {
+                    f32x2::splat(0.)
+                };
+                reverb.next_sample(samplerate, &frame, input)
+            })
+            .collect();
+        out[2 * samplerate as usize..].to_vec()
+    }
+
+    #[test]
+    fn shimmer_feedback_stacks_octaves() {
+        let fed_back = shimmer_tail(true, None);
+        let parallel = shimmer_tail(false, None);
+        assert!(rms(&parallel) > 1e-4);
+        let (fed_back, parallel) = (brightness(&fed_back), brightness(&parallel));
+        assert!(fed_back > 2. * parallel, "{fed_back} vs {parallel}");
+    }
+
+    #[test]
+    fn shimmer_tone_darkens_tail() {
+        let open = shimmer_tail(true, None);
+        let filtered = shimmer_tail(true, Some(3e3));
+        assert!(rms(&filtered) > 1e-4);
+        let (open, filtered) = (brightness(&open), brightness(&filtered));
+        assert!(filtered < 0.8 * open, "{filtered} vs {open}");
+    }
+
+    #[test]
+    fn shimmer_tone_bypasses_filter_at_top() {
+        let mut params = DelayParams::default();
+        params.shimmer_tone.value = super::SHIMMER_TONE_OPEN;
+        params.shimmer_tone.smoothed.reset(super::SHIMMER_TONE_OPEN);
+        let mut reverb = Reverb::new_with_params(Arc::new(params), 44100.);
+        reverb.process_slice(&[[0.; 2]; 16]);
+        assert!(reverb.shimmer_open);
+
+        Arc::get_mut(&mut reverb.params)
+            .unwrap()
+            .shimmer_tone
+            .smoothed
+            .reset(19e3);
+        reverb.process_slice(&[[0.; 2]; 16]);
+        assert!(!reverb.shimmer_open);
+    }
+
+    #[test]
+    fn allpass_diffuser_decays() {
+        let samplerate = 44100.;
+        let mut reverb = Reverb::new(samplerate);
+        reverb.set_diffuser(Diffuser::Allpass);
+        let out: Vec<f32x2> = (0..3 * samplerate as usize)
+            .map(|i| {
+                let input = f32x2::splat(if i == 0 { 1. } else { 0. });
+                process(&mut reverb, samplerate, input)
+            })
+            .collect();
+        assert!(out
+            .iter()
+            .all(|x| x.to_array().iter().all(|x| x.is_finite())));
+        let seconds: Vec<f32> = out.chunks(samplerate as usize).map(rms).collect();
+        assert!(seconds[0] > 1e-4);
+        assert!(seconds[2] < 0.1 * seconds[0], "{seconds:?}");
+    }
+
+    #[test]
+    fn input_low_cut_removes_lows() {
+        let samplerate = 44100.;
+        // Low enough to be cut, high enough to get past the DC blocker on the wet signal
+        let freq = 20.;
+        let wet_level = |low_cut: Option<f32>| {
+            let mut reverb = Reverb::new(samplerate);
+            if let Some(fc) = low_cut {
+                reverb.input_low.params = BiquadParams::highpass(
+                    f32x2::splat(fc / samplerate),
+                    f32x2::splat(FRAC_1_SQRT_2),
+                );
+            }
+            let out: Vec<f32x2> = (0..samplerate as usize)
+                .map(|i| {
+                    let x = 0.5 * f32::sin(TAU * freq * i as f32 / samplerate);
+                    process(&mut reverb, samplerate, f32x2::splat(x))
+                })
+                .collect();
+            rms(&out[out.len() / 2..])
+        };
+        let open = wet_level(None);
+        let cut = wet_level(Some(200.));
+        assert!(open > 0.1, "{open}");
+        assert!(cut < 2e-2 * open, "{cut} vs {open}");
+    }
+
+    #[test]
+    fn input_filters_follow_cutoffs() {
+        let samplerate = 44100.;
+        let params = DelayParams::default();
+        params.input_low_cut.smoothed.reset(200.);
+        params.input_high_cut.smoothed.reset(5e3);
+        let mut reverb = Reverb::new_with_params(Arc::new(params), samplerate);
+        reverb.process_slice(&vec![[0.; 2]; samplerate as usize / 10]);
+        for (filter, fc) in [(&reverb.input_low, 200.), (&reverb.input_high, 5e3)] {
+            let (gain, _) = filter.params.response(fc / samplerate);
+            assert!((util::gain_to_db(gain) + 3.).abs() < 0.1, "{fc} Hz: {gain}");
+        }
+    }
+
+    /// Largest difference between the channels of the output of a mono tank at the given width.
+    fn mono_tank_spread(width: f32) -> f32 {
+        let samplerate = 44100.;
+        let mut reverb = Reverb::new(samplerate);
+        let frame = FrameParams {
+            width,
+            mono_tank: true,
+            ..frame()
+        };
+        let mut rng = StdRng::seed_from_u64(0);
+        (0..samplerate as usize)
+            .map(|_| {
+                let input = f32x2::from_array([rng.gen_range(-1. ..1.), 0.]);
+                let out = reverb.next_sample(samplerate, &frame, input);
+                let [l, r] = out.to_array();
+                (l - r).abs()
+            })
+            .fold(0., f32::max)
+    }
+
+    #[test]
+    fn mono_tank_is_centered() {
+        assert_eq!(0., mono_tank_spread(0.5));
+        assert_eq!(0., mono_tank_spread(1.0));
+    }
+
+    #[test]
+    fn mono_tank_widens_past_unity() {
+        let (wide, wider) = (mono_tank_spread(1.5), mono_tank_spread(2.0));
+        assert!(wide > 1e-3, "{wide}");
+        assert!(wider > wide, "{wider} vs {wide}");
+    }
+
+    /// Energy of everything but the fundamental after saturating a sine at the given drive,
+    /// relative to the energy of the fundamental.
+    fn harmonic_energy(saturation: Saturation, drive_db: f32) -> f32 {
+        let (samplerate, freq) = (48e3, 1e3);
+        let out: Vec<f32> = (0..4800)
+            .map(|i| {
+                let x = 0.5 * f32::sin(TAU * freq * i as f32 / samplerate);
+                saturation.process(f32x2::splat(x), util::db_to_gain(drive_db))[0]
+            })
+            .collect();
+        let (re, im) = out.iter().enumerate().fold((0., 0.), |(re, im), (i, x)| {
+            let phase = TAU * freq * i as f32 / samplerate;
+            (re + x * phase.cos(), im - x * phase.sin())
+        });
+        let fundamental = 2. * (re * re + im * im) / (out.len() * out.len()) as f32;
+        let total = out.iter().map(|x| x * x).sum::<f32>() / out.len() as f32;
+        assert!(total.is_finite(), "{saturation:?} at {drive_db} dB");
+        (total - fundamental).max(0.) / fundamental
+    }
+
+    #[test]
+    fn drive_adds_harmonics() {
+        for saturation in [
+            Saturation::Tanh,
+            Saturation::SoftClip,
+            Saturation::HardClip,
+            Saturation::Tube,
+        ] {
+            let energies: Vec<f32> = [0., 6., 12., 18., 24.]
+                .into_iter()
+                .map(|drive| harmonic_energy(saturation, drive))
+                .collect();
+            for pair in energies.windows(2) {
+                assert!(pair[1] >= pair[0] - 1e-6, "{saturation:?}: {energies:?}");
+            }
+            assert!(
+                energies[4] > energies[0] + 1e-3,
+                "{saturation:?}: {energies:?}"
+            );
+        }
+        assert!(harmonic_energy(Saturation::None, 24.) < 1e-6);
+    }
+
+    #[test]
+    fn saturation_has_unity_gain_at_low_level() {
+        let x = 1e-3;
+        for saturation in [
+            Saturation::None,
+            Saturation::Tanh,
+            Saturation::SoftClip,
+            Saturation::HardClip,
+            Saturation::Tube,
+        ] {
+            for drive in [1., 2.] {
+                let y = saturation.process(f32x2::splat(x), drive)[0];
+                assert!(
+                    (y / x - 1.).abs() < 1e-3,
+                    "{saturation:?} at drive {drive}: {y}"
+                );
+            }
+        }
+    }
+
+    /// Sample at which an impulse in the feedback delay line is read back at the given size.
+    fn loop_echo(samplerate: f32, size: f32, delay: f32) -> usize {
+        let mut reverb = Reverb::new(samplerate);
+        reverb.delay.push_next(f32x2::splat(1.));
+        (1..reverb.delay.len())
+            .position(|_| {
+                let out = reverb.read_delay(samplerate, size, f32x2::splat(delay));
+                reverb.delay.push_next(f32x2::splat(0.));
+                out[0] == 1.
+            })
+            .unwrap()
+            + 1
+    }
+
+    #[test]
+    fn stereo_offset_delays_right_channel() {
+        let samplerate = 8000.;
+        let mut reverb = Reverb::new(samplerate);
+        reverb.stereo_offset = 5e-3;
+        reverb.delay.push_next(f32x2::splat(1.));
+        let mut echoes = [None; 2];
+        for n in 1..reverb.delay.len() {
+            let out = reverb.read_delay(samplerate, 0.5, f32x2::splat(0.2));
+            reverb.delay.push_next(f32x2::splat(0.));
+            for (echo, out) in echoes.iter_mut().zip(out.to_array()) {
+                if out == 1. {
+                    echo.get_or_insert(n);
+                }
+            }
+        }
+        // 0.2 s at 8 kHz, with the right channel 5 ms later
+        assert_eq!([Some(1600), Some(1640)], echoes);
+    }
+
+    #[test]
+    fn size_stretches_loop() {
+        let sizes = [0.25, 0.5, 0.75, 1.];
+        let echoes = sizes.map(|size| loop_echo(8000., size, 0.2));
+        // 0.2 s at 8 kHz, unscaled at the default size
+        assert_eq!([1000, 1600, 2200, 2800], echoes);
+
+        let samplerate = 44100.;
+        let reverb = Reverb::new(samplerate);
+        let loop_delays = sizes.map(|size| reverb.loop_delay(samplerate, size, 0.2));
+        let steps: Vec<f32> = loop_delays.windows(2).map(|w| w[1] - w[0]).collect();
+        for step in &steps {
+            assert!(*step > 0., "{loop_delays:?}");
+            assert!((step - steps[0]).abs() < 1e-4, "{loop_delays:?}");
+        }
+    }
+
+    #[test]
+    fn legacy_size_keeps_delay() {
+        let mut params = DelayParams::default();
+        params.size_scales_delay.value = false;
+        let reverb = Reverb::new_with_params(Arc::new(params), 8000.);
+        assert_eq!([1., 1.], [0.25, 1.].map(|size| reverb.room_scale(size)));
+    }
+
+    #[test]
+    fn pitch_window_is_short() {
+        let samplerate = 48e3;
+        let reverb = Reverb::new(samplerate);
+        // The read heads sit half a window behind the input on average
+        let window = 2. * reverb.pitch.latency() / samplerate;
+        assert!(window <= PITCH_WINDOW + 1e-3, "{window}");
+    }
+
+    #[test]
+    fn longest_delay_fits_at_192k() {
+        let samplerate = 192e3;
+        let reverb = Reverb::new(samplerate);
+        let longest = MAX_DELAY * MAX_ROOM_SCALE;
+        let len = reverb.delay.len() as f32 / samplerate;
+        assert!(
+            len >= longest + MAX_STEREO_OFFSET + DELAY_MOD_DEPTH,
+            "{len}"
+        );
+        assert!(
+            len <= longest + MAX_STEREO_OFFSET + DELAY_HEADROOM + 1e-3,
+            "{len}"
+        );
+        assert_eq!(
+            (longest * samplerate) as usize,
+            loop_echo(samplerate, 1., MAX_DELAY)
+        );
+    }
+
+    #[test]
+    fn impulse_response_decays() {
+        let samplerate = 44100.;
+        let mut reverb = Reverb::new(samplerate);
+        // Starts from a reverb already holding a tail, which the render clears
+        for _ in 0..1000 {
+            process(&mut reverb, samplerate, f32x2::splat(1.));
+        }
+        let ir = reverb.render_impulse_response(3 * samplerate as usize);
+        assert_eq!(3 * samplerate as usize, ir.len());
+        let energy: Vec<f32> = ir
+            .chunks(samplerate as usize)
+            .map(|chunk| chunk.iter().flatten().map(|x| x * x).sum())
+            .collect();
+        assert!(energy.iter().all(|e| e.is_finite()), "{energy:?}");
+        assert!(energy[1] < energy[0] && energy[2] < energy[1], "{energy:?}");
+        assert!(energy[2] > 0., "{energy:?}");
+    }
+
+    #[test]
+    fn noise_output_level() {
+        let samplerate = 44100.;
+        let mut reverb = Reverb::new(samplerate);
+        reverb.diffusion = Early::with_rng(samplerate, &mut StdRng::seed_from_u64(1));
+        let mut rng = StdRng::seed_from_u64(0);
+        let input: Vec<[f32; 2]> = (0..2 * samplerate as usize)
+            .map(|_| [rng.gen_range(-0.5..0.5), rng.gen_range(-0.5..0.5)])
+            .collect();
+        let output = reverb.process_slice(&input);
+        assert_eq!(input.len(), output.len());
+        let tail: Vec<f32x2> = output[samplerate as usize..]
+            .iter()
+            .map(|&frame| f32x2::from_array(frame))
+            .collect();
+        // Measured with the default parameters; a change beyond the tolerance means the overall
+        // level of the reverb has shifted
+        let level = rms(&tail);
+        assert!((level - 0.1077).abs() < 0.05 * 0.1077, "{level}");
+    }
+
+    #[test]
+    fn seed_reproduces_tail() {
+        let samplerate = 8000.;
+        let render = |seed| {
+            let mut params = DelayParams::default();
+            params.seed.value = seed;
+            let mut reverb = Reverb::new_with_params(Arc::new(params), samplerate);
+            reverb.render_impulse_response(samplerate as usize)
+        };
+        assert_eq!(render(42), render(42));
+        assert_ne!(render(42), render(43));
+    }
+
+    #[test]
+    fn reset_silences_tail() {
+        let samplerate = 8000.;
+        let mut reverb = Reverb::new(samplerate);
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut noise: Vec<f32x2> = (0..samplerate as usize)
+            .map(|_| f32x2::splat(rng.gen_range(-1.0..1.0)))
+            .collect();
+        reverb.process_in_place(&mut noise);
+        assert!(rms(&noise) > 0.01);
+
+        reverb.reset();
+        let mut silence = vec![f32x2::splat(0.); samplerate as usize];
+        reverb.process_in_place(&mut silence);
+        assert!(silence.iter().all(|x| *x == f32x2::splat(0.)));
+    }
+
+    #[test]
+    fn samplerate_change_rebuilds() {
+        let mut reverb = Reverb::new(44100.);
+        let len = reverb.delay.len();
+        let mut frames = vec![f32x2::splat(1.); 100];
+        reverb.process_in_place(&mut frames);
+        let tail = |reverb: &mut Reverb| {
+            let mut frames = vec![f32x2::splat(0.); 44100];
+            reverb.process_in_place(&mut frames);
+            rms(&frames)
+        };
+
+        // Nothing is rebuilt, so the tail is still there until the reset
+        reverb.set_samplerate(44100.);
+        assert_eq!(len, reverb.delay.len());
+        assert!(tail(&mut reverb) > 0.);
+        reverb.reset();
+        assert_eq!(len, reverb.delay.len());
+        assert_eq!(0., tail(&mut reverb));
+
+        reverb.set_samplerate(48000.);
+        assert!(reverb.delay.len() > len);
+    }
+
+    #[test]
+    fn panic_clears_once() {
+        let samplerate = 8000.;
+        let mut reverb = Reverb::new(samplerate);
+        let block = |reverb: &mut Reverb, input: f32| {
+            let mut frames = vec![f32x2::splat(0.); samplerate as usize];
+            frames[0] = f32x2::splat(input);
+            reverb.process_in_place(&mut frames);
+            frames
+        };
+        block(&mut reverb, 1.);
+
+        Arc::get_mut(&mut reverb.params).unwrap().panic.value = true;
+        assert!(block(&mut reverb, 0.)
+            .iter()
+            .all(|x| *x == f32x2::splat(0.)));
+        // Leaving the parameter on lets the reverb play again
+        block(&mut reverb, 1.);
+        assert!(rms(&block(&mut reverb, 0.)) > 1e-4);
+    }
+
+    #[test]
+    fn recovers_from_nan() {
+        let samplerate = 8000.;
+        let mut reverb = Reverb::new(samplerate);
+        let impulse = |reverb: &mut Reverb| {
+            let mut frames = vec![f32x2::splat(0.); samplerate as usize];
+            frames[0] = f32x2::splat(1.);
+            reverb.process_in_place(&mut frames);
+            frames
+        };
+        impulse(&mut reverb);
+
+        for _ in 0..reverb.delay.len() {
+            reverb.delay.push_next(f32x2::splat(f32::NAN));
+        }
+        let mut block = vec![f32x2::splat(0.); super::MAX_BLOCK_SIZE];
+        reverb.process_in_place(&mut block);
+        assert!(block.iter().all(|x| *x == f32x2::splat(0.)));
+        // The state was cleared, so the reverb plays again from the next block on
+        let frames = impulse(&mut reverb);
+        assert!(super::all_finite(&frames));
+        assert!(rms(&frames) > 1e-4);
+    }
+
+    #[test]
+    fn block_size_does_not_change_output() {
+        let samplerate = 8000.;
+        let mut rng = StdRng::seed_from_u64(0);
+        let input: Vec<f32x2> = (0..1000)
+            .map(|_| f32x2::from_array([rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0)]))
+            .collect();
+
+        let mut block = input.clone();
+        Reverb::new(samplerate).process_in_place(&mut block);
+        let mut reverb = Reverb::new(samplerate);
+        let mut per_sample = input;
+        for frame in per_sample.iter_mut() {
+            reverb.process_in_place(std::slice::from_mut(frame));
+        }
+        assert_eq!(block, per_sample);
+    }
+
+    #[test]
+    fn wet_has_no_dc() {
+        let samplerate = 44100.;
+        let mut reverb = Reverb::new(samplerate);
+        let mut rng = StdRng::seed_from_u64(0);
+        let out: Vec<f32x2> = (0..3 * samplerate as usize)
+            .map(|_| {
+                let input = f32x2::splat(0.3 + rng.gen_range(-0.1..0.1));
+                process(&mut reverb, samplerate, input)
+            })
+            .collect();
+        let tail = &out[2 * samplerate as usize..];
+        let mean = tail.iter().sum::<f32x2>() / f32x2::splat(tail.len() as f32);
+        for mean in mean.to_array() {
+            assert!(mean.abs() < 1e-2, "{mean}");
+        }
+    }
+
+    #[test]
+    fn freeze_holds_tail() {
+        let samplerate = 44100.;
+        let mut reverb = Reverb::new(samplerate);
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..samplerate as usize {
+            let input = f32x2::from_array([rng.gen(), rng.gen()]) - f32x2::splat(0.5);
+            process(&mut reverb, samplerate, input);
+        }
+
+        let windows: Vec<f32> = (0..4)
+            .map(|_| {
+                let out: Vec<_> = (0..samplerate as usize)
+                    .map(|_| {
+                        reverb.tick_freeze(samplerate, true);
+                        process(&mut reverb, samplerate, f32x2::splat(0.))
+                    })
+                    .collect();
+                rms(&out)
+            })
+            .collect();
+
+        let first = windows[0];
+        assert!(first > 1e-3);
+        for rms in windows {
+            assert!(
+                (0.5 * first..2. * first).contains(&rms),
+                "{rms} drifted from {first}"
+            );
+        }
+    }
+
+    #[test]
+    fn midi_note_holds_freeze() {
+        let samplerate = 8000.;
+        let mut reverb = Reverb::new(samplerate);
+        let mut frames = vec![f32x2::splat(0.); samplerate as usize];
+        frames[0] = f32x2::splat(1.);
+        reverb.process_in_place(&mut frames);
+        assert_eq!(0., reverb.freeze);
+
+        reverb.midi.handle(note(true, FREEZE_NOTE));
+        let mut frames = vec![f32x2::splat(0.); samplerate as usize];
+        reverb.process_in_place(&mut frames);
+        assert_eq!(1., reverb.freeze);
+        assert!(rms(&frames[frames.len() / 2..]) > 1e-3);
+
+        // Fades back out over the same crossfade once the note is released
+        reverb.midi.handle(note(false, FREEZE_NOTE));
+        let mut frames = vec![f32x2::splat(0.); (super::FREEZE_FADE * samplerate) as usize + 1];
+        reverb.process_in_place(&mut frames);
+        assert_eq!(0., reverb.freeze);
+    }
+
+    #[test]
+    fn midi_reverse_keeps_latency() {
+        let samplerate = 8000.;
+        let mut reverb = Reverb::new(samplerate);
+        reverb.midi.handle(note(true, REVERSE_NOTE));
+        let mut frames = vec![f32x2::splat(0.); samplerate as usize / 10];
+        reverb.process_in_place(&mut frames);
+        assert_eq!(1., reverb.reverse);
+        assert_eq!(0., reverb.reverse_latency);
+
+        // Held notes are dropped along with the rest of the state
+        reverb.reset();
+        assert!(!reverb.reversed());
+    }
+
+    #[test]
+    fn legacy_state_is_migrated() {
+        let mut params = DelayParams::default();
+        params.size.value = 0.8;
+        params.pitch_amt.value = 0.6;
+        params.deserialize_fields(&BTreeMap::new());
+        apply_loaded_state(&params, 44100.);
+
+        assert_eq!(1.0, params.mix.value);
+        assert_eq!(0.0, params.late.value);
+        assert_eq!(0.8, params.size.value);
+        assert!(!params.size_scales_delay.value);
+        assert!(!params.limiter.value);
+        assert!(!params.stage_mod_scaling.value);
+        assert_eq!(20e3, params.shimmer_tone.value);
+        assert_eq!(0.0, params.pitch_amt.value);
+        assert_eq!(super::STATE_VERSION, params.version.get());
+    }
+
+    #[test]
+    fn current_state_is_untouched() {
+        let mut params = DelayParams::default();
+        params.late.value = 0.3;
+        params.mix.value = 0.4;
+        params.deserialize_fields(&params.serialize_fields());
+        apply_loaded_state(&params, 44100.);
+
+        assert_eq!(0.3, params.late.value);
+        assert_eq!(0.4, params.mix.value);
+    }
+
+    #[test]
+    fn wet_solo_is_not_loaded() {
+        let mut params = DelayParams::default();
+        params.wet_solo.value = true;
+        params.deserialize_fields(&params.serialize_fields());
+        apply_loaded_state(&params, 44100.);
+
+        assert!(!params.wet_solo.value);
+    }
+}
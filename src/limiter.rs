@@ -0,0 +1,126 @@
+// Copyright (c) 2022 solarliner
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use std::f32::consts::LN_10;
+
+use nih_plug::prelude::*;
+
+use crate::simd::{f32x2, Simd};
+use crate::simdmath::simd_f32log;
+
+/// Width of the soft knee, centered on the ceiling, in dB.
+const KNEE: f32 = 6.;
+/// Time constant of the gain reduction engaging, in seconds.
+const ATTACK: f32 = 1e-3;
+/// Time constant of the gain reduction letting go, in seconds.
+const RELEASE: f32 = 150e-3;
+
+/// Stereo-linked soft-knee peak limiter, without lookahead.
+///
+/// Levels well below the ceiling pass through untouched, and the gain reduction eases in across
+/// the knee. The attack is fast rather than instant, so the start of a transient would get past
+/// it; whatever is still over the ceiling is pulled down to it on the spot instead, hard clipping
+/// the first samples of the transient.
+#[derive(Debug, Clone)]
+pub struct Limiter {
+    /// Current gain reduction, in dB.
+    gain_db: f32,
+    attack: f32,
+    release: f32,
+}
+
+impl Limiter {
+    pub fn new(samplerate: f32) -> Self {
+        Self {
+            gain_db: 0.,
+            attack: 1. - f32::exp(-1. / (ATTACK * samplerate)),
+            release: 1. - f32::exp(-1. / (RELEASE * samplerate)),
+        }
+    }
+
+    /// Releases any gain reduction.
+    pub fn reset(&mut self) {
+        self.gain_db = 0.;
+    }
+
+    /// Limits the next sample to `ceiling`, given as a linear gain.
+    pub fn next_sample(&mut self, ceiling: f32, x: f32x2) -> f32x2 {
+        let target = gain_reduction(util::gain_to_db(ceiling), x).reduce_min();
+        let coeff = if target < self.gain_db {
+            self.attack
+        } else {
+            self.release
+        };
+        self.gain_db += (target - self.gain_db) * coeff;
+
+        let peak = x.abs().reduce_max();
+        let gain = util::db_to_gain(self.gain_db).min(ceiling / peak);
+        x * Simd::splat(gain)
+    }
+}
+
+/// Gain reduction of the knee for each lane, in dB: none below the knee, then curving down so
+/// that the level comes out at the ceiling past it.
+fn gain_reduction(ceiling_db: f32, x: f32x2) -> f32x2 {
+    let level = simd_f32log(x.abs()) * Simd::splat(20. / LN_10);
+    let over = level - Simd::splat(ceiling_db - KNEE / 2.);
+    let knee = -over * over / Simd::splat(2. * KNEE);
+    let above = Simd::splat(ceiling_db) - level;
+    let reduction = over.lanes_lt(Simd::splat(KNEE)).select(knee, above);
+    over.lanes_le(Simd::splat(0.))
+        .select(Simd::splat(0.), reduction)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::TAU;
+
+    use nih_plug::prelude::*;
+
+    use crate::simd::f32x2;
+
+    use super::{gain_reduction, Limiter, KNEE};
+
+    #[test]
+    fn knee_is_continuous() {
+        let ceiling = -6.;
+        let reduction = |db: f32| gain_reduction(ceiling, f32x2::splat(util::db_to_gain(db)))[0];
+        assert_eq!(0., reduction(ceiling - KNEE));
+        assert_eq!(0., reduction(ceiling - KNEE / 2.));
+        assert!((reduction(ceiling + KNEE / 2.) + KNEE / 2.).abs() < 1e-3);
+        assert!((reduction(ceiling + 12.) + 12.).abs() < 1e-3);
+        // Comes out no louder than the ceiling anywhere along the curve
+        for i in 0..100 {
+            let db = ceiling - KNEE + i as f32 * 0.2;
+            assert!(db + reduction(db) <= ceiling + 1e-3, "{db} dB");
+        }
+        assert_eq!(0., gain_reduction(ceiling, f32x2::splat(0.))[0]);
+    }
+
+    #[test]
+    fn burst_stays_under_ceiling() {
+        let samplerate = 44100.;
+        let ceiling = util::db_to_gain(-6.);
+        let mut limiter = Limiter::new(samplerate);
+        let mut peak = 0f32;
+        for i in 0..samplerate as usize {
+            let burst = if (4410..8820).contains(&i) { 4. } else { 0.1 };
+            let x = burst * f32::sin(TAU * 220. * i as f32 / samplerate);
+            let y = limiter.next_sample(ceiling, f32x2::from_array([x, -0.5 * x]));
+            peak = peak.max(y.abs().reduce_max());
+        }
+        assert!(peak <= ceiling * 1.001, "{peak}");
+    }
+
+    #[test]
+    fn transparent_below_knee() {
+        let samplerate = 44100.;
+        let mut limiter = Limiter::new(samplerate);
+        for i in 0..1000 {
+            let x = f32x2::splat(0.25 * f32::sin(TAU * 440. * i as f32 / samplerate));
+            assert_eq!(x, limiter.next_sample(1., x));
+        }
+    }
+}
@@ -0,0 +1,117 @@
+// Copyright (c) 2022 solarliner
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use nih_plug::prelude::*;
+
+/// Note holding the freeze mode while pressed, C3 on any channel.
+pub(crate) const FREEZE_NOTE: u8 = 60;
+/// Note holding the reverse mode while pressed, D3 on any channel.
+pub(crate) const REVERSE_NOTE: u8 = 62;
+/// Controller holding the freeze mode, the sustain pedal.
+const FREEZE_CC: u8 = 64;
+/// Controller holding the reverse mode, the sostenuto pedal.
+const REVERSE_CC: u8 = 66;
+
+/// Momentary modes held from MIDI, on top of their parameters. The note and the pedal of a mode
+/// are tracked separately, so that letting go of one keeps the mode held by the other.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MidiActions {
+    freeze_note: bool,
+    freeze_cc: bool,
+    reverse_note: bool,
+    reverse_cc: bool,
+}
+
+impl MidiActions {
+    pub fn handle(&mut self, event: NoteEvent) {
+        match event {
+            NoteEvent::NoteOn { note, .. } => self.set_note(note, true),
+            NoteEvent::NoteOff { note, .. } => self.set_note(note, false),
+            NoteEvent::MidiCC { cc, value, .. } => {
+                let held = value >= 0.5;
+                match cc {
+                    FREEZE_CC => self.freeze_cc = held,
+                    REVERSE_CC => self.reverse_cc = held,
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn set_note(&mut self, note: u8, held: bool) {
+        match note {
+            FREEZE_NOTE => self.freeze_note = held,
+            REVERSE_NOTE => self.reverse_note = held,
+            _ => {}
+        }
+    }
+
+    pub fn freeze(&self) -> bool {
+        self.freeze_note || self.freeze_cc
+    }
+
+    pub fn reverse(&self) -> bool {
+        self.reverse_note || self.reverse_cc
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use nih_plug::prelude::*;
+
+    use super::{MidiActions, FREEZE_CC, FREEZE_NOTE, REVERSE_NOTE};
+
+    pub(crate) fn note(on: bool, note: u8) -> NoteEvent {
+        if on {
+            NoteEvent::NoteOn {
+                timing: 0,
+                voice_id: None,
+                channel: 0,
+                note,
+                velocity: 1.,
+            }
+        } else {
+            NoteEvent::NoteOff {
+                timing: 0,
+                voice_id: None,
+                channel: 0,
+                note,
+                velocity: 0.,
+            }
+        }
+    }
+
+    #[test]
+    fn notes_hold_modes() {
+        let mut midi = MidiActions::default();
+        midi.handle(note(true, FREEZE_NOTE));
+        assert!(midi.freeze() && !midi.reverse());
+        midi.handle(note(true, REVERSE_NOTE));
+        assert!(midi.reverse());
+        midi.handle(note(false, FREEZE_NOTE));
+        assert!(!midi.freeze() && midi.reverse());
+        // Other notes are ignored
+        midi.handle(note(false, REVERSE_NOTE + 1));
+        assert!(midi.reverse());
+    }
+
+    #[test]
+    fn pedal_and_note_hold_independently() {
+        let mut midi = MidiActions::default();
+        let pedal = |value| NoteEvent::MidiCC {
+            timing: 0,
+            channel: 0,
+            cc: FREEZE_CC,
+            value,
+        };
+        midi.handle(pedal(1.));
+        midi.handle(note(true, FREEZE_NOTE));
+        midi.handle(pedal(0.));
+        assert!(midi.freeze());
+        midi.handle(note(false, FREEZE_NOTE));
+        assert!(!midi.freeze());
+    }
+}
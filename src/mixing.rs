@@ -0,0 +1,134 @@
+// Copyright (c) 2022 solarliner
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use crate::simd::{LaneCount, Simd, SupportedLaneCount};
+use crate::{hadamard, householder};
+
+/// Orthogonal matrix mixing every lane of a diffuser or feedback network into the others. Being
+/// orthogonal, the mix preserves the energy of the signal, so that the feedback gain alone sets
+/// the decay.
+pub trait Mixing<const L: usize>
+where
+    LaneCount<L>: SupportedLaneCount,
+{
+    fn mix(&self, x: Simd<f32, L>) -> Simd<f32, L>;
+}
+
+/// Householder reflection, see [`householder::transform`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Householder;
+
+impl<const L: usize> Mixing<L> for Householder
+where
+    LaneCount<L>: SupportedLaneCount,
+{
+    #[inline]
+    fn mix(&self, x: Simd<f32, L>) -> Simd<f32, L> {
+        householder::transform(x)
+    }
+}
+
+/// Normalized Hadamard matrix, see [`hadamard::fwht_normalized`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Hadamard;
+
+impl<const L: usize> Mixing<L> for Hadamard
+where
+    LaneCount<L>: SupportedLaneCount,
+{
+    #[inline]
+    fn mix(&self, x: Simd<f32, L>) -> Simd<f32, L> {
+        hadamard::fwht_normalized(x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+
+    use crate::simd::{LaneCount, Simd, SupportedLaneCount};
+
+    use super::{Hadamard, Householder, Mixing};
+
+    fn random<const L: usize>(rng: &mut StdRng) -> Simd<f32, L>
+    where
+        LaneCount<L>: SupportedLaneCount,
+    {
+        Simd::from_array(std::array::from_fn(|_| rng.gen_range(-1. ..1.)))
+    }
+
+    fn dot<const L: usize>(a: Simd<f32, L>, b: Simd<f32, L>) -> f32
+    where
+        LaneCount<L>: SupportedLaneCount,
+    {
+        (a * b).reduce_sum()
+    }
+
+    fn assert_involution<const L: usize, M: Mixing<L>>(matrix: M)
+    where
+        LaneCount<L>: SupportedLaneCount,
+    {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            let input = random::<L>(&mut rng);
+            let output = matrix.mix(matrix.mix(input));
+            for (a, b) in input.to_array().into_iter().zip(output.to_array()) {
+                assert!((a - b).abs() < 1e-5, "{input:?} != {output:?}");
+            }
+        }
+    }
+
+    fn assert_preserves_norm<const L: usize, M: Mixing<L>>(matrix: M)
+    where
+        LaneCount<L>: SupportedLaneCount,
+    {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            let input = random::<L>(&mut rng);
+            let output = matrix.mix(input);
+            let (a, b) = (dot(input, input).sqrt(), dot(output, output).sqrt());
+            assert!((a - b).abs() < 1e-5, "{a} != {b}");
+        }
+    }
+
+    fn assert_orthogonal<const L: usize, M: Mixing<L>>(matrix: M)
+    where
+        LaneCount<L>: SupportedLaneCount,
+    {
+        let basis = |i: usize| {
+            let mut e = Simd::splat(0.);
+            e[i] = 1.;
+            e
+        };
+        let columns: [Simd<f32, L>; L] = std::array::from_fn(|i| matrix.mix(basis(i)));
+        for i in 0..L {
+            for j in 0..L {
+                let expected = if i == j { 1. } else { 0. };
+                let actual = dot(columns[i], columns[j]);
+                assert!((actual - expected).abs() < 1e-6, "{i}, {j}: {actual}");
+            }
+        }
+    }
+
+    #[test]
+    fn householder_is_orthogonal_involution() {
+        assert_involution::<4, _>(Householder);
+        assert_involution::<8, _>(Householder);
+        assert_preserves_norm::<4, _>(Householder);
+        assert_preserves_norm::<8, _>(Householder);
+        assert_orthogonal::<4, _>(Householder);
+        assert_orthogonal::<8, _>(Householder);
+    }
+
+    #[test]
+    fn hadamard_is_orthogonal_involution() {
+        assert_involution::<4, _>(Hadamard);
+        assert_involution::<8, _>(Hadamard);
+        assert_preserves_norm::<4, _>(Hadamard);
+        assert_preserves_norm::<8, _>(Hadamard);
+        assert_orthogonal::<4, _>(Hadamard);
+        assert_orthogonal::<8, _>(Hadamard);
+    }
+}
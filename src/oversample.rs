@@ -0,0 +1,254 @@
+// Copyright (c) 2022 solarliner
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+
+use nih_plug::prelude::*;
+
+use crate::simd::{f32x2, Simd};
+
+/// Half the number of non-zero side taps of the half-band filters. The filters are `4 * K - 1`
+/// taps long, delaying the signal by `2 * K - 1` samples at the higher rate.
+const K: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum Oversampling {
+    #[id = "1x"]
+    #[name = "Off"]
+    Off,
+    #[id = "2x"]
+    #[name = "2x"]
+    X2,
+    #[id = "4x"]
+    #[name = "4x"]
+    X4,
+}
+
+/// Taps of the half-band filter at odd offsets from its center, from the nearest outwards. The
+/// center tap is 0.5 and the taps at even offsets are all zero.
+fn half_band_taps() -> [f32; K] {
+    let len = (4 * K - 1) as f32;
+    let taps = std::array::from_fn(|i| {
+        let offset = (2 * i + 1) as f32;
+        let sinc = f32::sin(PI * offset / 2.) / (PI * offset / 2.);
+        // Blackman window, evaluated at the tap's position in the filter
+        let t = (len - 1.) / 2. + offset;
+        let window = 0.42 - 0.5 * f32::cos(2. * PI * t / (len - 1.))
+            + 0.08 * f32::cos(4. * PI * t / (len - 1.));
+        0.5 * sinc * window
+    });
+    // Normalize the side taps so that both polyphase branches have unity gain at DC
+    let sum: f32 = taps.iter().sum::<f32>() * 2.;
+    taps.map(|tap| tap * 0.5 / sum)
+}
+
+/// Polyphase half-band interpolator and decimator pair, converting between a base rate and twice
+/// that rate.
+#[derive(Debug, Clone)]
+struct HalfBand {
+    taps: [f32; K],
+    /// Base rate input of the interpolator, newest first.
+    up: VecDeque<f32x2>,
+    /// Even samples of the decimator input, newest first.
+    down_even: VecDeque<f32x2>,
+    /// Odd samples of the decimator input, newest first.
+    down_odd: VecDeque<f32x2>,
+}
+
+impl HalfBand {
+    fn new() -> Self {
+        let silence = |len| VecDeque::from(vec![Simd::splat(0.); len]);
+        Self {
+            taps: half_band_taps(),
+            up: silence(2 * K),
+            down_even: silence(2 * K),
+            down_odd: silence(K + 1),
+        }
+    }
+
+    fn reset(&mut self) {
+        for x in self
+            .up
+            .iter_mut()
+            .chain(&mut self.down_even)
+            .chain(&mut self.down_odd)
+        {
+            *x = Simd::splat(0.);
+        }
+    }
+
+    /// Symmetric FIR over the side taps, centered between the `K - 1`th and `K`th samples.
+    fn side_taps(&self, history: &VecDeque<f32x2>) -> f32x2 {
+        self.taps
+            .iter()
+            .enumerate()
+            .fold(Simd::splat(0.), |acc, (i, &tap)| {
+                acc + Simd::splat(tap) * (history[K - 1 - i] + history[K + i])
+            })
+    }
+
+    /// Produces the two samples at the higher rate for one base rate sample.
+    fn upsample(&mut self, x: f32x2) -> [f32x2; 2] {
+        self.up.pop_back();
+        self.up.push_front(x);
+        // Zero-stuffing doubles the gain needed to keep the level
+        let even = Simd::splat(2.) * self.side_taps(&self.up);
+        let odd = self.up[K - 1];
+        [even, odd]
+    }
+
+    /// Produces one base rate sample from two samples at the higher rate.
+    fn downsample(&mut self, [even, odd]: [f32x2; 2]) -> f32x2 {
+        self.down_even.pop_back();
+        self.down_even.push_front(even);
+        self.down_odd.pop_back();
+        self.down_odd.push_front(odd);
+        self.side_taps(&self.down_even) + Simd::splat(0.5) * self.down_odd[K]
+    }
+}
+
+/// Runs a nonlinear function at 2 or 4 times the sample rate, filtering out the harmonics it
+/// generates above the base Nyquist frequency before they fold back as aliasing.
+#[derive(Debug, Clone)]
+pub struct Oversampler {
+    factor: Oversampling,
+    stages: [HalfBand; 2],
+    /// Last output of the inner stage, held back by a sample at twice the base rate. This pads the
+    /// half sample of latency the inner stage leaves at 4x to a whole sample.
+    pad: f32x2,
+}
+
+impl Oversampler {
+    pub fn new() -> Self {
+        Self {
+            factor: Oversampling::Off,
+            stages: [HalfBand::new(), HalfBand::new()],
+            pad: Simd::splat(0.),
+        }
+    }
+
+    /// Changes the oversampling factor, clearing the filters when it changes.
+    pub fn set_factor(&mut self, factor: Oversampling) {
+        if factor != self.factor {
+            self.factor = factor;
+            self.reset();
+        }
+    }
+
+    /// Clears the filters.
+    pub fn reset(&mut self) {
+        for stage in &mut self.stages {
+            stage.reset();
+        }
+        self.pad = Simd::splat(0.);
+    }
+
+    /// Delay added by the filters, in samples at the base rate. The outer stage delays by
+    /// `2 * K - 1` samples, and the inner one by half as many, which the padding at 4x rounds up to
+    /// a whole sample.
+    pub fn latency(&self) -> u32 {
+        let stage = (2 * K - 1) as u32;
+        match self.factor {
+            Oversampling::Off => 0,
+            Oversampling::X2 => stage,
+            Oversampling::X4 => stage + (stage + 1) / 2,
+        }
+    }
+
+    pub fn process(&mut self, x: f32x2, f: impl Fn(f32x2) -> f32x2) -> f32x2 {
+        let [outer, inner] = &mut self.stages;
+        match self.factor {
+            Oversampling::Off => f(x),
+            Oversampling::X2 => {
+                let up = outer.upsample(x);
+                outer.downsample(up.map(f))
+            }
+            Oversampling::X4 => {
+                let [even, odd] = outer.upsample(x).map(|x| {
+                    let up = inner.upsample(x);
+                    inner.downsample(up.map(&f))
+                });
+                let padded = [self.pad, even];
+                self.pad = odd;
+                outer.downsample(padded)
+            }
+        }
+    }
+}
+
+impl Default for Oversampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::TAU;
+
+    use super::{Oversampler, Oversampling};
+    use crate::simd::f32x2;
+    use crate::simdmath::simd_f32tanh;
+
+    /// Magnitude of the given frequency in the signal, with the frequency falling on a bin.
+    fn magnitude(signal: &[f32], samplerate: f32, freq: f32) -> f32 {
+        let (re, im) = signal
+            .iter()
+            .enumerate()
+            .fold((0., 0.), |(re, im), (i, x)| {
+                let phase = TAU * freq * i as f32 / samplerate;
+                (re + x * phase.cos(), im - x * phase.sin())
+            });
+        (re * re + im * im).sqrt() * 2. / signal.len() as f32
+    }
+
+    fn saturate(factor: Oversampling, freq: f32, samplerate: f32) -> Vec<f32> {
+        let mut oversampler = Oversampler::new();
+        oversampler.set_factor(factor);
+        let len = samplerate as usize;
+        let output: Vec<f32> = (0..2 * len)
+            .map(|i| {
+                let x = 4. * f32::sin(TAU * freq * i as f32 / samplerate);
+                oversampler.process(f32x2::splat(x), simd_f32tanh)[0]
+            })
+            .collect();
+        output[len..].to_vec()
+    }
+
+    #[test]
+    fn passes_low_frequencies() {
+        let samplerate = 48e3;
+        for factor in [Oversampling::X2, Oversampling::X4] {
+            let mut oversampler = Oversampler::new();
+            oversampler.set_factor(factor);
+            let latency = oversampler.latency() as usize;
+            let input: Vec<f32> = (0..4800)
+                .map(|i| f32::sin(TAU * 1e3 * i as f32 / samplerate))
+                .collect();
+            let output: Vec<f32> = input
+                .iter()
+                .map(|&x| oversampler.process(f32x2::splat(x), |x| x)[0])
+                .collect();
+            // Delayed by exactly the reported latency
+            for (x, y) in input.iter().zip(&output[latency..]).skip(100) {
+                assert!((x - y).abs() < 1e-3, "{factor:?}: {x} != {y}");
+            }
+        }
+    }
+
+    #[test]
+    fn reduces_aliasing() {
+        let samplerate = 48e3;
+        // The 3rd harmonic of a 15 kHz tone, at 45 kHz, folds back down to 3 kHz
+        let alias = |factor| magnitude(&saturate(factor, 15e3, samplerate), samplerate, 3e3);
+        let none = alias(Oversampling::Off);
+        let x2 = alias(Oversampling::X2);
+        let x4 = alias(Oversampling::X4);
+        assert!(none > 0.1, "{none}");
+        assert!(x2 < 0.1 * none, "2x: {x2} vs {none}");
+        assert!(x4 < 0.1 * none, "4x: {x4} vs {none}");
+    }
+}
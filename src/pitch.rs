@@ -3,41 +3,119 @@
 // This software is released under the MIT License.
 // https://opensource.org/licenses/MIT
 
-use std::simd::{LaneCount, Simd, SupportedLaneCount};
+use std::f32::consts::PI;
 
 use crate::delay::Delay;
+use crate::simd::{LaneCount, Simd, SupportedLaneCount};
 
+/// Delay-line pitch shifter with two read heads half a window apart, crossfaded with a raised
+/// cosine so that each head is silent when it wraps around.
 pub struct PitchShifter<const N: usize>
 where
     LaneCount<N>: SupportedLaneCount,
 {
     buffer: Delay<Simd<f32, N>>,
-    pos: f32,
+    phase: f32,
 }
 
 impl<const N: usize> PitchShifter<N>
 where
     LaneCount<N>: SupportedLaneCount,
 {
+    /// Creates a pitch shifter with a window of `max_delay` samples, which is also its buffer
+    /// length. The window should hold at least a period of the lowest frequency being shifted,
+    /// but the read heads lag half a window behind the input, and each one sweeps the window once
+    /// every `window / |1 - pitch|` samples. Ratios far from unity, like two octaves down, make
+    /// for the shortest grains; a window of a few tens of milliseconds keeps those smooth without
+    /// smearing the signal in time.
     pub fn new(max_delay: usize) -> Self {
         Self {
             buffer: Delay::new(max_delay),
-            pos: 0.,
+            phase: 0.,
         }
     }
 
+    /// Clears the buffer and brings the read heads back to their starting positions.
+    pub fn reset(&mut self) {
+        self.buffer.reset();
+        self.phase = 0.;
+    }
+
+    /// Average delay of the read heads behind the input, in samples.
+    pub fn latency(&self) -> f32 {
+        1. + self.window() / 2.
+    }
+
     pub fn next_sample(
         &mut self,
         samplerate: f32,
         pitch: f32,
         input: Simd<f32, N>,
     ) -> Simd<f32, N> {
-        let out = self.buffer.tap(self.pos);
-        self.pos += pitch;
-        if self.pos > self.buffer.len() as _ {
-            self.pos -= self.buffer.len() as f32;
-        }
+        let window = self.window();
+        let out = [self.phase, (self.phase + 0.5).fract()]
+            .into_iter()
+            .map(|phase| {
+                let gain = f32::sin(PI * phase).powi(2);
+                let pos = self.buffer.clamp_position(1. + phase * window);
+                self.buffer.tap(pos) * Simd::splat(gain)
+            })
+            .fold(Simd::splat(0.), |acc, x| acc + x);
+        self.phase = (self.phase - (pitch - 1.) / window).rem_euclid(1.);
         self.buffer.push_next(input);
         out
     }
+
+    fn window(&self) -> f32 {
+        (self.buffer.len() as f32 - 3.).max(1.)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::TAU;
+
+    use crate::simd::Simd;
+
+    use super::PitchShifter;
+
+    #[test]
+    fn no_discontinuities() {
+        let mut pitch = PitchShifter::<1>::new(2048);
+        let mut last = 0.;
+        for i in 0..20000 {
+            let input = Simd::splat(f32::sin(TAU * 0.01 * i as f32));
+            let out = pitch.next_sample(44100., 2., input)[0];
+            if i > 2048 {
+                assert!(
+                    (out - last).abs() < 0.2,
+                    "jump of {} at sample {i}",
+                    out - last
+                );
+            }
+            last = out;
+        }
+    }
+
+    #[test]
+    fn detected_pitch_matches_ratio() {
+        let freq = 0.01;
+        for semitones in [-12., -7., 7., 12.] {
+            let ratio = f32::powf(2., semitones / 12.);
+            let mut pitch = PitchShifter::<1>::new(2048);
+            let out: Vec<f32> = (0..40000)
+                .map(|i| {
+                    let input = Simd::splat(f32::sin(TAU * freq * i as f32));
+                    pitch.next_sample(44100., ratio, input)[0]
+                })
+                .skip(4096)
+                .collect();
+            let crossings = out.windows(2).filter(|w| w[0] < 0. && w[1] >= 0.).count();
+            let detected = crossings as f32 / out.len() as f32 / freq;
+            assert!(
+                (detected - ratio).abs() < 0.03 * ratio,
+                "detected ratio {detected}, expected {ratio}"
+            );
+        }
+    }
 }
@@ -0,0 +1,222 @@
+// Copyright (c) 2022 solarliner
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use nih_plug::prelude::*;
+
+use crate::DelayParams;
+
+use PresetValue::{Bool, Float, Variant};
+
+/// Value of a single parameter in a preset.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PresetValue {
+    Float(f32),
+    Bool(bool),
+    /// Name of the selected variant of an enum parameter, as shown to the user.
+    Variant(&'static str),
+}
+
+impl PresetValue {
+    /// Normalized value of `param` once set to this value, or `None` if the value is not one of
+    /// the parameter's variants.
+    ///
+    /// # Safety
+    ///
+    /// `param` must point to a parameter that is still alive.
+    unsafe fn normalized(self, param: ParamPtr) -> Option<f32> {
+        match self {
+            Float(value) => Some(param.preview_normalized(value)),
+            Bool(value) => Some(if value { 1. } else { 0. }),
+            Variant(name) => param.string_to_normalized_value(name),
+        }
+    }
+}
+
+/// Snapshot of the parameters, by parameter ID. Parameters left out go back to their default value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preset {
+    pub name: &'static str,
+    pub values: &'static [(&'static str, PresetValue)],
+}
+
+impl Preset {
+    /// Normalized value of every parameter of `params` once the preset is loaded, by parameter ID.
+    pub(crate) fn normalized_values(&self, params: &DelayParams) -> Vec<(String, ParamPtr, f32)> {
+        let defaults = DelayParams::default();
+        let defaults = defaults.param_map();
+        params
+            .param_map()
+            .into_iter()
+            .zip(defaults)
+            .map(|((id, param, _), (_, default, _))| {
+                let value = self
+                    .values
+                    .iter()
+                    .find(|(preset_id, _)| *preset_id == id)
+                    // SAFETY: both parameters are alive for the duration of this function
+                    .and_then(|&(_, value)| unsafe { value.normalized(param) })
+                    .unwrap_or_else(|| unsafe { default.normalized_value() });
+                (id, param, value)
+            })
+            .collect()
+    }
+}
+
+pub const FACTORY_PRESETS: &[Preset] = &[
+    Preset {
+        name: "Small Room",
+        values: &[
+            ("ersize", Float(0.2)),
+            ("delay", Float(0.01)),
+            ("dcmode", Variant("Decay Time")),
+            ("decay", Float(0.6)),
+            ("mddpt", Float(0.05)),
+            ("dlow", Float(80.)),
+            ("dhigh", Float(6000.)),
+            ("late", Float(0.3)),
+            ("mix", Float(0.3)),
+        ],
+    },
+    Preset {
+        name: "Large Hall",
+        values: &[
+            ("ersize", Float(0.9)),
+            ("delay", Float(0.04)),
+            ("dcmode", Variant("Decay Time")),
+            ("decay", Float(4.5)),
+            ("mddpt", Float(0.15)),
+            ("mdspd", Float(0.2)),
+            ("dlow", Float(60.)),
+            ("dhigh", Float(4000.)),
+            ("late", Float(0.7)),
+            ("width", Float(1.3)),
+            ("mix", Float(0.35)),
+        ],
+    },
+    Preset {
+        name: "Plate",
+        values: &[
+            ("ersize", Float(0.5)),
+            ("delay", Float(0.005)),
+            ("dcmode", Variant("Decay Time")),
+            ("decay", Float(2.2)),
+            ("mddpt", Float(0.3)),
+            ("mdspd", Float(1.2)),
+            ("dlow", Float(200.)),
+            ("dhigh", Float(9000.)),
+            ("late", Float(0.5)),
+            ("mix", Float(0.3)),
+        ],
+    },
+    Preset {
+        name: "Shimmer Pad",
+        values: &[
+            ("ersize", Float(1.0)),
+            ("delay", Float(0.3)),
+            ("dcmode", Variant("Decay Time")),
+            ("decay", Float(12.)),
+            ("mddpt", Float(0.4)),
+            ("mdspd", Float(0.1)),
+            ("mdshape", Variant("Triangle")),
+            ("dlow", Float(150.)),
+            ("dhigh", Float(5000.)),
+            ("shimr", Float(0.5)),
+            ("shimint", Float(12.)),
+            ("late", Float(0.8)),
+            ("width", Float(1.5)),
+            ("mix", Float(0.5)),
+        ],
+    },
+    Preset {
+        name: "Infinite Freeze",
+        values: &[
+            ("ersize", Float(1.0)),
+            ("delay", Float(0.5)),
+            ("mddpt", Float(0.2)),
+            ("dlow", Float(40.)),
+            ("dhigh", Float(8000.)),
+            ("late", Float(1.0)),
+            ("freeze", Bool(true)),
+            ("mix", Float(1.0)),
+        ],
+    },
+    Preset {
+        name: "Slapback",
+        values: &[
+            ("ersize", Float(0.0)),
+            ("delay", Float(0.12)),
+            ("dcmode", Variant("Feedback")),
+            ("fbck", Float(0.2)),
+            ("mddpt", Float(0.0)),
+            ("late", Float(0.0)),
+            ("mix", Float(0.25)),
+        ],
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use nih_plug::prelude::*;
+
+    use super::{Preset, FACTORY_PRESETS};
+    use crate::DelayParams;
+
+    fn find(name: &str) -> &'static Preset {
+        FACTORY_PRESETS
+            .iter()
+            .find(|preset| preset.name == name)
+            .unwrap()
+    }
+
+    /// Value of the parameter `id` once `preset` is loaded, formatted as it is shown to the user.
+    fn loaded(preset: &Preset, id: &str) -> String {
+        let params = DelayParams::default();
+        let (_, param, value) = preset
+            .normalized_values(&params)
+            .into_iter()
+            .find(|(param_id, _, _)| param_id == id)
+            .unwrap();
+        unsafe { param.normalized_value_to_string(value, false) }
+    }
+
+    #[test]
+    fn presets_apply_documented_values() {
+        let hall = find("Large Hall");
+        assert_eq!("4.50", loaded(hall, "decay"));
+        assert_eq!("Decay Time", loaded(hall, "dcmode"));
+        // Left out of the preset
+        assert_eq!(loaded(find("Plate"), "freeze"), loaded(hall, "freeze"));
+
+        let freeze = find("Infinite Freeze");
+        assert_ne!(loaded(hall, "freeze"), loaded(freeze, "freeze"));
+
+        let shimmer = find("Shimmer Pad");
+        assert_eq!("Triangle", loaded(shimmer, "mdshape"));
+        assert_eq!("50.00", loaded(shimmer, "shimr"));
+    }
+
+    #[test]
+    fn presets_are_current() {
+        let params = DelayParams::default();
+        let param_map = params.param_map();
+        let mut names = BTreeSet::new();
+        for preset in FACTORY_PRESETS {
+            assert!(names.insert(preset.name), "duplicate {}", preset.name);
+            for &(id, value) in preset.values {
+                let (_, param, _) = param_map
+                    .iter()
+                    .find(|(param_id, _, _)| param_id == id)
+                    .unwrap_or_else(|| panic!("{} sets unknown {id}", preset.name));
+                assert!(
+                    unsafe { value.normalized(*param) }.is_some(),
+                    "{} sets {id} to {value:?}",
+                    preset.name
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,99 @@
+// Copyright (c) 2022 solarliner
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use crate::simd::{f32x2, Simd};
+
+/// Plays a signal back in reversed segments of a fixed length.
+///
+/// Each segment is recorded while the previous one is played backwards, so a sample recorded at
+/// offset `i` into a segment of length `len` comes out `2 * len - 1 - 2 * i` samples later: a
+/// decaying tail comes out as a swell, cut off where it started. On average, the output lags the
+/// input by `len` samples.
+#[derive(Debug, Clone)]
+pub struct Reverser {
+    buffer: Vec<f32x2>,
+    pos: usize,
+    fade: f32,
+}
+
+impl Reverser {
+    /// Creates a reverser with segments of `len` samples, faded in and out over `fade` samples to
+    /// avoid clicks at the boundaries.
+    pub fn new(len: usize, fade: usize) -> Self {
+        Self {
+            buffer: vec![Simd::splat(0.); 2 * len.max(1)],
+            pos: 0,
+            fade: fade.max(1) as f32,
+        }
+    }
+
+    /// Length of the segments, which is also the average latency, in samples.
+    pub fn segment_len(&self) -> usize {
+        self.buffer.len() / 2
+    }
+
+    /// Clears the recorded segments and starts a new one.
+    pub fn reset(&mut self) {
+        self.buffer.fill(Simd::splat(0.));
+        self.pos = 0;
+    }
+
+    pub fn next_sample(&mut self, x: f32x2) -> f32x2 {
+        let len = self.segment_len();
+        let (segment, i) = (self.pos / len, self.pos % len);
+        let y = self.buffer[(1 - segment) * len + len - 1 - i];
+        self.buffer[self.pos] = x;
+        self.pos = (self.pos + 1) % self.buffer.len();
+
+        let edge = i.min(len - 1 - i) as f32;
+        y * Simd::splat((edge / self.fade).min(1.))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+
+    use crate::simd::f32x2;
+
+    use super::Reverser;
+
+    #[test]
+    fn decaying_tail_swells_then_cuts() {
+        let len = 4800;
+        let mut reverser = Reverser::new(len, 48);
+        let mut rng = StdRng::seed_from_u64(0);
+        // Exponentially decaying noise, starting at the beginning of a segment
+        let output: Vec<f32> = (0..3 * len)
+            .map(|i| {
+                let x = rng.gen_range(-1. ..1.) * f32::exp(-(i as f32) / len as f32 * 3.);
+                reverser.next_sample(f32x2::splat(x))[0]
+            })
+            .collect();
+
+        let envelope: Vec<f32> = output
+            .chunks(len / 10)
+            .map(|c| (c.iter().map(|x| x * x).sum::<f32>() / c.len() as f32).sqrt())
+            .collect();
+        // Silent while the first segment is recorded
+        assert!(envelope[..10].iter().all(|&x| x == 0.));
+        // Rising over the reversed segment
+        for w in envelope[10..20].windows(2) {
+            assert!(w[1] > w[0], "{} <= {}", w[1], w[0]);
+        }
+        // Then cut, as the next segment starts with the quietest part of the tail
+        assert!(envelope[20] < 0.1 * envelope[19]);
+    }
+
+    #[test]
+    fn segments_are_reversed() {
+        let mut reverser = Reverser::new(8, 1);
+        let output: Vec<f32> = (0..16)
+            .map(|i| reverser.next_sample(f32x2::splat(i as f32))[0])
+            .collect();
+        // Edges are faded out, the rest plays the first segment backwards
+        assert_eq!(&[0., 6., 5., 4., 3., 2., 1., 0.], &output[8..]);
+    }
+}
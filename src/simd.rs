@@ -0,0 +1,524 @@
+// Copyright (c) 2022 solarliner
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+//! Vector types the DSP code is written against. With the `simd` feature, on by default, these are
+//! the `std::simd` types, which need a nightly compiler. Without it they are replaced by a scalar
+//! implementation of the same API, processing one lane at a time, so that the crate builds on
+//! stable Rust at the cost of some performance.
+
+#[cfg(feature = "simd")]
+pub use std::simd::*;
+
+#[cfg(not(feature = "simd"))]
+pub use scalar::*;
+
+#[cfg(not(feature = "simd"))]
+mod scalar {
+    use std::{
+        fmt::Debug,
+        iter::Sum,
+        marker::PhantomData,
+        ops::{
+            Add, AddAssign, BitAnd, BitOr, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg,
+            Not, Shl, Shr, Sub, SubAssign,
+        },
+        slice::SliceIndex,
+    };
+
+    /// Number of lanes of a vector, mirroring `std::simd::LaneCount`. Every lane count is
+    /// supported by the scalar implementation, the bound only keeps signatures identical.
+    pub struct LaneCount<const LANES: usize>;
+
+    pub trait SupportedLaneCount {}
+
+    impl<const LANES: usize> SupportedLaneCount for LaneCount<LANES> {}
+
+    /// Types that can be held in the lanes of a [`Simd`] vector.
+    pub trait SimdElement: Copy + Default + PartialEq + PartialOrd + Debug {}
+
+    impl SimdElement for f32 {}
+    impl SimdElement for f64 {}
+    impl SimdElement for i32 {}
+    impl SimdElement for u32 {}
+    impl SimdElement for i64 {}
+    impl SimdElement for u64 {}
+    impl SimdElement for usize {}
+    impl SimdElement for isize {}
+
+    /// Conversion between lane types, with the semantics of `as`.
+    pub trait SimdCast<U>: SimdElement {
+        fn cast(self) -> U;
+    }
+
+    macro_rules! impl_cast {
+        ($($from:ty => $to:ty),*) => {
+            $(
+                impl SimdCast<$to> for $from {
+                    #[inline(always)]
+                    fn cast(self) -> $to {
+                        self as $to
+                    }
+                }
+            )*
+        };
+    }
+
+    impl_cast!(f32 => i32, f32 => u32, i32 => f32, i32 => u32, u32 => f32, u32 => i32);
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    #[repr(transparent)]
+    pub struct Simd<T, const LANES: usize>([T; LANES]);
+
+    #[allow(non_camel_case_types)]
+    pub type f32x2 = Simd<f32, 2>;
+    #[allow(non_camel_case_types)]
+    pub type f32x4 = Simd<f32, 4>;
+    #[allow(non_camel_case_types)]
+    pub type f32x8 = Simd<f32, 8>;
+
+    impl<T: SimdElement, const LANES: usize> Simd<T, LANES> {
+        #[inline(always)]
+        pub fn splat(value: T) -> Self {
+            Self([value; LANES])
+        }
+
+        #[inline(always)]
+        pub fn from_array(array: [T; LANES]) -> Self {
+            Self(array)
+        }
+
+        #[inline(always)]
+        pub fn to_array(self) -> [T; LANES] {
+            self.0
+        }
+
+        #[inline(always)]
+        pub fn as_array(&self) -> &[T; LANES] {
+            &self.0
+        }
+
+        #[inline(always)]
+        pub fn as_mut_array(&mut self) -> &mut [T; LANES] {
+            &mut self.0
+        }
+
+        /// Reads each lane from `slice` at the index in the same lane of `idxs`, or the default
+        /// value when the index is out of bounds.
+        #[inline(always)]
+        pub fn gather_or_default(slice: &[T], idxs: Simd<usize, LANES>) -> Self {
+            Self(idxs.0.map(|i| slice.get(i).copied().unwrap_or_default()))
+        }
+
+        /// Interleaves the lanes of both vectors, returning the first and second halves of the
+        /// result.
+        #[inline(always)]
+        pub fn interleave(self, other: Self) -> (Self, Self) {
+            let lane = |i: usize| {
+                if i % 2 == 0 {
+                    self.0[i / 2]
+                } else {
+                    other.0[i / 2]
+                }
+            };
+            (
+                Self(std::array::from_fn(&lane)),
+                Self(std::array::from_fn(|i| lane(i + LANES))),
+            )
+        }
+
+        #[inline(always)]
+        pub fn cast<U: SimdElement>(self) -> Simd<U, LANES>
+        where
+            T: SimdCast<U>,
+        {
+            Simd(self.0.map(T::cast))
+        }
+
+        #[inline(always)]
+        pub fn lanes_eq(self, other: Self) -> Mask<i32, LANES> {
+            self.compare(other, |a, b| a == b)
+        }
+
+        #[inline(always)]
+        pub fn lanes_ne(self, other: Self) -> Mask<i32, LANES> {
+            self.compare(other, |a, b| a != b)
+        }
+
+        #[inline(always)]
+        pub fn lanes_lt(self, other: Self) -> Mask<i32, LANES> {
+            self.compare(other, |a, b| a < b)
+        }
+
+        #[inline(always)]
+        pub fn lanes_le(self, other: Self) -> Mask<i32, LANES> {
+            self.compare(other, |a, b| a <= b)
+        }
+
+        #[inline(always)]
+        pub fn lanes_gt(self, other: Self) -> Mask<i32, LANES> {
+            self.compare(other, |a, b| a > b)
+        }
+
+        #[inline(always)]
+        pub fn lanes_ge(self, other: Self) -> Mask<i32, LANES> {
+            self.compare(other, |a, b| a >= b)
+        }
+
+        #[inline(always)]
+        fn compare(self, other: Self, f: impl Fn(T, T) -> bool) -> Mask<i32, LANES> {
+            Mask::from_array(std::array::from_fn(|i| f(self.0[i], other.0[i])))
+        }
+
+        #[inline(always)]
+        fn map(self, f: impl Fn(T) -> T) -> Self {
+            Self(self.0.map(f))
+        }
+
+        #[inline(always)]
+        fn zip(self, other: Self, f: impl Fn(T, T) -> T) -> Self {
+            Self(std::array::from_fn(|i| f(self.0[i], other.0[i])))
+        }
+    }
+
+    impl<const LANES: usize> Simd<f32, LANES> {
+        #[inline(always)]
+        pub fn abs(self) -> Self {
+            self.map(f32::abs)
+        }
+
+        #[inline(always)]
+        pub fn floor(self) -> Self {
+            self.map(f32::floor)
+        }
+
+        #[inline(always)]
+        pub fn ceil(self) -> Self {
+            self.map(f32::ceil)
+        }
+
+        #[inline(always)]
+        pub fn round(self) -> Self {
+            self.map(f32::round)
+        }
+
+        #[inline(always)]
+        pub fn trunc(self) -> Self {
+            self.map(f32::trunc)
+        }
+
+        #[inline(always)]
+        pub fn fract(self) -> Self {
+            self.map(f32::fract)
+        }
+
+        #[inline(always)]
+        pub fn sqrt(self) -> Self {
+            self.map(f32::sqrt)
+        }
+
+        #[inline(always)]
+        pub fn recip(self) -> Self {
+            self.map(f32::recip)
+        }
+
+        #[inline(always)]
+        pub fn signum(self) -> Self {
+            self.map(f32::signum)
+        }
+
+        #[inline(always)]
+        pub fn min(self, other: Self) -> Self {
+            self.zip(other, f32::min)
+        }
+
+        #[inline(always)]
+        pub fn max(self, other: Self) -> Self {
+            self.zip(other, f32::max)
+        }
+
+        #[inline(always)]
+        pub fn clamp(self, min: Self, max: Self) -> Self {
+            Self(std::array::from_fn(|i| self.0[i].clamp(min.0[i], max.0[i])))
+        }
+
+        #[inline(always)]
+        pub fn is_nan(self) -> Mask<i32, LANES> {
+            Mask::from_array(self.0.map(f32::is_nan))
+        }
+
+        #[inline(always)]
+        pub fn is_finite(self) -> Mask<i32, LANES> {
+            Mask::from_array(self.0.map(f32::is_finite))
+        }
+
+        #[inline(always)]
+        pub fn to_bits(self) -> Simd<u32, LANES> {
+            Simd(self.0.map(f32::to_bits))
+        }
+
+        #[inline(always)]
+        pub fn from_bits(bits: Simd<u32, LANES>) -> Self {
+            Self(bits.0.map(f32::from_bits))
+        }
+
+        #[inline(always)]
+        pub fn reduce_sum(self) -> f32 {
+            self.0.into_iter().sum()
+        }
+
+        #[inline(always)]
+        pub fn reduce_max(self) -> f32 {
+            self.0.into_iter().fold(f32::NEG_INFINITY, f32::max)
+        }
+
+        #[inline(always)]
+        pub fn reduce_min(self) -> f32 {
+            self.0.into_iter().fold(f32::INFINITY, f32::min)
+        }
+    }
+
+    impl<T: SimdElement, const LANES: usize> Default for Simd<T, LANES> {
+        #[inline(always)]
+        fn default() -> Self {
+            Self::splat(T::default())
+        }
+    }
+
+    impl<T, I: SliceIndex<[T]>, const LANES: usize> Index<I> for Simd<T, LANES> {
+        type Output = I::Output;
+
+        #[inline(always)]
+        fn index(&self, index: I) -> &Self::Output {
+            &self.0[index]
+        }
+    }
+
+    impl<T, I: SliceIndex<[T]>, const LANES: usize> IndexMut<I> for Simd<T, LANES> {
+        #[inline(always)]
+        fn index_mut(&mut self, index: I) -> &mut Self::Output {
+            &mut self.0[index]
+        }
+    }
+
+    /// Implements a lanewise binary operator for vectors and references to vectors, along with
+    /// its assigning version when given.
+    macro_rules! impl_binary_op {
+        ($op:ident, $method:ident $(, $assign:ident, $assign_method:ident)?) => {
+            impl<T: SimdElement + $op<Output = T>, const LANES: usize> $op for Simd<T, LANES> {
+                type Output = Self;
+
+                #[inline(always)]
+                fn $method(self, rhs: Self) -> Self {
+                    self.zip(rhs, T::$method)
+                }
+            }
+
+            impl<T: SimdElement + $op<Output = T>, const LANES: usize> $op<&Simd<T, LANES>>
+                for Simd<T, LANES>
+            {
+                type Output = Self;
+
+                #[inline(always)]
+                fn $method(self, rhs: &Self) -> Self {
+                    self.$method(*rhs)
+                }
+            }
+
+            impl<T: SimdElement + $op<Output = T>, const LANES: usize> $op<Simd<T, LANES>>
+                for &Simd<T, LANES>
+            {
+                type Output = Simd<T, LANES>;
+
+                #[inline(always)]
+                fn $method(self, rhs: Simd<T, LANES>) -> Simd<T, LANES> {
+                    (*self).$method(rhs)
+                }
+            }
+
+            impl<T: SimdElement + $op<Output = T>, const LANES: usize> $op<&Simd<T, LANES>>
+                for &Simd<T, LANES>
+            {
+                type Output = Simd<T, LANES>;
+
+                #[inline(always)]
+                fn $method(self, rhs: &Simd<T, LANES>) -> Simd<T, LANES> {
+                    (*self).$method(*rhs)
+                }
+            }
+
+            $(
+                impl<T: SimdElement + $op<Output = T>, const LANES: usize> $assign
+                    for Simd<T, LANES>
+                {
+                    #[inline(always)]
+                    fn $assign_method(&mut self, rhs: Self) {
+                        *self = self.$method(rhs);
+                    }
+                }
+            )?
+        };
+    }
+
+    impl_binary_op!(Add, add, AddAssign, add_assign);
+    impl_binary_op!(Sub, sub, SubAssign, sub_assign);
+    impl_binary_op!(Mul, mul, MulAssign, mul_assign);
+    impl_binary_op!(Div, div, DivAssign, div_assign);
+    impl_binary_op!(BitAnd, bitand);
+    impl_binary_op!(BitOr, bitor);
+    impl_binary_op!(Shl, shl);
+    impl_binary_op!(Shr, shr);
+
+    impl<T: SimdElement + Neg<Output = T>, const LANES: usize> Neg for Simd<T, LANES> {
+        type Output = Self;
+
+        #[inline(always)]
+        fn neg(self) -> Self {
+            self.map(T::neg)
+        }
+    }
+
+    impl<T: SimdElement + Add<Output = T>, const LANES: usize> Sum for Simd<T, LANES> {
+        fn sum<It: Iterator<Item = Self>>(iter: It) -> Self {
+            iter.fold(Self::default(), Add::add)
+        }
+    }
+
+    impl<'a, T: SimdElement + Add<Output = T>, const LANES: usize> Sum<&'a Self> for Simd<T, LANES> {
+        fn sum<It: Iterator<Item = &'a Self>>(iter: It) -> Self {
+            iter.fold(Self::default(), |acc, x| acc + *x)
+        }
+    }
+
+    /// Lanewise boolean vector, as returned by the comparisons of [`Simd`]. The element type
+    /// only mirrors `std::simd::Mask`, every mask holds plain booleans.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Mask<T, const LANES: usize>([bool; LANES], PhantomData<T>);
+
+    impl<T, const LANES: usize> Mask<T, LANES> {
+        #[inline(always)]
+        pub fn splat(value: bool) -> Self {
+            Self([value; LANES], PhantomData)
+        }
+
+        #[inline(always)]
+        pub fn from_array(array: [bool; LANES]) -> Self {
+            Self(array, PhantomData)
+        }
+
+        #[inline(always)]
+        pub fn to_array(self) -> [bool; LANES] {
+            self.0
+        }
+
+        #[inline(always)]
+        pub fn test(&self, lane: usize) -> bool {
+            self.0[lane]
+        }
+
+        #[inline(always)]
+        pub fn any(self) -> bool {
+            self.0.contains(&true)
+        }
+
+        #[inline(always)]
+        pub fn all(self) -> bool {
+            !self.0.contains(&false)
+        }
+
+        /// Picks each lane from `true_values` where the mask is set, and from `false_values`
+        /// elsewhere.
+        #[inline(always)]
+        pub fn select<U: SimdElement>(
+            self,
+            true_values: Simd<U, LANES>,
+            false_values: Simd<U, LANES>,
+        ) -> Simd<U, LANES> {
+            Simd(std::array::from_fn(|i| {
+                if self.0[i] {
+                    true_values.0[i]
+                } else {
+                    false_values.0[i]
+                }
+            }))
+        }
+    }
+
+    impl<T, const LANES: usize> BitAnd for Mask<T, LANES> {
+        type Output = Self;
+
+        #[inline(always)]
+        fn bitand(self, rhs: Self) -> Self {
+            Self::from_array(std::array::from_fn(|i| self.0[i] & rhs.0[i]))
+        }
+    }
+
+    impl<T, const LANES: usize> BitOr for Mask<T, LANES> {
+        type Output = Self;
+
+        #[inline(always)]
+        fn bitor(self, rhs: Self) -> Self {
+            Self::from_array(std::array::from_fn(|i| self.0[i] | rhs.0[i]))
+        }
+    }
+
+    impl<T, const LANES: usize> Not for Mask<T, LANES> {
+        type Output = Self;
+
+        #[inline(always)]
+        fn not(self) -> Self {
+            Self::from_array(self.0.map(|lane| !lane))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{f32x4, Simd};
+
+    #[test]
+    fn lanewise_arithmetic() {
+        let a = f32x4::from_array([1., -2., 3.5, 0.]);
+        let b = f32x4::from_array([0.5, 4., -1., 2.]);
+        assert_eq!([1.5, 2., 2.5, 2.], (a + b).to_array());
+        assert_eq!([0.5, -6., 4.5, -2.], (a - b).to_array());
+        assert_eq!([0.5, -8., -3.5, 0.], (a * b).to_array());
+        assert_eq!([2., -0.5, -3.5, 0.], (a / b).to_array());
+        assert_eq!([-1., 2., -3.5, -0.], (-a).to_array());
+        assert_eq!(2.5, a.reduce_sum());
+        assert_eq!(3.5, a.reduce_max());
+        assert_eq!(-2., a.reduce_min());
+        assert_eq!(a * b, &a * &b);
+    }
+
+    #[test]
+    fn masks_select_lanes() {
+        let a = f32x4::from_array([1., f32::NAN, -3., f32::INFINITY]);
+        let negative = a.lanes_lt(Simd::splat(0.));
+        assert_eq!([false, false, true, false], negative.to_array());
+        let y = (negative | a.is_nan()).select(Simd::splat(0.), a);
+        assert_eq!([1., 0., 0., f32::INFINITY], y.to_array());
+        assert!(!a.is_finite().all() && a.is_finite().any());
+    }
+
+    #[test]
+    fn gather_and_interleave() {
+        let x = Simd::<f32, 2>::from_array([1., 2.]);
+        let gathered =
+            Simd::<f32, 4>::gather_or_default(x.as_array(), Simd::from_array([0, 1, 0, 5]));
+        assert_eq!([1., 2., 1., 0.], gathered.to_array());
+        let (first, second) = Simd::<f32, 4>::splat(-1.).interleave(Simd::splat(1.));
+        assert_eq!([-1., 1., -1., 1.], first.to_array());
+        assert_eq!([-1., 1., -1., 1.], second.to_array());
+    }
+
+    #[test]
+    fn bits_round_trip() {
+        let x = f32x4::from_array([0.5, -2., 1e-3, 7.]);
+        let bits = x.to_bits();
+        assert_eq!(x, Simd::<f32, 4>::from_bits(bits));
+        let exponent = ((bits >> Simd::splat(23)) & Simd::splat(0xff)).cast::<i32>();
+        assert_eq!([126, 128, 117, 129], exponent.to_array());
+    }
+}
@@ -3,7 +3,9 @@
 // This software is released under the MIT License.
 // https://opensource.org/licenses/MIT
 
-use std::simd::*;
+use std::f32::consts::{FRAC_1_SQRT_2, FRAC_PI_2, LOG2_E, PI, TAU};
+
+use crate::simd::*;
 
 #[inline(always)]
 pub fn simd_f32func<T: SimdElement, const LANES: usize>(
@@ -19,28 +21,204 @@ where
     x
 }
 
+/// Rational approximation of tanh. The error is within 5e-7, both absolute and relative, which is
+/// up to 7 ULPs. Inputs are clamped to the range beyond which tanh rounds to ±1 in single
+/// precision.
 #[inline(always)]
 pub fn simd_f32tanh<const LANES: usize>(x: Simd<f32, LANES>) -> Simd<f32, { LANES }>
 where
     LaneCount<LANES>: SupportedLaneCount,
 {
-    simd_f32func(f32::tanh, x)
+    const CLAMP: f32 = 7.905_311;
+    const ALPHA: [f32; 7] = [
+        -2.760_768_5e-16,
+        2.000_188e-13,
+        -8.604_672e-11,
+        5.122_297e-8,
+        1.485_722_4e-5,
+        6.372_619_3e-4,
+        4.893_524_6e-3,
+    ];
+    const BETA: [f32; 4] = [1.198_258_4e-6, 1.185_347e-4, 2.268_434_6e-3, 4.893_525e-3];
+
+    let x = x.clamp(Simd::splat(-CLAMP), Simd::splat(CLAMP));
+    let x2 = x * x;
+    let p = ALPHA
+        .into_iter()
+        .fold(Simd::splat(0.), |acc, a| acc * x2 + Simd::splat(a));
+    let q = BETA
+        .into_iter()
+        .fold(Simd::splat(0.), |acc, b| acc * x2 + Simd::splat(b));
+    x * p / q
+}
+
+/// Cubic soft clipper with unity gain around zero, reaching ±1 with a zero slope at ±1.5 and flat
+/// beyond.
+#[inline(always)]
+pub fn simd_f32softclip<const LANES: usize>(x: Simd<f32, LANES>) -> Simd<f32, { LANES }>
+where
+    LaneCount<LANES>: SupportedLaneCount,
+{
+    // `x - x³/3` flattens out at ±2/3 for inputs of ±1, stretched by the knee to flatten at ±1
+    const KNEE: f32 = 1.5;
+    let x = (x / Simd::splat(KNEE)).clamp(Simd::splat(-1.), Simd::splat(1.));
+    x * (Simd::splat(KNEE) - Simd::splat(KNEE / 3.) * x * x)
+}
+
+#[inline(always)]
+pub fn simd_f32hardclip<const LANES: usize>(x: Simd<f32, LANES>) -> Simd<f32, { LANES }>
+where
+    LaneCount<LANES>: SupportedLaneCount,
+{
+    x.clamp(Simd::splat(-1.), Simd::splat(1.))
+}
+
+/// Asymmetric saturation from a biased [`simd_f32tanh`], adding even harmonics like a tube
+/// stage. The bias is removed so that silence stays at zero, and the output is scaled back to unity
+/// gain around zero.
+#[inline(always)]
+pub fn simd_f32tube<const LANES: usize>(x: Simd<f32, LANES>) -> Simd<f32, { LANES }>
+where
+    LaneCount<LANES>: SupportedLaneCount,
+{
+    const BIAS: f32 = 0.2;
+    let offset = simd_f32tanh(Simd::splat(BIAS));
+    // The slope of tanh at the bias point
+    let slope = Simd::splat(1.) - offset * offset;
+    (simd_f32tanh(x + Simd::splat(BIAS)) - offset) / slope
+}
+
+/// Vectorized natural exponential, using the classic split into a power of two and a polynomial
+/// over the remaining fraction. Relative error is within 1e-6 over the representable range; inputs
+/// past it saturate to 0 or +inf like [`f32::exp`], and NaNs are propagated.
+#[inline(always)]
+pub fn simd_f32exp<const LANES: usize>(x: Simd<f32, LANES>) -> Simd<f32, { LANES }>
+where
+    LaneCount<LANES>: SupportedLaneCount,
+{
+    const MAX: f32 = 88.722_84;
+    const MIN: f32 = -87.336_55;
+    const COEFFS: [f32; 6] = [
+        1.987_569_2e-4,
+        1.398_199_9e-3,
+        8.333_452e-3,
+        4.166_579_6e-2,
+        1.666_666_5e-1,
+        5e-1,
+    ];
+
+    let clamped = x.clamp(Simd::splat(MIN), Simd::splat(MAX));
+    let n = (clamped * Simd::splat(LOG2_E)).round();
+    // ln(2) split into a part that is exactly representable and a small correction
+    let r = clamped - n * Simd::splat(0.693_359_4) - n * Simd::splat(-2.121_944_4e-4);
+    let r2 = r * r;
+    let p = COEFFS
+        .into_iter()
+        .fold(Simd::splat(0.), |acc, c| acc * r + Simd::splat(c));
+    let y = p * r2 + r + Simd::splat(1.);
+
+    // Scale by 2^n in two steps so that neither factor leaves the normal range
+    let n = n.cast::<i32>();
+    let n_half = n >> Simd::splat(1);
+    let y = y * exp2i(n_half) * exp2i(n - n_half);
+
+    let y = x
+        .lanes_gt(Simd::splat(MAX))
+        .select(Simd::splat(f32::INFINITY), y);
+    let y = x.lanes_lt(Simd::splat(MIN)).select(Simd::splat(0.), y);
+    x.is_nan().select(x, y)
 }
 
+/// Vectorized natural logarithm, extracting the exponent from the bit representation and
+/// evaluating a polynomial over the mantissa. The error is within 1e-6 for normal inputs (relative,
+/// or absolute close to 1 where the result approaches zero).
+/// Zero maps to -inf, negative inputs to NaN and +inf to itself, matching [`f32::ln`].
+#[inline(always)]
+pub fn simd_f32log<const LANES: usize>(x: Simd<f32, LANES>) -> Simd<f32, { LANES }>
+where
+    LaneCount<LANES>: SupportedLaneCount,
+{
+    const COEFFS: [f32; 9] = [
+        7.037_683_6e-2,
+        -1.151_461e-1,
+        1.167_699_9e-1,
+        -1.242_014_1e-1,
+        1.424_932_3e-1,
+        -1.666_805_8e-1,
+        2.000_071_4e-1,
+        -2.499_999_4e-1,
+        3.333_333e-1,
+    ];
+
+    let bits = x.to_bits();
+    let exponent = ((bits >> Simd::splat(23)) & Simd::splat(0xff)).cast::<i32>() - Simd::splat(126);
+    // Mantissa in [0.5, 1)
+    let mantissa =
+        Simd::<f32, LANES>::from_bits((bits & Simd::splat(0x807f_ffff)) | Simd::splat(0x3f00_0000));
+
+    // Shift the mantissa to [sqrt(0.5), sqrt(2)) so the polynomial is evaluated around 1
+    let small = mantissa.lanes_lt(Simd::splat(FRAC_1_SQRT_2));
+    let e = exponent.cast::<f32>() - small.select(Simd::splat(1.), Simd::splat(0.));
+    let m = small.select(mantissa + mantissa, mantissa) - Simd::splat(1.);
+
+    let m2 = m * m;
+    let p = COEFFS
+        .into_iter()
+        .fold(Simd::splat(0.), |acc, c| acc * m + Simd::splat(c));
+    let y = p * m * m2 + e * Simd::splat(-2.121_944_4e-4) - Simd::splat(0.5) * m2;
+    let y = m + y + e * Simd::splat(0.693_359_4);
+
+    let y = x
+        .lanes_eq(Simd::splat(0.))
+        .select(Simd::splat(f32::NEG_INFINITY), y);
+    let y = x.lanes_eq(Simd::splat(f32::INFINITY)).select(x, y);
+    (x.lanes_lt(Simd::splat(0.)) | x.is_nan()).select(Simd::splat(f32::NAN), y)
+}
+
+#[inline(always)]
+fn exp2i<const LANES: usize>(n: Simd<i32, LANES>) -> Simd<f32, LANES>
+where
+    LaneCount<LANES>: SupportedLaneCount,
+{
+    Simd::<f32, LANES>::from_bits(((n + Simd::splat(127)) << Simd::splat(23)).cast::<u32>())
+}
+
+/// Polynomial approximation of the cosine, see [`simd_f32sin`] for accuracy.
 #[inline(always)]
 pub fn simd_f32cos<const LANES: usize>(x: Simd<f32, LANES>) -> Simd<f32, { LANES }>
 where
     LaneCount<LANES>: SupportedLaneCount,
 {
-    simd_f32func(f32::cos, x)
+    simd_f32sin(x + Simd::splat(FRAC_PI_2))
 }
 
+/// Polynomial approximation of the sine. The input is reduced to [-π/2, π/2] where an 11th
+/// order odd polynomial is evaluated. The maximum absolute error is about 3e-7 over [-10, 10],
+/// growing with the rounding error of the range reduction for larger inputs.
 #[inline(always)]
 pub fn simd_f32sin<const LANES: usize>(x: Simd<f32, LANES>) -> Simd<f32, { LANES }>
 where
     LaneCount<LANES>: SupportedLaneCount,
 {
-    simd_f32func(f32::sin, x)
+    const COEFFS: [f32; 6] = [
+        -2.505_210_8e-8,
+        2.755_731_9e-6,
+        -1.984_127e-4,
+        8.333_333e-3,
+        -1.666_666_7e-1,
+        1.,
+    ];
+
+    let x = x - (x / Simd::splat(TAU)).round() * Simd::splat(TAU);
+    let x = x
+        .abs()
+        .lanes_gt(Simd::splat(FRAC_PI_2))
+        .select(x.signum() * Simd::splat(PI) - x, x);
+    let x2 = x * x;
+    let p = COEFFS
+        .into_iter()
+        .fold(Simd::splat(0.), |acc, c| acc * x2 + Simd::splat(c));
+    x * p
 }
 
 #[inline(always)]
@@ -50,3 +228,91 @@ where
 {
     simd_f32func(f32::tan, x)
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::simd::f32x4;
+
+    use super::*;
+
+    #[test]
+    fn tanh_matches_scalar() {
+        for i in -2000..=2000 {
+            let x = i as f32 * 5e-3;
+            let expected = f32x4::from_array([x, 2. * x, -x, 0.5 * x]);
+            let actual = simd_f32tanh(expected);
+            for (e, a) in expected.to_array().into_iter().zip(actual.to_array()) {
+                assert!((e.tanh() - a).abs() < 1e-6, "tanh({e}) = {a}");
+            }
+        }
+    }
+
+    #[test]
+    fn sin_cos_match_scalar() {
+        for i in -2000..=2000 {
+            let x = f32x4::splat(i as f32 * 5e-3);
+            let (sin, cos) = (simd_f32sin(x)[0], simd_f32cos(x)[0]);
+            assert!((x[0].sin() - sin).abs() < 1e-5, "sin({}) = {sin}", x[0]);
+            assert!((x[0].cos() - cos).abs() < 1e-5, "cos({}) = {cos}", x[0]);
+        }
+    }
+
+    #[test]
+    fn exp_matches_scalar() {
+        for i in -800..=800 {
+            let x = i as f32 * 0.1;
+            let actual = simd_f32exp(f32x4::splat(x))[0];
+            let expected = x.exp();
+            assert!(
+                ((actual - expected) / expected).abs() < 1e-5,
+                "exp({x}) = {actual}, expected {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn exp_edge_cases() {
+        let x = f32x4::from_array([-200., 200., f32::NEG_INFINITY, f32::NAN]);
+        let y = simd_f32exp(x);
+        assert_eq!(0., y[0]);
+        assert_eq!(f32::INFINITY, y[1]);
+        assert_eq!(0., y[2]);
+        assert!(y[3].is_nan());
+    }
+
+    #[test]
+    fn log_matches_scalar() {
+        for i in -300..=300 {
+            let x = f32::powf(10., i as f32 * 0.1);
+            let actual = simd_f32log(f32x4::splat(x))[0];
+            let expected = x.ln();
+            assert!(
+                (actual - expected).abs() <= 1e-5 * expected.abs().max(1.),
+                "log({x}) = {actual}, expected {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn log_edge_cases() {
+        let x = f32x4::from_array([0., -1., f32::INFINITY, f32::NAN]);
+        let y = simd_f32log(x);
+        assert_eq!(f32::NEG_INFINITY, y[0]);
+        assert!(y[1].is_nan());
+        assert_eq!(f32::INFINITY, y[2]);
+        assert!(y[3].is_nan());
+    }
+
+    #[test]
+    fn clippers_are_bounded() {
+        for i in -100..=100 {
+            let x = f32x4::splat(i as f32 * 0.1);
+            for y in [simd_f32softclip(x), simd_f32hardclip(x)] {
+                assert!(y[0].abs() <= 1., "{} -> {}", x[0], y[0]);
+            }
+            assert!(simd_f32tube(x)[0].abs() < 2.);
+        }
+        assert_eq!(0., simd_f32tube(f32x4::splat(0.))[0]);
+        assert_eq!(1., simd_f32softclip(f32x4::splat(1.5))[0]);
+    }
+}
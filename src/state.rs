@@ -0,0 +1,112 @@
+// Copyright (c) 2022 solarliner
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use nih_plug::prelude::*;
+
+use crate::STATE_VERSION;
+
+/// Key of the version in the persisted fields of the state.
+const VERSION_FIELD: &str = "version";
+
+/// Version of the parameter values, persisted alongside them. This implements [`Params`] by hand
+/// rather than holding a `#[persist]` field, as persisted fields are only set when they are found in
+/// the loaded state, while this needs to know about every state being loaded: the ones saved before
+/// the version was persisted have none, and are treated as version 0.
+pub(crate) struct StateVersion {
+    version: AtomicU32,
+    loaded: AtomicBool,
+}
+
+impl Default for StateVersion {
+    fn default() -> Self {
+        Self {
+            version: AtomicU32::new(STATE_VERSION),
+            loaded: AtomicBool::new(false),
+        }
+    }
+}
+
+impl StateVersion {
+    /// Version of the plugin the current parameter values come from, older than
+    /// [`STATE_VERSION`] until they are migrated.
+    pub fn get(&self) -> u32 {
+        self.version.load(Ordering::Relaxed)
+    }
+
+    /// Marks the parameter values as coming from `version`.
+    pub fn set(&self, version: u32) {
+        self.version.store(version, Ordering::Relaxed);
+    }
+
+    /// Whether a state was loaded since the last call.
+    pub fn take_loaded(&self) -> bool {
+        self.loaded.swap(false, Ordering::Relaxed)
+    }
+}
+
+unsafe impl Params for StateVersion {
+    fn param_map(&self) -> Vec<(String, ParamPtr, String)> {
+        Vec::new()
+    }
+
+    fn serialize_fields(&self) -> BTreeMap<String, String> {
+        // Values saved before they were migrated keep their version, so that they still are once
+        // loaded again
+        BTreeMap::from([(VERSION_FIELD.to_string(), self.get().to_string())])
+    }
+
+    fn deserialize_fields(&self, serialized: &BTreeMap<String, String>) {
+        let version = serialized
+            .get(VERSION_FIELD)
+            .and_then(|version| version.parse().ok())
+            .unwrap_or(0);
+        self.set(version);
+        self.loaded.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use nih_plug::prelude::*;
+
+    use super::StateVersion;
+    use crate::STATE_VERSION;
+
+    #[test]
+    fn fresh_instances_are_current() {
+        let version = StateVersion::default();
+        assert_eq!(STATE_VERSION, version.get());
+        assert!(!version.take_loaded());
+        assert_eq!(
+            Some(&STATE_VERSION.to_string()),
+            version.serialize_fields().get("version")
+        );
+    }
+
+    #[test]
+    fn legacy_states_are_version_0() {
+        let version = StateVersion::default();
+        version.deserialize_fields(&BTreeMap::new());
+        assert_eq!(0, version.get());
+        assert!(version.take_loaded());
+        assert!(!version.take_loaded());
+
+        let saved = version.serialize_fields();
+        version.deserialize_fields(&saved);
+        assert_eq!(0, version.get());
+    }
+
+    #[test]
+    fn versioned_states_keep_their_version() {
+        let version = StateVersion::default();
+        version.deserialize_fields(&BTreeMap::from([("version".to_string(), "1".to_string())]));
+        assert_eq!(1, version.get());
+    }
+}
@@ -0,0 +1,115 @@
+// Copyright (c) 2022 solarliner
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+//! Compares the impulse response of the reverb at its default parameters against a reference
+//! checked into `tests/data`, to catch unintended changes to the sound.
+//!
+//! When a change to the sound is intended, regenerate the reference with
+//! `UPDATE_REFERENCE_IR=1 cargo test --test reference_ir` and check in the new files.
+
+use std::fs;
+use std::path::PathBuf;
+
+use nih_reverb::Reverb;
+
+/// Sample rate [`Reverb::default`] runs at.
+const SAMPLERATE: f32 = 44100.;
+/// Length of the rendered impulse response, long enough to cover most of the default tail.
+const LEN: usize = 2 * SAMPLERATE as usize;
+/// Number of frames compared sample by sample, covering the pre-delay and the early reflections.
+const HEAD_LEN: usize = 4096;
+/// Length of the blocks the rest of the response is compared over, by their RMS level.
+const BLOCK_LEN: usize = 256;
+
+/// Largest difference allowed between samples of the head.
+const HEAD_TOLERANCE: f32 = 1e-4;
+/// Largest difference allowed between block levels, in dB.
+const ENVELOPE_TOLERANCE: f32 = 0.5;
+/// Blocks quieter than this in both responses are not compared, in dB.
+const ENVELOPE_FLOOR: f32 = -90.;
+
+fn data_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("data")
+        .join(name)
+}
+
+/// RMS level of each channel over consecutive blocks, in dB.
+fn envelope(ir: &[[f32; 2]]) -> Vec<[f32; 2]> {
+    ir.chunks(BLOCK_LEN)
+        .map(|block| {
+            [0, 1].map(|ch| {
+                let power = block.iter().map(|frame| frame[ch] * frame[ch]).sum::<f32>()
+                    / block.len() as f32;
+                10. * power.max(1e-20).log10()
+            })
+        })
+        .collect()
+}
+
+fn write(name: &str, frames: &[[f32; 2]]) {
+    let contents: String = frames
+        .iter()
+        .map(|[l, r]| format!("{l:e} {r:e}\n"))
+        .collect();
+    fs::write(data_path(name), contents).unwrap();
+}
+
+fn read(name: &str) -> Vec<[f32; 2]> {
+    let path = data_path(name);
+    let contents = fs::read_to_string(&path)
+        .unwrap_or_else(|err| panic!("Cannot read {}: {err}", path.display()));
+    contents
+        .lines()
+        .map(|line| {
+            let mut values = line.split_whitespace().map(|x| x.parse().unwrap());
+            [values.next().unwrap(), values.next().unwrap()]
+        })
+        .collect()
+}
+
+#[test]
+fn matches_reference_ir() {
+    let ir = Reverb::default().render_impulse_response(LEN);
+    let head = &ir[..HEAD_LEN];
+    let envelope = envelope(&ir);
+
+    if std::env::var_os("UPDATE_REFERENCE_IR").is_some() {
+        write("reference_ir_head.txt", head);
+        write("reference_ir_envelope.txt", &envelope);
+        return;
+    }
+
+    let reference = read("reference_ir_head.txt");
+    assert_eq!(reference.len(), head.len());
+    for (i, (expected, actual)) in reference.iter().zip(head).enumerate() {
+        for ch in 0..2 {
+            assert!(
+                (expected[ch] - actual[ch]).abs() <= HEAD_TOLERANCE,
+                "Frame {i}, channel {ch}: expected {}, got {}",
+                expected[ch],
+                actual[ch]
+            );
+        }
+    }
+
+    let reference = read("reference_ir_envelope.txt");
+    assert_eq!(reference.len(), envelope.len());
+    for (i, (expected, actual)) in reference.iter().zip(&envelope).enumerate() {
+        for ch in 0..2 {
+            if expected[ch].max(actual[ch]) < ENVELOPE_FLOOR {
+                continue;
+            }
+            assert!(
+                (expected[ch] - actual[ch]).abs() <= ENVELOPE_TOLERANCE,
+                "Block {i} ({:.3} s), channel {ch}: expected {} dB, got {} dB",
+                (i * BLOCK_LEN) as f32 / SAMPLERATE,
+                expected[ch],
+                actual[ch]
+            );
+        }
+    }
+}